@@ -0,0 +1,206 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// An immutable, reference-counted string. Cloning a `Symbol` copies a pointer, and two symbols
+/// obtained from the same `Interner` for the same text share one allocation, so comparing them is a
+/// pointer comparison. Symbols from different interners, or created directly with `From`, still
+/// compare equal when their text is equal.
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns `true` if both symbols share the same allocation.
+    pub fn ptr_eq(&self, other: &Symbol) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Default for Symbol {
+    fn default() -> Self {
+        Symbol::from("")
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Symbol(Arc::from(s))
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(s: String) -> Self {
+        Symbol(Arc::from(s))
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(s: &String) -> Self {
+        Symbol(Arc::from(s.as_str()))
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other) || self.0 == other.0
+    }
+}
+
+impl Eq for Symbol {}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        &*self.0 == other.as_str()
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.ptr_eq(other) {
+            return Ordering::Equal;
+        }
+        self.0.cmp(&other.0)
+    }
+}
+
+// Must agree with the Hash of str so that a HashSet<Symbol> can be searched with a &str.
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Symbol::from)
+    }
+}
+
+/// Hands out a single shared `Symbol` for each distinct string it is given.
+#[derive(Debug, Clone, Default)]
+pub struct Interner(HashSet<Symbol>);
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner(HashSet::new())
+    }
+
+    /// Returns the symbol for `s`, adding it to the interner if it has not been seen before.
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(symbol) = self.0.get(s) {
+            return symbol.clone();
+        }
+        let symbol = Symbol::from(s);
+        self.0.insert(symbol.clone());
+        symbol
+    }
+
+    /// Returns the symbol for `s` if it has already been interned.
+    pub fn get(&self, s: &str) -> Option<Symbol> {
+        self.0.get(s).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern_shares_allocations() {
+        let mut interner = Interner::new();
+        let a = interner.intern("cat");
+        let b = interner.intern(&String::from("cat"));
+        let c = interner.intern("dog");
+        assert!(a.ptr_eq(&b));
+        assert!(!a.ptr_eq(&c));
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.get("dog"), Some(c));
+        assert_eq!(interner.get("bird"), None);
+    }
+
+    #[test]
+    fn symbols_compare_by_text() {
+        let mut interner = Interner::new();
+        let interned = interner.intern("cat");
+        let other = Symbol::from("cat");
+        assert!(!interned.ptr_eq(&other));
+        assert_eq!(interned, other);
+        assert_eq!(interned, "cat");
+        assert!(interned < interner.intern("dog"));
+        assert_eq!(format!("{}", interned), "cat");
+    }
+}
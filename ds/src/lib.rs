@@ -1,6 +1,10 @@
 use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
 
+pub use interner::{Interner, Symbol};
+
+mod interner;
+
 #[derive(Debug, Eq)]
 pub struct Stack<T>(Vec<T>)
 where
@@ -48,6 +52,10 @@ where
         self.0.is_empty()
     }
 
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
     pub fn vec(&self) -> &Vec<T> {
         &self.0
     }
@@ -0,0 +1,56 @@
+//! Parsing throughput benchmarks. Run with `cargo bench -p ezxml`; criterion reports the
+//! throughput of each benchmark in MiB/s.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+/// Generates a catalog-like document of roughly `size` bytes with attributes, nesting, entity
+/// references and text content.
+fn generate_document(size: usize) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<catalog>\n");
+    let mut i = 0;
+    while xml.len() < size {
+        xml.push_str(&format!(
+            concat!(
+                "  <book id=\"bk{}\" lang=\"en\" available=\"true\">\n",
+                "    <author>Gambardella, Matthew</author>\n",
+                "    <title>XML Developer&apos;s Guide, volume {}</title>\n",
+                "    <genre>Computer</genre>\n",
+                "    <price currency=\"USD\">44.95</price>\n",
+                "    <description>An in-depth look at creating applications with XML &amp; ",
+                "related technologies. Covers elements, attributes, namespaces &lt;and&gt; ",
+                "more.</description>\n",
+                "    <tags><tag>xml</tag><tag>programming</tag><tag/></tags>\n",
+                "  </book>\n"
+            ),
+            i, i
+        ));
+        i += 1;
+    }
+    xml.push_str("</catalog>\n");
+    xml
+}
+
+fn parse_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_str");
+
+    let ezfile = xtest::load("ezfile").read_xml_file();
+    group.throughput(Throughput::Bytes(ezfile.len() as u64));
+    group.bench_function("ezfile", |b| {
+        b.iter(|| ezxml::parse_str(black_box(ezfile.as_str())).unwrap())
+    });
+
+    let catalog = generate_document(4 * 1024 * 1024);
+    group.throughput(Throughput::Bytes(catalog.len() as u64));
+    group.sample_size(20);
+    group.bench_function("catalog_4mb", |b| {
+        b.iter(|| ezxml::parse_str(black_box(catalog.as_str())).unwrap())
+    });
+    group.bench_function("catalog_4mb_borrowed", |b| {
+        b.iter(|| ezxml::parse_str_borrowed(black_box(catalog.as_str())).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, parse_benchmarks);
+criterion_main!(benches);
@@ -31,6 +31,20 @@ pub enum Error {
         source_line: u32,
         position: Position,
     },
+    /// Elements were nested more deeply than `ParseOpts::max_depth` allows.
+    #[snafu(display(
+        "{}:{} Maximum element depth of {} exceeded: {:?}",
+        source_file,
+        source_line,
+        max_depth,
+        position
+    ))]
+    MaxDepth {
+        source_file: String,
+        source_line: u32,
+        max_depth: usize,
+        position: Position,
+    },
     IoRead {
         parse_location: ParseLocation,
         source: std::io::Error,
@@ -43,6 +57,6 @@ pub enum Error {
 // used in `std::io::Read` implementations
 impl From<Error> for std::io::Error {
     fn from(err: Error) -> Self {
-        Self::new(std::io::ErrorKind::Other, err)
+        Self::other(err)
     }
 }
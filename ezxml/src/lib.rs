@@ -23,7 +23,12 @@ This initial list gets me to a sort of 'pre-mvp' that can handle only the simple
 #[macro_use]
 extern crate log;
 
-pub use parser::parse_str;
+#[cfg(feature = "tokio")]
+pub use parser::AsyncEventReader;
+pub use parser::{
+    parse_fragment, parse_fragment_opts, parse_str, parse_str_borrowed, parse_str_borrowed_opts,
+    parse_str_opts, Event, EventReader, ParseOpts, PushParser,
+};
 pub use xdoc::{Document, ElementData, Node};
 
 pub mod error;
@@ -0,0 +1,77 @@
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::error::Result;
+use crate::parser::reader::ReadState;
+use crate::parser::{Event, ParseOpts};
+
+/// An async pull parser that reads a document from a `tokio::io::AsyncRead` a chunk at a time. It
+/// produces the same events as `EventReader`, and waiting for input never blocks the executor.
+/// Requires the `tokio` feature.
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use ezxml::{AsyncEventReader, Event};
+///
+/// let mut reader = AsyncEventReader::new("<cat>bones</cat>".as_bytes());
+/// let mut names = Vec::new();
+/// while let Some(event) = reader.next_event().await.unwrap() {
+///     if let Event::Start { name, .. } = event {
+///         names.push(name);
+///     }
+/// }
+/// assert_eq!(names, vec!["cat"]);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AsyncEventReader<R: AsyncRead + Unpin> {
+    source: R,
+    st: ReadState,
+}
+
+impl<R: AsyncRead + Unpin> AsyncEventReader<R> {
+    pub fn new(source: R) -> Self {
+        Self::new_opts(source, &ParseOpts::default())
+    }
+
+    pub fn new_opts(source: R, opts: &ParseOpts) -> Self {
+        AsyncEventReader {
+            source,
+            st: ReadState::new(opts),
+        }
+    }
+
+    /// Returns the next event, or `None` once the end of the document has been reached.
+    pub async fn next_event(&mut self) -> Result<Option<Event<'static>>> {
+        loop {
+            if let Some(event) = self.st.next_ready() {
+                return Ok(Some(event));
+            }
+            if self.st.is_done() {
+                return Ok(None);
+            }
+            let result = self.source.read(&mut self.st.buf).await;
+            let n = self.st.io_err(result)?;
+            self.st.parse_chunk(n)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn long_text_is_read_in_linear_time() {
+        // a text node far longer than a chunk
+        let xml = format!("<a>{}</a>", "x".repeat(8 * 1024 * 1024));
+        let mut reader = AsyncEventReader::new(xml.as_bytes());
+        let mut events = Vec::new();
+        while events.len() < 3 {
+            events.push(reader.next_event().await.unwrap().unwrap());
+        }
+        assert!(matches!(&events[1], Event::Text(text) if text.len() == xml.len() - 7));
+        assert!(reader.st.lexed() < 2 * xml.len());
+        assert!(reader.next_event().await.unwrap().is_none());
+    }
+}
@@ -32,47 +32,71 @@ const U_203F: char = '\u{203F}';
 const U_2040: char = '\u{2040}';
 
 pub(crate) fn is_name_start_char(c: char) -> bool {
-    // TODO oops make sure its the same as 1.1 https://www.w3.org/TR/2006/REC-xml11-20060816/
+    // XML 1.1 has the same rule https://www.w3.org/TR/2006/REC-xml11-20060816/#NT-NameStartChar
     // https://www.w3.org/TR/2008/REC-xml-20081126/#NT-NameStartChar
     // [4]   	NameStartChar	   ::=   	":" | [A-Z] | "_" | [a-z] | [#xC0-#xD6] | [#xD8-#xF6] |
     // [#xF8-#x2FF] | [#x370-#x37D] | [#x37F-#x1FFF] | [#x200C-#x200D] | [#x2070-#x218F] |
     // [#x2C00-#x2FEF] | [#x3001-#xD7FF] | [#xF900-#xFDCF] | [#xFDF0-#xFFFD] | [#x10000-#xEFFFF]
     // let x = c as u64;
-    match c {
-        'A'..='Z' => true,
-        'a'..='z' => true,
-        ':' => true,
-        '_' => true,
-        U_00C0..=U_00D6 => true,
-        U_00D8..=U_00F6 => true,
-        U_00F8..=U_02FF => true,
-        U_0370..=U_037D => true,
-        U_037F..=U_1FFF => true,
-        U_200C..=U_200D => true,
-        U_2070..=U_218F => true,
-        U_2C00..=U_2FEF => true,
-        U_3001..=U_D7FF => true,
-        U_F900..=U_FDCF => true,
-        U_FDF0..=U_FFFD => true,
-        U_10000..=U_EFFFF => true,
-        _ => false,
-    }
+    matches!(c,
+        'A'..='Z'
+        | 'a'..='z'
+        | ':'
+        | '_'
+        | U_00C0..=U_00D6
+        | U_00D8..=U_00F6
+        | U_00F8..=U_02FF
+        | U_0370..=U_037D
+        | U_037F..=U_1FFF
+        | U_200C..=U_200D
+        | U_2070..=U_218F
+        | U_2C00..=U_2FEF
+        | U_3001..=U_D7FF
+        | U_F900..=U_FDCF
+        | U_FDF0..=U_FFFD
+        | U_10000..=U_EFFFF
+    )
 }
 
 pub(crate) fn is_name_char(c: char) -> bool {
-    // TODO oops make sure its the same as 1.1 https://www.w3.org/TR/2006/REC-xml11-20060816/
+    // XML 1.1 has the same rule https://www.w3.org/TR/2006/REC-xml11-20060816/#NT-NameChar
     // https://www.w3.org/TR/2008/REC-xml-20081126/#NT-NameChar
     // [4a] NameChar ::= NameStartChar | "-" | "." | [0-9] | #xB7 | [#x0300-#x036F] | [#x203F-#x2040]
     if is_name_start_char(c) {
         return true;
     }
+    matches!(c, U_00B7 | U_0300..=U_036F | U_203F..=U_2040 | '0'..='9' | '-' | '.')
+}
+
+/// Returns `true` if `c` may be written with a character reference.
+pub(crate) fn is_char(c: char, is_1_1: bool) -> bool {
+    // https://www.w3.org/TR/2008/REC-xml-20081126/#NT-Char
+    // [2] Char ::= #x9 | #xA | #xD | [#x20-#xD7FF] | [#xE000-#xFFFD] | [#x10000-#x10FFFF]
+    // https://www.w3.org/TR/2006/REC-xml11-20060816/#NT-Char
+    // [2] Char ::= [#x1-#xD7FF] | [#xE000-#xFFFD] | [#x10000-#x10FFFF]
     match c {
-        U_00B7 => true,
-        U_0300..=U_036F => true,
-        U_203F..=U_2040 => true,
-        '0'..='9' => true,
-        '-' => true,
-        '.' => true,
+        '\t' | '\n' | '\r' | ' '..=U_D7FF | '\u{E000}'..=U_FFFD | U_10000.. => true,
+        '\u{1}'..='\u{1F}' => is_1_1,
         _ => false,
     }
 }
+
+/// Returns `true` for the four whitespace characters allowed by the XML `S` production.
+#[inline]
+pub(crate) fn is_whitespace_byte(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r' | b'\n')
+}
+
+/// Returns `true` if `b` is an ASCII byte that can start a name. Bytes above `0x7F` return `false`
+/// and must be decoded and checked with `is_name_start_char`.
+#[inline]
+pub(crate) fn is_ascii_name_start_byte(b: u8) -> bool {
+    matches!(b, b'A'..=b'Z' | b'a'..=b'z' | b':' | b'_')
+}
+
+/// Returns `true` if `b` is an ASCII byte that can appear in a name. Bytes above `0x7F` return
+/// `false` and must be decoded and checked with `is_name_char`.
+#[inline]
+pub(crate) fn is_ascii_name_byte(b: u8) -> bool {
+    matches!(b, b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b':' | b'_' | b'-' | b'.')
+}
@@ -1,192 +1,188 @@
-use xdoc::{ElementData, Node, OrdMap};
+use std::borrow::Cow;
 
-use crate::error::{Error, Result};
-use crate::parser::{parse_name, Iter};
+use ds::Interner;
+use xdoc::borrowed::{self, Attribute};
+use xdoc::{ElementData, OrdMap};
 
-pub(crate) fn parse_element(iter: &mut Iter) -> Result<ElementData> {
-    iter.expect('<')?;
-    iter.advance_or_die()?;
-    let name = parse_name(iter)?;
-    let mut element = make_named_element(name.as_str())?;
+use crate::error::{Error, Result};
+use crate::parser::text::parse_attribute_value;
+use crate::parser::Iter;
 
-    // absorb whitespace
-    iter.skip_whitespace()?;
+/// The names of the elements whose start tag has been parsed but whose end tag has not yet been
+/// reached, innermost last. The names are stored end-to-end in a single `String` so that opening
+/// and closing elements does not allocate.
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
+pub(crate) struct OpenElements {
+    names: String,
+    ends: Vec<usize>,
+}
 
-    // check and return early if it is an empty, self-closing tag
-    if iter.is('/') {
-        //println!("It is a self-closing tag with no attributes, i.e. an 'empty' element.");
-        iter.advance_or_die()?;
-        iter.expect('>')?;
-        return Ok(element);
+impl OpenElements {
+    pub(crate) fn push(&mut self, name: &str) {
+        self.names.push_str(name);
+        self.ends.push(self.names.len());
     }
 
-    // now the only valid chars are '>' or the start of an attribute name
-    if iter.is_name_start_char() {
-        element.attributes = parse_attributes(iter)?;
+    pub(crate) fn pop(&mut self) {
+        self.ends.pop();
+        self.names.truncate(self.ends.last().copied().unwrap_or(0));
     }
 
-    // check and return early if it is an empty, self-closing tag that had attributes
-    if iter.is('/') {
-        //println!("It is a self-closing tag with no attributes, i.e. an 'empty' element.");
-        iter.advance_or_die()?;
-        iter.expect('>')?;
-        return Ok(element);
+    pub(crate) fn peek(&self) -> Option<&str> {
+        let end = *self.ends.last()?;
+        let start = match self.ends.len() {
+            1 => 0,
+            n => self.ends[n - 2],
+        };
+        Some(&self.names[start..end])
     }
 
-    // now the only valid char is '>' and we reach the child nodes
-    iter.expect('>')?;
-    iter.advance_or_die()?;
-    parse_children(iter, &mut element)?;
-    // TODO - expect to be pointing either at '>' or the iter is at the end
-    // TODO - implement
-    while iter.advance() {}
-    Ok(element)
-}
+    pub(crate) fn len(&self) -> usize {
+        self.ends.len()
+    }
 
-fn split_element_name(input: &str) -> Result<(&str, &str)> {
-    let split: Vec<&str> = input.split(':').collect();
-    match split.len() {
-        1 => Ok(("", split.first().unwrap())),
-        2 => Ok((split.first().unwrap(), split.last().unwrap())),
-        _ => Err(Error::Bug {
-            message: "Bad string cannot be split".to_string(),
-        }),
+    pub(crate) fn is_empty(&self) -> bool {
+        self.ends.is_empty()
     }
 }
 
-fn make_named_element(input: &str) -> Result<ElementData> {
-    let split = split_element_name(input)?;
-    Ok(ElementData {
-        namespace: match split.0 {
-            "" => None,
-            _ => Some(split.0.to_owned()),
-        },
-        name: split.1.to_string(),
-        attributes: Default::default(),
-        nodes: vec![],
-    })
-}
+pub(crate) type Attributes<'a> = Vec<Attribute<'a>>;
 
-fn parse_attributes(iter: &mut Iter) -> Result<OrdMap> {
-    let mut attributes = OrdMap::new();
-    loop {
-        iter.skip_whitespace()?;
-        if iter.is('/') || iter.is('>') {
-            break;
-        }
-        let key = if iter.is_name_start_char() {
-            parse_name(iter)?
-        } else {
-            String::default()
-        };
-        iter.skip_whitespace()?;
-        iter.expect('=')?;
-        iter.advance_or_die()?;
-        iter.skip_whitespace()?;
-        iter.expect('"')?;
-        iter.advance_or_die()?;
-        let value = parse_attribute_value(iter)?;
-        iter.expect('"')?;
-        attributes.mut_map().insert(key, value);
-        if !iter.advance() {
-            break;
-        }
+/// Parses a start tag and leaves the iter just past its closing '>'. Returns the element name, its
+/// attributes, and `true` if it is an empty, self-closing tag.
+pub(crate) fn parse_start_tag<'a>(
+    iter: &mut Iter<'a>,
+) -> Result<(Cow<'a, str>, Attributes<'a>, bool)> {
+    // the element we are about to parse will be nested one level deeper than the open elements
+    if iter.st.open.len() >= iter.opts.max_depth {
+        return Err(Error::MaxDepth {
+            source_file: file!().to_owned(),
+            source_line: line!(),
+            max_depth: iter.opts.max_depth,
+            position: iter.position_at(iter.pos),
+        });
     }
-    Ok(attributes)
+    iter.expect(b'<')?;
+    let name = iter.parse_name()?;
+    let attributes = parse_attributes(iter)?;
+
+    // check and return early if it is an empty, self-closing tag
+    let is_empty = iter.is(b'/');
+    if is_empty {
+        iter.pos += 1;
+    }
+    iter.expect(b'>')?;
+    iter.st.open.push(name);
+    Ok((Cow::Borrowed(name), attributes, is_empty))
 }
 
-fn parse_attribute_value(iter: &mut Iter) -> Result<String> {
-    let mut result = String::new();
+/// Parses attributes until the iter reaches the '/' or '>' that ends the start tag.
+fn parse_attributes<'a>(iter: &mut Iter<'a>) -> Result<Attributes<'a>> {
+    let mut attributes: Attributes<'a> = Vec::new();
     loop {
-        if iter.is('<') || iter.is('>') {
+        let had_whitespace = iter.skip_whitespace();
+        if iter.is(b'/') || iter.is(b'>') {
+            return Ok(attributes);
+        }
+        // attributes must be separated from the name and from each other by whitespace
+        if !had_whitespace {
             return Err(iter.err(file!(), line!()));
         }
-        if iter.is('"') {
-            break;
+        let key_start = iter.pos;
+        let key = iter.parse_name()?;
+        if attributes.iter().any(|a| a.key == key) {
+            return Err(iter.err_at(key_start, file!(), line!()));
         }
-        // TODO - handle escapes
-        result.push(iter.st.c);
-        iter.advance_or_die()?;
+        iter.skip_whitespace();
+        iter.expect(b'=')?;
+        iter.skip_whitespace();
+        let value = parse_attribute_value(iter)?;
+        attributes.push(Attribute {
+            key: Cow::Borrowed(key),
+            value,
+        });
     }
-    Ok(result)
 }
 
-fn parse_children(iter: &mut Iter, parent: &mut ElementData) -> Result<()> {
-    // TODO - support comments, processing instructions and whatever else
-    loop {
-        iter.skip_whitespace()?;
-        if iter.is('<') {
-            if let Some(node) = handle_left_angle(iter, parent)? {
-                parent.nodes.push(node);
-            } else {
-                // we received 'None' which means that the end tag was parsed
-                return Ok(());
-            }
-        } else {
-            let text = parse_text(iter)?;
-            parent.nodes.push(Node::String(text));
-            if iter.is('<') {
-                if let Some(node) = handle_left_angle(iter, parent)? {
-                    parent.nodes.push(node);
-                } else {
-                    // we received 'None' which means that the end tag was parsed
-                    return Ok(());
-                }
-            }
-        }
-        if !iter.advance() {
-            break;
-        }
+/// Parses an end tag, which must match the innermost open element, and leaves the iter just past
+/// its closing '>'.
+pub(crate) fn parse_end_tag<'a>(iter: &mut Iter<'a>) -> Result<Cow<'a, str>> {
+    let start = iter.pos;
+    iter.expect_str("</")?;
+    let name = iter.parse_name()?;
+    if iter.st.open.peek() != Some(name) {
+        return Err(iter.err_at(start, file!(), line!()));
     }
-    Ok(())
+    iter.skip_whitespace();
+    iter.expect(b'>')?;
+    Ok(Cow::Borrowed(name))
 }
 
-fn handle_left_angle(iter: &mut Iter, parent: &mut ElementData) -> Result<Option<Node>> {
-    if iter.peek_is('/') {
-        let end_tag_name = parse_end_tag_name(iter)?;
-        if end_tag_name != parent.fullname() {
-            return Err(iter.err(file!(), line!()));
-        }
-        // return None to signal that we have parsed and end tag
-        return Ok(None);
+fn split_element_name(input: &str) -> Result<(&str, &str)> {
+    match input.split_once(':') {
+        None => Ok(("", input)),
+        Some((_, local)) if local.contains(':') => Err(Error::Bug {
+            message: "Bad string cannot be split".to_string(),
+        }),
+        Some(split) => Ok(split),
     }
-    let element = parse_element(iter)?;
-    Ok(Some(Node::Element(element)))
 }
 
-fn parse_end_tag_name(iter: &mut Iter) -> Result<String> {
-    iter.expect('<')?;
-    iter.advance_or_die()?;
-    iter.expect('/')?;
-    iter.advance_or_die()?;
-    iter.skip_whitespace()?;
-    iter.expect_name_start_char()?;
-    let mut name = String::default();
-    name.push(iter.st.c);
-    loop {
-        iter.advance_or_die()?;
-        if iter.is('>') || iter.is_whitespace() {
-            break;
-        } else if iter.is_name_char() {
-            name.push(iter.st.c);
-        } else {
-            return Err(iter.err(file!(), line!()));
+fn make_named_element(names: &mut Interner, input: &str) -> Result<ElementData> {
+    let split = split_element_name(input)?;
+    Ok(ElementData {
+        namespace: match split.0 {
+            "" => None,
+            _ => Some(names.intern(split.0)),
+        },
+        name: names.intern(split.1),
+        attributes: Default::default(),
+        nodes: vec![],
+    })
+}
+
+/// Creates an owned `ElementData` from the parts of a start tag, taking its element and attribute
+/// names from `names`.
+pub(crate) fn make_element(
+    names: &mut Interner,
+    name: &str,
+    attributes: Attributes<'_>,
+) -> Result<ElementData> {
+    let mut element = make_named_element(names, name)?;
+    if !attributes.is_empty() {
+        let mut map = OrdMap::new();
+        for a in attributes {
+            map.mut_map()
+                .insert(names.intern(&a.key), a.value.into_owned());
         }
+        element.attributes = map;
     }
-    iter.skip_whitespace()?;
-    iter.expect('>')?;
-    Ok(name)
+    Ok(element)
 }
 
-fn parse_text(iter: &mut Iter) -> Result<String> {
-    let mut result = String::new();
-    loop {
-        if iter.is('<') {
-            break;
+/// Creates a `borrowed::ElementData` from the parts of a start tag without copying any strings.
+pub(crate) fn make_borrowed_element<'a>(
+    name: Cow<'a, str>,
+    attributes: Attributes<'a>,
+) -> Result<borrowed::ElementData<'a>> {
+    let (namespace, name) = match name {
+        Cow::Borrowed(s) => {
+            let (ns, local) = split_element_name(s)?;
+            (Cow::Borrowed(ns), Cow::Borrowed(local))
         }
-        // TODO - handle escapes
-        result.push(iter.st.c);
-        iter.advance_or_die()?;
-    }
-    Ok(result)
+        Cow::Owned(s) => {
+            let (ns, local) = split_element_name(&s)?;
+            (Cow::Owned(ns.to_owned()), Cow::Owned(local.to_owned()))
+        }
+    };
+    Ok(borrowed::ElementData {
+        namespace: if namespace.is_empty() {
+            None
+        } else {
+            Some(namespace)
+        },
+        name,
+        attributes,
+        nodes: vec![],
+    })
 }
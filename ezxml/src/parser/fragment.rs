@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use xdoc::Node;
+
+use crate::error::Result;
+use crate::parser::element::Attributes;
+use crate::parser::{Builder, Event, Iter, ParseOpts};
+
+/// Parses the content of an element, such as `<a/><b/>text`, which may hold any number of elements
+/// and text and may begin with a text declaration, i.e. an external parsed entity. The nodes can
+/// then be added to an existing tree.
+pub fn parse_fragment(s: &str) -> Result<Vec<Node>> {
+    parse_fragment_opts(s, &ParseOpts::default(), None)
+}
+
+/// Parses a fragment as `parse_fragment` does. `namespaces` holds the namespace bindings, prefix to
+/// namespace name, that are in scope where the fragment will be placed. When it is given, every
+/// prefix used in the fragment must either be bound there or be declared within the fragment.
+pub fn parse_fragment_opts(
+    s: &str,
+    opts: &ParseOpts,
+    namespaces: Option<&HashMap<String, String>>,
+) -> Result<Vec<Node>> {
+    let mut iter = Iter::new_fragment(s, opts);
+    let mut builder = Builder::new(opts);
+    let mut scopes = namespaces.map(Scopes::new);
+    loop {
+        let start = iter.pos;
+        let event = match iter.next_event()? {
+            Some(event) => event,
+            None => break,
+        };
+        trace!("{:?}", event);
+        if let Some(scopes) = scopes.as_mut() {
+            let is_bound = match &event {
+                Event::Start { name, attributes } => scopes.push(name, attributes),
+                Event::End { .. } => {
+                    scopes.pop();
+                    true
+                }
+                _ => true,
+            };
+            if !is_bound {
+                return Err(iter.err_at(start, file!(), line!()));
+            }
+        }
+        builder.push_event(event)?;
+    }
+    Ok(builder.top)
+}
+
+/// The namespace prefixes that are in scope at each open element of a fragment.
+struct Scopes<'n> {
+    context: &'n HashMap<String, String>,
+    /// The prefixes declared by each open element, innermost last.
+    declared: Vec<Vec<String>>,
+}
+
+impl<'n> Scopes<'n> {
+    fn new(context: &'n HashMap<String, String>) -> Self {
+        Scopes {
+            context,
+            declared: Vec::new(),
+        }
+    }
+
+    /// Enters an element. Returns `false` if the element or one of its attributes uses a prefix
+    /// that is not bound.
+    fn push(&mut self, name: &str, attributes: &Attributes<'_>) -> bool {
+        let declared: Vec<String> = attributes
+            .iter()
+            .filter_map(|a| a.key.strip_prefix("xmlns:"))
+            .map(str::to_owned)
+            .collect();
+        self.declared.push(declared);
+        let is_bound = |prefix: &str| {
+            prefix == "xml"
+                || self.context.contains_key(prefix)
+                || self.declared.iter().flatten().any(|p| p == prefix)
+        };
+        prefix(name).is_none_or(is_bound)
+            && attributes
+                .iter()
+                .filter_map(|a| prefix(&a.key))
+                .all(|p| p == "xmlns" || is_bound(p))
+    }
+
+    fn pop(&mut self) {
+        self.declared.pop();
+    }
+}
+
+fn prefix(name: &str) -> Option<&str> {
+    name.split_once(':').map(|(prefix, _)| prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    fn error_absolute(result: Result<Vec<Node>>) -> u64 {
+        match result.err().unwrap() {
+            Error::Parse { position, .. } => position.absolute,
+            e => panic!("expected Error::Parse, got {}", e),
+        }
+    }
+
+    #[test]
+    fn multiple_top_level_nodes() {
+        let nodes = parse_fragment("<a/>\n<b>x</b>text<!--c--><![CDATA[d]]>").unwrap();
+        assert_eq!(nodes.len(), 5);
+        assert!(matches!(&nodes[0], Node::Element(a) if a.name == "a"));
+        assert!(matches!(&nodes[1], Node::Element(b) if b.name == "b"));
+        assert_eq!(nodes[2], Node::String("text".to_string()));
+        assert_eq!(nodes[3], Node::Comment("c".to_string()));
+        assert_eq!(nodes[4], Node::CData("d".to_string()));
+        assert_eq!(parse_fragment("").unwrap(), vec![]);
+        assert_eq!(
+            parse_fragment("just text").unwrap(),
+            vec![Node::String("just text".to_string())]
+        );
+    }
+
+    #[test]
+    fn text_declaration() {
+        let nodes = parse_fragment("<?xml encoding=\"UTF-8\"?><a/>").unwrap();
+        assert_eq!(nodes.len(), 1);
+        let nodes = parse_fragment("<?pi?><a/>").unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert!(parse_fragment("<a/><?xml version=\"1.0\"?>").is_err());
+    }
+
+    #[test]
+    fn malformed_fragments() {
+        assert!(parse_fragment("</a>").is_err());
+        assert!(parse_fragment("<a>").is_err());
+        assert!(parse_fragment("<a></b>").is_err());
+        assert!(parse_fragment("<!DOCTYPE a><a/>").is_err());
+        assert!(parse_fragment("a & b").is_err());
+    }
+
+    #[test]
+    fn namespace_bindings() {
+        let mut context = HashMap::new();
+        context.insert("x".to_string(), "urn:x".to_string());
+        let parse = |s| parse_fragment_opts(s, &ParseOpts::default(), Some(&context));
+        assert!(parse("<x:a x:k='1'/>").is_ok());
+        assert!(parse("<y:a xmlns:y='urn:y'><y:b y:k='1'/></y:a>").is_ok());
+        assert!(parse("<a xml:lang='en'/>").is_ok());
+        assert_eq!(error_absolute(parse("<a/>\n<y:a/>")), 6);
+        assert_eq!(error_absolute(parse("<a y:k='1'/>")), 1);
+        // a declaration is only in scope within the element that makes it
+        assert_eq!(error_absolute(parse("<y:a xmlns:y='urn:y'/><y:b/>")), 23);
+        // without bindings the prefixes are not checked
+        assert!(parse_fragment("<y:a/>").is_ok());
+    }
+}
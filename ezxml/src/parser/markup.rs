@@ -0,0 +1,89 @@
+use crate::error::Result;
+use crate::parser::text::checked_text;
+use crate::parser::{DocStatus, Event, Iter};
+
+/// Parses the constructs that begin with `<!`: comments, CDATA sections and the DOCTYPE. Leaves the
+/// iter just past the closing '>'.
+pub(crate) fn parse_markup_declaration<'a>(iter: &mut Iter<'a>) -> Result<Event<'a>> {
+    if iter.starts_with(b"<!--") {
+        parse_comment(iter)
+    } else if iter.starts_with(b"<![CDATA[") {
+        parse_cdata(iter)
+    } else if iter.starts_with(b"<!DOCTYPE") {
+        parse_doctype(iter)
+    } else if [&b"<!--"[..], b"<![CDATA[", b"<!DOCTYPE"]
+        .iter()
+        .any(|keyword| keyword.starts_with(iter.rest()))
+    {
+        // the input ends part way through one of the keywords
+        Err(iter.end_err(file!(), line!()))
+    } else {
+        // point at the character following '<!'
+        iter.pos += 2;
+        Err(iter.err(file!(), line!()))
+    }
+}
+
+fn parse_comment<'a>(iter: &mut Iter<'a>) -> Result<Event<'a>> {
+    iter.pos += "<!--".len();
+    let start = iter.pos;
+    // '--' is not allowed within a comment, so the first one we find must be the end
+    let end = match iter.find(b"--") {
+        Some(end) => end,
+        None => return Err(iter.end_err(file!(), line!())),
+    };
+    let text = checked_text(iter, start, end)?;
+    iter.pos = end + 2;
+    iter.expect(b'>')?;
+    Ok(Event::Comment(text))
+}
+
+fn parse_cdata<'a>(iter: &mut Iter<'a>) -> Result<Event<'a>> {
+    if iter.st.doc_status != DocStatus::ProcessingRoot {
+        return Err(iter.err(file!(), line!()));
+    }
+    iter.pos += "<![CDATA[".len();
+    let start = iter.pos;
+    let end = match iter.find(b"]]>") {
+        Some(end) => end,
+        None => return Err(iter.end_err(file!(), line!())),
+    };
+    let text = checked_text(iter, start, end)?;
+    iter.pos = end + 3;
+    Ok(Event::CData(text))
+}
+
+/// Parses the DOCTYPE, including any internal subset, as a blob.
+fn parse_doctype<'a>(iter: &mut Iter<'a>) -> Result<Event<'a>> {
+    if iter.st.doc_status > DocStatus::BeforeRoot {
+        return Err(iter.err(file!(), line!()));
+    }
+    iter.pos += "<!DOCTYPE".len();
+    if !iter.skip_whitespace() {
+        return Err(iter.err(file!(), line!()));
+    }
+    let start = iter.pos;
+    // the DOCTYPE ends at the first '>' that is neither quoted nor inside the internal subset
+    let mut quote: Option<u8> = None;
+    let mut in_subset = false;
+    loop {
+        let b = match iter.peek() {
+            Some(b) => b,
+            None => return Err(iter.end_err(file!(), line!())),
+        };
+        match (quote, b) {
+            (Some(q), _) if b == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'"') | (None, b'\'') => quote = Some(b),
+            (None, b'[') => in_subset = true,
+            (None, b']') => in_subset = false,
+            (None, b'>') if !in_subset => break,
+            _ => {}
+        }
+        iter.pos += 1;
+    }
+    let end = start + iter.src[start..iter.pos].trim_end().len();
+    let text = checked_text(iter, start, end)?;
+    iter.pos += 1;
+    Ok(Event::DocType(text))
+}
@@ -1,19 +1,41 @@
 extern crate env_logger;
 
-use std::iter::Peekable;
-use std::str::Chars;
+use std::borrow::Cow;
+use std::cell::Cell;
 
+use ds::Interner;
 pub use ds::Stack;
-use xdoc::{Declaration, Document, Encoding, PIData, Version};
+use memchr::memmem;
+use xdoc::{borrowed, Declaration, Document, ElementData, Node, PIData, Version};
 
 use crate::error::{Error, Result};
-use crate::parser::chars::{is_name_char, is_name_start_char};
-use crate::parser::element::parse_element;
+use crate::parser::chars::{
+    is_ascii_name_byte, is_ascii_name_start_byte, is_name_char, is_name_start_char,
+    is_whitespace_byte,
+};
+use crate::parser::element::{
+    make_borrowed_element, make_element, parse_end_tag, parse_start_tag, Attributes, OpenElements,
+};
+use crate::parser::markup::parse_markup_declaration;
 use crate::parser::pi::parse_pi;
+use crate::parser::text::parse_text;
 
+#[cfg(feature = "tokio")]
+pub use crate::parser::async_reader::AsyncEventReader;
+pub use crate::parser::fragment::{parse_fragment, parse_fragment_opts};
+pub use crate::parser::push::PushParser;
+pub use crate::parser::reader::EventReader;
+
+#[cfg(feature = "tokio")]
+mod async_reader;
 mod chars;
 mod element;
+mod fragment;
+mod markup;
 mod pi;
+mod push;
+mod reader;
+mod text;
 
 #[derive(Debug, Clone, Copy, Eq, PartialOrd, PartialEq, Hash)]
 pub struct Position {
@@ -34,314 +56,555 @@ impl Default for Position {
 }
 
 impl Position {
-    fn increment(&mut self, current_char: char) {
+    /// Advances past `current_char`. The `previous_char` is needed because "\r\n" is a single line
+    /// end, as is "\r\u{85}" in XML 1.1.
+    fn increment(&mut self, current_char: char, previous_char: Option<char>, version: &Version) {
         self.absolute += 1;
-        if current_char == '\n' {
-            self.line += 1;
-            self.column = 0;
-        } else {
-            self.column += 1;
+        let is_1_1 = *version == Version::OneDotOne;
+        match current_char {
+            // the '\r' has already ended the line
+            '\n' if previous_char == Some('\r') => {}
+            '\u{85}' if is_1_1 && previous_char == Some('\r') => {}
+            '\n' | '\r' => self.new_line(),
+            '\u{85}' | '\u{2028}' if is_1_1 => self.new_line(),
+            _ => self.column += 1,
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.column = 0;
+    }
+}
+
+/// Options that control parsing. New options may be added, so outside of this crate the options are
+/// built from `ParseOpts::default()` with the `with_*` methods rather than with a struct literal.
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct ParseOpts {
+    /// The maximum number of elements that may be open at once, counting the root element. A
+    /// document that nests more deeply than this is rejected with `Error::MaxDepth`.
+    pub max_depth: usize,
+    /// Keeps text that is only whitespace, such as the indentation between elements, which is
+    /// otherwise dropped. Whitespace outside of the root element is always dropped.
+    pub preserve_whitespace: bool,
+}
+
+impl Default for ParseOpts {
+    fn default() -> Self {
+        ParseOpts {
+            max_depth: 1024,
+            preserve_whitespace: false,
         }
     }
 }
 
+impl ParseOpts {
+    /// Sets `max_depth`.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets `preserve_whitespace`.
+    pub fn with_preserve_whitespace(mut self, preserve_whitespace: bool) -> Self {
+        self.preserve_whitespace = preserve_whitespace;
+        self
+    }
+}
+
+/// The tokens produced by the parsers. Names and text are sliced from the input wherever possible
+/// and are only allocated when entity references have to be decoded.
 #[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash)]
+pub enum Event<'a> {
+    /// `<?xml version="1.0"?>`
+    Declaration(Declaration),
+    /// `<!DOCTYPE doc>`, the contents are a blob.
+    DocType(Cow<'a, str>),
+    /// `<name key="value">`. A self-closing tag produces a `Start` immediately followed by an `End`.
+    Start {
+        name: Cow<'a, str>,
+        attributes: Attributes<'a>,
+    },
+    /// `</name>`
+    End { name: Cow<'a, str> },
+    /// Character data with entity references decoded. Whitespace is reported too.
+    Text(Cow<'a, str>),
+    /// `<![CDATA[text]]>`
+    CData(Cow<'a, str>),
+    /// `<!-- comment -->`
+    Comment(Cow<'a, str>),
+    /// `<?target data?>`
+    ProcessingInstruction(PIData),
+}
+
+impl<'a> Event<'a> {
+    /// Copies any borrowed strings so that the event no longer refers to the input.
+    pub fn into_owned(self) -> Event<'static> {
+        let own = |s: Cow<'a, str>| Cow::Owned(s.into_owned());
+        match self {
+            Event::Declaration(declaration) => Event::Declaration(declaration),
+            Event::DocType(s) => Event::DocType(own(s)),
+            Event::Start { name, attributes } => Event::Start {
+                name: own(name),
+                attributes: attributes
+                    .into_iter()
+                    .map(|a| borrowed::Attribute {
+                        key: own(a.key),
+                        value: own(a.value),
+                    })
+                    .collect(),
+            },
+            Event::End { name } => Event::End { name: own(name) },
+            Event::Text(s) => Event::Text(own(s)),
+            Event::CData(s) => Event::CData(own(s)),
+            Event::Comment(s) => Event::Comment(own(s)),
+            Event::ProcessingInstruction(pi) => Event::ProcessingInstruction(pi),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialOrd, PartialEq, Hash, Default)]
+pub(crate) enum DocStatus {
+    #[default]
+    BeforeDeclaration,
+    BeforeRoot,
+    ProcessingRoot,
+    AfterRoot,
+}
+
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
 pub(crate) struct ParserState {
-    pub(crate) position: Position,
-    pub(crate) c: char,
     pub(crate) doc_status: DocStatus,
-    pub(crate) tag_status: TagStatus,
-    pub(crate) stack: Option<Stack<crate::Node>>,
+    pub(crate) open: OpenElements,
+    /// The input is the content of an element rather than a document: any number of elements and
+    /// text, optionally preceded by a text declaration.
+    pub(crate) is_fragment: bool,
+    /// Taken from the XML declaration, this selects the rules for line ends and allowed chars.
+    pub(crate) version: Version,
 }
 
+/// A tokenizer that scans the UTF-8 bytes of its input. Delimiters are located with `memchr`,
+/// names and text are sliced straight out of the input, and line and column numbers are only
+/// computed when an error needs them.
 pub(crate) struct Iter<'a> {
-    pub(crate) it: Peekable<Chars<'a>>,
+    pub(crate) src: &'a str,
+    pub(crate) bytes: &'a [u8],
+    /// The byte offset of the next byte to be scanned.
+    pub(crate) pos: usize,
     pub(crate) st: ParserState,
+    pub(crate) opts: ParseOpts,
+    /// The name of a self-closing element whose `End` event has not yet been returned.
+    pending_end: Option<Cow<'a, str>>,
+    /// The position just before `src`, which is not the start of the document when the input
+    /// arrives in pieces.
+    base: Position,
+    /// The position at the end of `src`, when the caller already knows it. Errors at the end of the
+    /// input are then reported without counting the chars of `src` again.
+    pub(crate) end_position: Option<Position>,
+    /// Set when an error is reported at the end of the input, i.e. where more input may have fixed
+    /// it.
+    pub(crate) hit_end: Cell<bool>,
 }
 
 impl<'a> Iter<'a> {
-    /// Returns an `Iter` primed with the first character, otherwise returns an error.
-    fn new(s: &'a str) -> Result<Self> {
-        let mut i = Iter {
-            it: s.chars().peekable(),
-            st: ParserState {
-                position: Default::default(),
-                c: 'x',
-                doc_status: Default::default(),
-                tag_status: Default::default(),
-                stack: None,
-            },
-        };
-        if !i.advance() {
-            return Err(Error::Parse {
-                source_file: file!().to_owned(),
-                source_line: line!(),
-                position: Position::default(),
-            });
-        }
-        Ok(i)
-    }
-
-    /// Returns `false` if the iterator could not be advanced (end).
-    pub(crate) fn advance(&mut self) -> bool {
-        let option_char = self.it.next();
-        match option_char {
-            Some(c) => {
-                self.st.c = c;
-                self.st.position.increment(self.st.c);
-                true
-            }
-            None => false,
+    pub(crate) fn new(s: &'a str, opts: &ParseOpts) -> Self {
+        Iter {
+            src: s,
+            bytes: s.as_bytes(),
+            pos: 0,
+            st: ParserState::default(),
+            opts: opts.clone(),
+            pending_end: None,
+            base: Position::default(),
+            end_position: None,
+            hit_end: Cell::new(false),
         }
     }
 
-    pub(crate) fn advance_or_die(&mut self) -> Result<()> {
-        if self.advance() {
-            Ok(())
-        } else {
-            Err(self.err(file!(), line!()))
-        }
+    /// Creates an iter over the content of an element, as described by `ParserState::is_fragment`.
+    pub(crate) fn new_fragment(s: &'a str, opts: &ParseOpts) -> Self {
+        let mut iter = Iter::new(s, opts);
+        iter.st.is_fragment = true;
+        iter
     }
 
-    pub(crate) fn err(&self, file: &str, line: u32) -> Error {
-        Error::Parse {
-            source_file: file.to_owned(),
-            source_line: line,
-            position: self.st.position,
+    /// Continues parsing a document in `s`, the unparsed remainder of a document whose earlier
+    /// input ended at `base` and left the parser in `st`.
+    pub(crate) fn resume(s: &'a str, opts: &ParseOpts, st: ParserState, base: Position) -> Self {
+        Iter {
+            st,
+            base,
+            ..Iter::new(s, opts)
         }
     }
 
-    pub(crate) fn expect(&self, expected: char) -> Result<()> {
-        if self.is(expected) {
-            Ok(())
-        } else {
-            Err(self.err(file!(), line!()))
+    /// Returns the next event, or `None` once the root element has been closed and the rest of the
+    /// input is whitespace.
+    pub(crate) fn next_event(&mut self) -> Result<Option<Event<'a>>> {
+        if let Some(name) = self.pending_end.take() {
+            self.close_element();
+            return Ok(Some(Event::End { name }));
+        }
+        if self.st.is_fragment && self.st.doc_status == DocStatus::BeforeDeclaration {
+            // a fragment may begin with a text declaration, after which it is parsed as content
+            let declaration = if self.starts_with(b"<?") {
+                Some(parse_pi(self)?)
+            } else {
+                None
+            };
+            self.st.doc_status = DocStatus::ProcessingRoot;
+            if declaration.is_some() {
+                return Ok(declaration);
+            }
+        }
+        if self.st.doc_status == DocStatus::ProcessingRoot {
+            return match self.peek() {
+                None if self.st.is_fragment && self.st.open.is_empty() => Ok(None),
+                None => Err(self.end_err(file!(), line!())),
+                Some(b'<') => self.parse_markup().map(Some),
+                Some(_) => Ok(Some(Event::Text(parse_text(self)?))),
+            };
+        }
+        self.skip_whitespace();
+        let event = match self.peek() {
+            None if self.st.doc_status == DocStatus::AfterRoot => return Ok(None),
+            None => return Err(self.end_err(file!(), line!())),
+            Some(b'<') => self.parse_markup()?,
+            Some(_) => return Err(self.err(file!(), line!())),
+        };
+        // the xml declaration must either be the first thing in the document or else omitted
+        if self.st.doc_status == DocStatus::BeforeDeclaration {
+            self.st.doc_status = DocStatus::BeforeRoot;
         }
+        Ok(Some(event))
     }
 
-    pub(crate) fn is_name_start_char(&self) -> bool {
-        is_name_start_char(self.st.c)
+    fn parse_markup(&mut self) -> Result<Event<'a>> {
+        match self.peek_at(1) {
+            None => Err(self.end_err(file!(), line!())),
+            Some(b'?') => parse_pi(self),
+            Some(b'!') => parse_markup_declaration(self),
+            Some(b'/') => {
+                let name = parse_end_tag(self)?;
+                self.close_element();
+                Ok(Event::End { name })
+            }
+            _ => {
+                if self.st.doc_status == DocStatus::AfterRoot {
+                    // only one root element is allowed
+                    return Err(self.err(file!(), line!()));
+                }
+                let (name, attributes, is_empty) = parse_start_tag(self)?;
+                self.st.doc_status = DocStatus::ProcessingRoot;
+                if is_empty {
+                    self.pending_end = Some(name.clone());
+                }
+                Ok(Event::Start { name, attributes })
+            }
+        }
     }
 
-    pub(crate) fn is_name_char(&self) -> bool {
-        is_name_char(self.st.c)
+    fn close_element(&mut self) {
+        self.st.open.pop();
+        if self.st.open.is_empty() && !self.st.is_fragment {
+            self.st.doc_status = DocStatus::AfterRoot;
+        }
     }
 
-    pub(crate) fn is_after_name_char(&self) -> bool {
-        match self.st.c {
-            ' ' | '\t' | '=' | '/' | '>' => true,
-            _ => false,
-        }
+    #[inline]
+    pub(crate) fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
     }
 
-    pub(crate) fn expect_name_start_char(&self) -> Result<()> {
-        if !self.is_name_start_char() {
-            Err(self.err(file!(), line!()))
-        } else {
-            Ok(())
-        }
+    #[inline]
+    pub(crate) fn peek_at(&self, n: usize) -> Option<u8> {
+        self.bytes.get(self.pos + n).copied()
     }
 
-    pub(crate) fn expect_name_char(&self) -> Result<()> {
-        if !self.is_name_char() {
-            Err(self.err(file!(), line!()))
-        } else {
-            Ok(())
-        }
+    #[inline]
+    pub(crate) fn is(&self, value: u8) -> bool {
+        self.peek() == Some(value)
     }
 
-    pub(crate) fn skip_whitespace(&mut self) -> Result<()> {
-        loop {
-            if !self.is_whitespace() {
-                return Ok(());
-            }
-            if !self.advance() {
-                return Ok(());
-            }
-        }
+    #[inline]
+    pub(crate) fn starts_with(&self, value: &[u8]) -> bool {
+        self.bytes[self.pos..].starts_with(value)
     }
 
-    pub(crate) fn is_whitespace(&self) -> bool {
-        self.st.c.is_ascii_whitespace()
+    #[inline]
+    pub(crate) fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
     }
 
-    pub(crate) fn is(&self, value: char) -> bool {
-        self.st.c == value
+    /// Returns the byte offset of the next occurrence of `needle` at or after the current position.
+    pub(crate) fn find(&self, needle: &[u8]) -> Option<usize> {
+        memmem::find(self.rest(), needle).map(|i| self.pos + i)
     }
 
-    pub(crate) fn peek_is(&mut self, value: char) -> bool {
-        if let Some(&next) = self.it.peek() {
-            return next == value;
+    /// Advances past `expected` or returns an error.
+    #[inline]
+    pub(crate) fn expect(&mut self, expected: u8) -> Result<()> {
+        match self.peek() {
+            Some(b) if b == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(_) => Err(self.err(file!(), line!())),
+            None => Err(self.end_err(file!(), line!())),
         }
-        false
     }
-}
 
-pub fn parse_str(s: &str) -> Result<Document> {
-    let mut iter = Iter::new(s)?;
-    let mut document = Document::new();
-    loop {
-        parse_document(&mut iter, &mut document)?;
-        trace!("{:?}", iter.st);
-        if !iter.advance() {
-            break;
+    /// Advances past `expected` or returns an error.
+    pub(crate) fn expect_str(&mut self, expected: &str) -> Result<()> {
+        for &b in expected.as_bytes() {
+            self.expect(b)?;
         }
+        Ok(())
     }
-    Ok(document)
-}
 
-// TODO - disallow dead code
-#[allow(dead_code)]
-#[derive(Debug, Clone, Copy, Eq, PartialOrd, PartialEq, Hash)]
-pub(crate) enum TagStatus {
-    TagOpen(u64),
-    InsideTag(u64),
-    InsideProcessingInstruction(u64),
-    TagClose(u64, u64),
-    OutsideTag,
-}
+    /// Returns `true` if the document follows the rules of XML 1.1 rather than 1.0.
+    #[inline]
+    pub(crate) fn is_1_1(&self) -> bool {
+        self.st.version == Version::OneDotOne
+    }
 
-impl Default for TagStatus {
-    fn default() -> Self {
-        TagStatus::OutsideTag
+    #[inline]
+    pub(crate) fn is_whitespace(&self) -> bool {
+        self.peek().is_some_and(is_whitespace_byte)
     }
-}
 
-// TODO - disallow dead code
-#[allow(dead_code)]
-#[derive(Debug, Clone, Copy, Eq, PartialOrd, PartialEq, Hash)]
-pub(crate) enum DocStatus {
-    BeforeDeclaration,
-    AfterDeclaration,
-    BeforeRoot,
-    ProcessingRoot,
-    AfterRoot,
-}
+    /// Returns `true` if any whitespace was skipped.
+    #[inline]
+    pub(crate) fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while self.is_whitespace() {
+            self.pos += 1;
+        }
+        self.pos != start
+    }
 
-impl Default for DocStatus {
-    fn default() -> Self {
-        DocStatus::BeforeDeclaration
+    /// Returns the char starting at byte offset `i`.
+    fn char_at(&self, i: usize) -> Option<char> {
+        self.src.get(i..).and_then(|s| s.chars().next())
     }
-}
 
-fn parse_document(iter: &mut Iter, document: &mut Document) -> Result<()> {
-    loop {
-        if iter.st.c.is_ascii_whitespace() {
-            if !iter.advance() {
+    /// Parses a name and returns it as a slice of the input.
+    pub(crate) fn parse_name(&mut self) -> Result<&'a str> {
+        let start = self.pos;
+        match self.peek() {
+            None => return Err(self.end_err(file!(), line!())),
+            Some(b) if is_ascii_name_start_byte(b) => self.pos += 1,
+            Some(b) if b.is_ascii() => return Err(self.err(file!(), line!())),
+            Some(_) => match self.char_at(self.pos) {
+                Some(c) if is_name_start_char(c) => self.pos += c.len_utf8(),
+                _ => return Err(self.err(file!(), line!())),
+            },
+        }
+        while let Some(b) = self.peek() {
+            if is_ascii_name_byte(b) {
+                self.pos += 1;
+            } else if b.is_ascii() {
                 break;
+            } else {
+                match self.char_at(self.pos) {
+                    Some(c) if is_name_char(c) => self.pos += c.len_utf8(),
+                    _ => break,
+                }
             }
-            continue;
-        } else if iter.st.c != '<' {
-            return Err(iter.err(file!(), line!()));
-        }
-        let next = peek_or_die(iter)?;
-        match next {
-            '?' => {
-                // currently only one processing instruction is supported. no comments are
-                // supported. the xml declaration must either be the first thing in the document
-                // or else omitted.
-                state_must_be_before_declaration(iter)?;
-                let pi_data = parse_pi(iter)?;
-                document.declaration = parse_declaration(&pi_data)?;
-                iter.st.doc_status = DocStatus::AfterDeclaration;
-            }
-            '-' => no_comments()?,
-            _ => {
-                document.root = parse_element(iter)?;
-            }
         }
+        // the name may continue beyond the end of the input, and a name is always followed by
+        // something, so this is reported as the input ending too soon
+        if self.pos == self.bytes.len() {
+            return Err(self.end_err(file!(), line!()));
+        }
+        Ok(&self.src[start..self.pos])
+    }
 
-        if !iter.advance() {
-            break;
+    /// Computes the position of the char that begins at byte offset `offset`.
+    pub(crate) fn position_at(&self, offset: usize) -> Position {
+        match self.end_position {
+            Some(position) if offset >= self.src.len() => return position,
+            _ => {}
+        }
+        let mut position = self.base;
+        let mut previous = None;
+        let end = offset.min(self.src.len());
+        for c in self.src[..end].chars() {
+            position.increment(c, previous, &self.st.version);
+            previous = Some(c);
+        }
+        if let Some(c) = self.char_at(end) {
+            position.increment(c, previous, &self.st.version);
         }
+        position
+    }
+
+    pub(crate) fn err(&self, file: &str, line: u32) -> Error {
+        self.err_at(self.pos, file, line)
+    }
+
+    pub(crate) fn err_at(&self, offset: usize, file: &str, line: u32) -> Error {
+        if offset >= self.bytes.len() {
+            self.hit_end.set(true);
+        }
+        Error::Parse {
+            source_file: file.to_owned(),
+            source_line: line,
+            position: self.position_at(offset),
+        }
+    }
+
+    /// The error for input that ends before the document is complete.
+    pub(crate) fn end_err(&self, file: &str, line: u32) -> Error {
+        self.err_at(self.bytes.len(), file, line)
     }
-    Ok(())
 }
 
-fn parse_declaration(pi_data: &PIData) -> Result<Declaration> {
-    let mut declaration = Declaration::default();
-    if pi_data.target != "xml" {
-        return Err(Error::Bug {
-            message: "TODO - better message".to_string(),
-        });
-    }
-    if pi_data.instructions.map().len() > 2 {
-        return Err(Error::Bug {
-            message: "TODO - better message".to_string(),
-        });
-    }
-    if let Some(val) = pi_data.instructions.map().get("version") {
-        match val.as_str() {
-            "1.0" => {
-                declaration.version = Version::One;
-            }
-            "1.1" => {
-                declaration.version = Version::OneDotOne;
-            }
-            _ => {
-                return Err(Error::Bug {
-                    message: "TODO - better message".to_string(),
-                });
-            }
+pub fn parse_str(s: &str) -> Result<Document> {
+    parse_str_opts(s, &ParseOpts::default())
+}
+
+pub fn parse_str_opts(s: &str, opts: &ParseOpts) -> Result<Document> {
+    let mut iter = Iter::new(s, opts);
+    let mut document = Document::new();
+    let mut builder = Builder::new(opts);
+    while let Some(event) = iter.next_event()? {
+        trace!("{:?}", event);
+        match event {
+            Event::Declaration(declaration) => document.declaration = declaration,
+            event => builder.push_event(event)?,
         }
     }
-    if let Some(val) = pi_data.instructions.map().get("encoding") {
-        match val.as_str() {
-            "UTF-8" => {
-                declaration.encoding = Encoding::Utf8;
-            }
-            _ => {
-                return Err(Error::Bug {
-                    message: "TODO - better message".to_string(),
-                });
+    // the nodes before the root element are the prolog and those after it are the epilog
+    let mut is_prolog = true;
+    for node in builder.top {
+        match node {
+            Node::Element(root) => {
+                document.root = root;
+                is_prolog = false;
             }
+            node if is_prolog => document.prolog.push(node),
+            node => document.epilog.push(node),
         }
     }
-    Ok(declaration)
+    Ok(document)
 }
 
-fn state_must_be_before_declaration(iter: &Iter) -> Result<()> {
-    if iter.st.doc_status != DocStatus::BeforeDeclaration {
-        Err(Error::Bug {
-            message: "TODO - better message".to_string(),
-        })
-    } else {
+/// Builds owned nodes from events. Nodes that are not inside of any element are collected in `top`.
+pub(crate) struct Builder {
+    /// Rather than recursing once per nesting level, each open element is pushed onto the stack
+    /// and popped when its end tag is found. Nesting depth is therefore limited by
+    /// `ParseOpts::max_depth` and the heap, never by the native stack.
+    stack: Stack<ElementData>,
+    /// Element and attribute names repeat throughout a document, so each distinct name is
+    /// allocated once and shared by every element that uses it.
+    names: Interner,
+    preserve_whitespace: bool,
+    pub(crate) top: Vec<Node>,
+}
+
+impl Builder {
+    pub(crate) fn new(opts: &ParseOpts) -> Self {
+        Builder {
+            stack: Stack::new(),
+            names: Interner::new(),
+            preserve_whitespace: opts.preserve_whitespace,
+            top: Vec::new(),
+        }
+    }
+
+    /// Adds the node for `event`. Declarations are the caller's concern and are ignored.
+    pub(crate) fn push_event(&mut self, event: Event<'_>) -> Result<()> {
+        match event {
+            Event::Declaration(_) => {}
+            Event::DocType(text) => self.push_node(Node::DocType(text.into_owned())),
+            Event::Start { name, attributes } => {
+                self.stack
+                    .push(make_element(&mut self.names, &name, attributes)?)
+            }
+            Event::End { .. } => {
+                let element = self.stack.pop().ok_or_else(|| Error::Bug {
+                    message: "End event with no open element.".to_string(),
+                })?;
+                self.push_node(Node::Element(element));
+            }
+            Event::Text(text) => {
+                // whitespace-only text between elements is not preserved unless asked for
+                let is_kept = self.preserve_whitespace && !self.stack.is_empty();
+                if is_kept || !text.bytes().all(is_whitespace_byte) {
+                    self.push_node(Node::String(text.into_owned()));
+                }
+            }
+            Event::CData(text) => self.push_node(Node::CData(text.into_owned())),
+            Event::Comment(text) => self.push_node(Node::Comment(text.into_owned())),
+            Event::ProcessingInstruction(pi) => self.push_node(Node::ProcessingInstruction(pi)),
+        }
         Ok(())
     }
-}
 
-pub(crate) fn peek_or_die(iter: &mut Iter) -> Result<char> {
-    let opt = iter.it.peek();
-    match opt {
-        Some(c) => Ok(*c),
-        None => Err(Error::Bug {
-            message: "TODO - better message".to_string(),
-        }),
+    /// Adds `node` to the innermost open element, or to `top` if no element is open.
+    fn push_node(&mut self, node: Node) {
+        match self.stack.peek_mut() {
+            Some(parent) => parent.nodes.push(node),
+            None => self.top.push(node),
+        }
     }
 }
 
-fn no_comments() -> Result<()> {
-    Err(Error::Bug {
-        message: "comments are not supported".to_string(),
-    })
+/// Parses a document whose names and text borrow from `s`. Strings are only allocated where an
+/// entity reference had to be decoded.
+pub fn parse_str_borrowed(s: &str) -> Result<borrowed::Document<'_>> {
+    parse_str_borrowed_opts(s, &ParseOpts::default())
 }
 
-fn parse_name(iter: &mut Iter) -> Result<String> {
-    iter.expect_name_start_char()?;
-    let mut name = String::default();
-    name.push(iter.st.c);
-    iter.advance_or_die()?;
-    loop {
-        if iter.is_after_name_char() {
-            break;
-        }
-        iter.expect_name_char()?;
-        name.push(iter.st.c);
-        if !iter.advance() {
-            break;
+pub fn parse_str_borrowed_opts<'a>(s: &'a str, opts: &ParseOpts) -> Result<borrowed::Document<'a>> {
+    let mut iter = Iter::new(s, opts);
+    let mut document = borrowed::Document::default();
+    let mut stack: Stack<borrowed::ElementData<'a>> = Stack::new();
+    let mut is_prolog = true;
+    while let Some(event) = iter.next_event()? {
+        trace!("{:?}", event);
+        let node = match event {
+            Event::Declaration(declaration) => {
+                document.declaration = declaration;
+                continue;
+            }
+            Event::DocType(text) => borrowed::Node::DocType(text),
+            Event::Start { name, attributes } => {
+                stack.push(make_borrowed_element(name, attributes)?);
+                continue;
+            }
+            Event::End { .. } => {
+                let element = stack.pop().ok_or_else(|| Error::Bug {
+                    message: "End event with no open element.".to_string(),
+                })?;
+                if stack.is_empty() {
+                    document.root = element;
+                    is_prolog = false;
+                    continue;
+                }
+                borrowed::Node::Element(element)
+            }
+            Event::Text(text) => {
+                // whitespace-only text between elements is not preserved unless asked for
+                let is_kept = opts.preserve_whitespace && !stack.is_empty();
+                if !is_kept && text.bytes().all(is_whitespace_byte) {
+                    continue;
+                }
+                borrowed::Node::String(text)
+            }
+            Event::CData(text) => borrowed::Node::CData(text),
+            Event::Comment(text) => borrowed::Node::Comment(text),
+            Event::ProcessingInstruction(pi) => borrowed::Node::ProcessingInstruction(pi),
+        };
+        match stack.peek_mut() {
+            Some(parent) => parent.nodes.push(node),
+            None if is_prolog => document.prolog.push(node),
+            None => document.epilog.push(node),
         }
     }
-    Ok(name)
+    Ok(document)
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -350,7 +613,9 @@ fn parse_name(iter: &mut Iter) -> Result<String> {
 
 #[cfg(test)]
 mod tests {
-    const _XML1: &str = r##"
+    use super::*;
+
+    const XML1: &str = r##"
 <?xml version="1.0" encoding="UTF-8" standalone="no"?>
 <!DOCTYPE something PUBLIC "-//Some//Path//EN" "http://www.example.org/dtds/partwise.dtd">
 <cats>
@@ -366,4 +631,310 @@ mod tests {
   </cat>
 </cats>
     "##;
+
+    fn events(s: &str) -> Vec<Event<'_>> {
+        let mut iter = Iter::new(s, &ParseOpts::default());
+        let mut result = Vec::new();
+        while let Some(event) = iter.next_event().unwrap() {
+            result.push(event);
+        }
+        result
+    }
+
+    fn error_position(s: &str) -> Position {
+        match parse_str(s).err().unwrap() {
+            Error::Parse { position, .. } => position,
+            e => panic!("expected Error::Parse, got {}", e),
+        }
+    }
+
+    #[test]
+    fn parse_xml1() {
+        let doc = parse_str(XML1).unwrap();
+        assert_eq!(doc.declaration.version, xdoc::Version::One);
+        assert_eq!(doc.root.name, "cats");
+        assert_eq!(doc.root.nodes.len(), 2);
+        if let Node::Element(cat) = &doc.root.nodes[0] {
+            assert_eq!(cat.attributes.map().get("id").unwrap(), "b1");
+            if let Node::Element(name) = &cat.nodes[0] {
+                assert_eq!(
+                    name.nodes,
+                    vec![Node::String("\n        Bones\n    ".to_string())]
+                );
+            } else {
+                panic!("expected the name element");
+            }
+        } else {
+            panic!("expected the cat element");
+        }
+    }
+
+    #[test]
+    fn text_is_borrowed_unless_decoded() {
+        let events = events("<a b='x &lt; y' c=\"z\">plain<d/>&#x41;&#66;&amp;</a>");
+        match &events[0] {
+            Event::Start { name, attributes } => {
+                assert!(matches!(name, Cow::Borrowed("a")));
+                assert!(matches!(&attributes[0].value, Cow::Owned(s) if s == "x < y"));
+                assert!(matches!(&attributes[1].value, Cow::Borrowed("z")));
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+        assert!(matches!(&events[1], Event::Text(Cow::Borrowed("plain"))));
+        assert!(matches!(&events[2], Event::Start { name, .. } if name == "d"));
+        assert!(matches!(&events[3], Event::End { name } if name == "d"));
+        assert!(matches!(&events[4], Event::Text(Cow::Owned(s)) if s == "AB&"));
+        assert!(matches!(&events[5], Event::End { name } if name == "a"));
+        assert_eq!(events.len(), 6);
+    }
+
+    #[test]
+    fn names_are_interned() {
+        let xml = r#"<cats><cat name="bones"/><x:cat name="bishop"/><x:dog/></cats>"#;
+        let doc = parse_str(xml).unwrap();
+        let children: Vec<&ElementData> = doc
+            .root
+            .nodes
+            .iter()
+            .map(|n| match n {
+                Node::Element(e) => e,
+                node => panic!("unexpected node {:?}", node),
+            })
+            .collect();
+        assert!(children[0].name.ptr_eq(&children[1].name));
+        assert!(children[1]
+            .namespace
+            .as_ref()
+            .unwrap()
+            .ptr_eq(children[2].namespace.as_ref().unwrap()));
+        let key = |e: &ElementData| e.attributes.map().keys().next().unwrap().clone();
+        assert!(key(children[0]).ptr_eq(&key(children[1])));
+    }
+
+    #[test]
+    fn borrowed_document() {
+        let xml = "<x:cats a=\"1\" b=\"&amp;\"><cat>Bones</cat><cat>B&amp;B</cat></x:cats>";
+        let doc = parse_str_borrowed(xml).unwrap();
+        let root = doc.root();
+        assert!(matches!(root.namespace, Some(Cow::Borrowed("x"))));
+        assert!(matches!(root.name, Cow::Borrowed("cats")));
+        assert!(matches!(root.attributes[0].value, Cow::Borrowed("1")));
+        assert!(matches!(&root.attributes[1].value, Cow::Owned(s) if s == "&"));
+        match &root.nodes[0] {
+            borrowed::Node::Element(cat) => {
+                assert!(matches!(
+                    cat.nodes[0],
+                    borrowed::Node::String(Cow::Borrowed("Bones"))
+                ))
+            }
+            node => panic!("unexpected node {:?}", node),
+        }
+        match &root.nodes[1] {
+            borrowed::Node::Element(cat) => {
+                assert!(
+                    matches!(&cat.nodes[0], borrowed::Node::String(Cow::Owned(s)) if s == "B&B")
+                )
+            }
+            node => panic!("unexpected node {:?}", node),
+        }
+        assert_eq!(doc.into_owned(), parse_str(xml).unwrap());
+        assert_eq!(
+            parse_str_borrowed(XML1).unwrap().into_owned(),
+            parse_str(XML1).unwrap()
+        );
+    }
+
+    #[test]
+    fn comments_cdata_and_pis_in_content() {
+        let doc = parse_str("<a><!-- c --><![CDATA[<x>]]><?t k=\"v\"?></a>").unwrap();
+        assert_eq!(doc.root.nodes[0], Node::Comment(" c ".to_string()));
+        assert_eq!(doc.root.nodes[1], Node::CData("<x>".to_string()));
+        if let Node::ProcessingInstruction(pi) = &doc.root.nodes[2] {
+            assert_eq!(pi.target, "t");
+            assert_eq!(pi.instructions.map().get("k").unwrap(), "v");
+        } else {
+            panic!("expected a processing instruction");
+        }
+    }
+
+    #[test]
+    fn preserve_whitespace() {
+        let xml = "<!-- c -->\n<a>\n  <b> </b>\n</a>\n";
+        let opts = ParseOpts::default().with_preserve_whitespace(true);
+        let doc = parse_str_opts(xml, &opts).unwrap();
+        assert_eq!(doc.prolog.len(), 1);
+        assert!(doc.epilog.is_empty());
+        assert_eq!(doc.root.nodes.len(), 3);
+        assert_eq!(doc.root.nodes[0], Node::String("\n  ".to_string()));
+        assert_eq!(doc.root.first_child_named("b").unwrap().inner_text(), " ");
+        let borrowed = parse_str_borrowed_opts(xml, &opts).unwrap();
+        assert_eq!(borrowed.root.nodes.len(), 3);
+        assert_eq!(parse_str(xml).unwrap().root.nodes.len(), 1);
+    }
+
+    #[test]
+    fn non_ascii_names() {
+        let doc = parse_str("<données clé=\"é\"><ü/></données>").unwrap();
+        assert_eq!(doc.root.name, "données");
+        assert_eq!(doc.root.attributes.map().get("clé").unwrap(), "é");
+    }
+
+    #[test]
+    fn error_positions() {
+        // the mismatched end tag
+        let position = error_position("<a>\n  <b></c>\n</a>");
+        assert_eq!(
+            (position.line, position.column, position.absolute),
+            (2, 6, 10)
+        );
+        // the unknown entity reference, counting chars rather than bytes
+        let position = error_position("<é>&foo;</é>");
+        assert_eq!(
+            (position.line, position.column, position.absolute),
+            (1, 5, 4)
+        );
+        // the end of input
+        let position = error_position("<a>");
+        assert_eq!(position.absolute, 3);
+    }
+
+    #[test]
+    fn line_ends_are_normalized() {
+        let xml = "<a b='1\r\n2'>x\r\ny\rz&#13;<!--\r\n--><![CDATA[\r]]><?p k='\r\n'?></a>";
+        let parsed = events(xml);
+        assert!(matches!(
+            &parsed[0],
+            Event::Start { attributes, .. } if attributes[0].value == "1\n2"
+        ));
+        // a character reference is not a line end
+        assert_eq!(parsed[1], Event::Text(Cow::Borrowed("x\ny\nz\r")));
+        assert_eq!(parsed[2], Event::Comment(Cow::Borrowed("\n")));
+        assert_eq!(parsed[3], Event::CData(Cow::Borrowed("\n")));
+        match &parsed[4] {
+            Event::ProcessingInstruction(pi) => {
+                assert_eq!(pi.instructions.map().get("k").unwrap(), "\n")
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        // text without line ends to replace is still borrowed
+        assert!(matches!(
+            events("<a>x\ny</a>")[1],
+            Event::Text(Cow::Borrowed(_))
+        ));
+    }
+
+    #[test]
+    fn xml_1_1_line_ends() {
+        let text = |xml: &str| match &events(xml)[2] {
+            Event::Text(text) => text.to_string(),
+            event => panic!("unexpected event {:?}", event),
+        };
+        let body = "<a>1\u{85}2\r\u{85}3\u{2028}4</a>";
+        assert_eq!(
+            text(&format!("<?xml version=\"1.1\"?>{}", body)),
+            "1\n2\n3\n4"
+        );
+        assert_eq!(
+            text(&format!("<?xml version=\"1.0\"?>{}", body)),
+            "1\u{85}2\n\u{85}3\u{2028}4"
+        );
+    }
+
+    #[test]
+    fn crlf_positions() {
+        let lf = error_position("<a>\n  <b>\n</c>\n</a>");
+        let crlf = error_position("<a>\r\n  <b>\r\n</c>\r\n</a>");
+        assert_eq!((crlf.line, crlf.column), (lf.line, lf.column));
+        assert_eq!((crlf.line, crlf.column), (3, 1));
+        let cr = error_position("<a>\r  <b>\r</c>\r</a>");
+        assert_eq!((cr.line, cr.column), (3, 1));
+        let nel = error_position("<?xml version=\"1.1\"?><a>\u{85}  <b>\u{2028}</c></a>");
+        assert_eq!((nel.line, nel.column), (3, 1));
+    }
+
+    #[test]
+    fn disallowed_chars() {
+        let v1_0 = "<?xml version=\"1.0\"?>";
+        let v1_1 = "<?xml version=\"1.1\"?>";
+        let ok = |decl: &str, body: &str| parse_str(&format!("{}<a>{}</a>", decl, body)).is_ok();
+        for decl in &[v1_0, v1_1] {
+            assert!(!ok(decl, "\u{1}"));
+            assert!(!ok(decl, "&#0;"));
+            assert!(!ok(decl, "\u{FFFE}"));
+            assert!(!ok(decl, "&#xFFFF;"));
+            assert!(!ok(decl, "<!--\u{1F}-->"));
+            assert!(ok(decl, "\t\u{10000}&#9;"));
+        }
+        // control chars may only be written as character references, and only in XML 1.1
+        assert!(!ok(v1_0, "&#x1;"));
+        assert!(ok(v1_1, "&#x1;"));
+        // XML 1.1 restricts the C1 controls except NEL
+        assert!(ok(v1_0, "\u{7F}\u{80}\u{9F}"));
+        assert!(!ok(v1_1, "\u{7F}"));
+        assert!(!ok(v1_1, "\u{80}"));
+        assert!(ok(v1_1, "&#x7F;&#x80;\u{85}"));
+        // the position is that of the disallowed char
+        let position = error_position("<a b='x\u{0}'/>");
+        assert_eq!(position.absolute, 8);
+    }
+
+    #[test]
+    fn prolog_and_epilog() {
+        let xml = r#"<?xml version="1.0"?>
+<!-- license -->
+<?xml-stylesheet href="cats.xsl"?>
+<!DOCTYPE cats>
+<cats/>
+<!-- end -->
+<?done?>"#;
+        let doc = parse_str(xml).unwrap();
+        assert_eq!(doc.prolog.len(), 3);
+        assert_eq!(doc.prolog[0], Node::Comment(" license ".to_string()));
+        assert!(
+            matches!(&doc.prolog[1], Node::ProcessingInstruction(pi) if pi.target == "xml-stylesheet")
+        );
+        assert_eq!(doc.prolog[2], Node::DocType("cats".to_string()));
+        assert_eq!(doc.root.name, "cats");
+        assert_eq!(doc.epilog.len(), 2);
+        assert_eq!(doc.epilog[0], Node::Comment(" end ".to_string()));
+        assert_eq!(parse_str_borrowed(xml).unwrap().into_owned(), doc);
+        // they survive a round trip
+        assert_eq!(parse_str(&doc.to_string()).unwrap(), doc);
+        assert_eq!(doc.to_string(), xml);
+    }
+
+    #[test]
+    fn pretty_printing_keeps_text() {
+        let xml = "<doc><p>Hello <b>world</b>!</p><p>1 &lt; 2 &amp;\r\n<![CDATA[ <3 ]]></p></doc>";
+        let doc = parse_str(xml).unwrap();
+        let pretty = doc.to_string();
+        assert!(pretty.starts_with("<doc>\n  <p>Hello <b>world</b>!</p>\n"));
+        assert_eq!(parse_str(&pretty).unwrap(), doc);
+        for f in xtest::load_all() {
+            if let xtest::Syntax::Good {} = f.metadata.syntax {
+                let doc = parse_str(&f.read_xml_file()).unwrap();
+                assert_eq!(parse_str(&doc.to_string()).unwrap(), doc, "{}", f.name);
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_documents() {
+        for xml in &[
+            "",
+            "text",
+            "<a>",
+            "<a></b>",
+            "<a b=\"1\" b=\"2\"/>",
+            "<a b=\"1\"c=\"2\"/>",
+            "<a>]]></a>",
+            "<a><!-- -- --></a>",
+            "<a/><b/>",
+            "<a/>text",
+            "<a/><?xml version=\"1.0\"?>",
+            "<a>&#xD800;</a>",
+        ] {
+            assert!(parse_str(xml).is_err(), "expected an error for '{}'", xml);
+        }
+    }
 }
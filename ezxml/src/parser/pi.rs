@@ -1,185 +1,116 @@
-use xdoc::PIData;
+use std::borrow::Cow;
 
-use crate::error::{Error, Result};
-use crate::parser::Iter;
+use memchr::memchr;
+use xdoc::{Declaration, Encoding, PIData, Version};
 
-use super::chars::{is_name_char, is_name_start_char};
+use crate::error::Result;
+use crate::parser::text::checked_text;
+use crate::parser::{DocStatus, Event, Iter};
 
-#[derive(PartialEq)]
-enum PIStatus {
-    BeforeTarget,
-    InsideTarget,
-    AfterTarget,
-    InsideKey,
-    AfterKey,
-    Equals,
-    AfterEquals,
-    ValOpenQuote,
-    InsideVal,
-    ValCloseQuote,
-    AfterVal,
-    QuestionMark,
-    Close,
-}
-
-struct PIProcessor {
-    status: PIStatus,
-    key_buffer: String,
-    value_buffer: String,
-    pi_data: PIData,
-}
-
-impl PIProcessor {
-    fn new() -> Self {
-        Self {
-            status: PIStatus::BeforeTarget,
-            key_buffer: "".to_string(),
-            value_buffer: "".to_string(),
-            pi_data: PIData::default(),
-        }
+/// Parses a processing instruction, or the XML declaration, and leaves the iter just past its
+/// closing '?>'.
+pub(crate) fn parse_pi<'a>(iter: &mut Iter<'a>) -> Result<Event<'a>> {
+    let start = iter.pos;
+    iter.expect_str("<?")?;
+    let target = iter.parse_name()?;
+    let is_declaration = target == "xml";
+    // the xml declaration must either be the first thing in the document or else omitted, and
+    // other targets beginning with 'xml' are reserved.
+    if is_declaration && iter.st.doc_status != DocStatus::BeforeDeclaration
+        || !is_declaration && target.eq_ignore_ascii_case("xml")
+    {
+        return Err(iter.err_at(start, file!(), line!()));
     }
-
-    /// Takes the current strings from `key_buffer` and `value_buffer` and adds them to the
-    /// `instructions`. Clears these buffers to begin processing the next key/value pair.
-    fn take_buffers(&mut self) -> Result<()> {
-        if self.key_buffer.is_empty() {
-            // TODO - better error
-            return Err(Error::Bug {
-                message: "Empty key - this is a bug and should have been detected sooner."
-                    .to_string(),
-            });
-        }
-        if self
-            .pi_data
+    let end = match iter.find(b"?>") {
+        Some(end) => end,
+        None => return Err(iter.end_err(file!(), line!())),
+    };
+    if iter.pos != end && !iter.is_whitespace() {
+        return Err(iter.err(file!(), line!()));
+    }
+    let mut pi_data = PIData {
+        target: target.to_owned(),
+        instructions: Default::default(),
+    };
+    for (key, value) in parse_instructions(iter, end)? {
+        pi_data
             .instructions
             .mut_map()
-            .insert(self.key_buffer.clone(), self.value_buffer.clone())
-            .is_some()
-        {
-            // TODO - better error
-            return Err(Error::Bug {
-                message: "Duplicate key".to_string(),
-            });
-        }
-        self.key_buffer.clear();
-        self.value_buffer.clear();
-        Ok(())
+            .insert(key.into(), value.into_owned());
+    }
+    iter.pos = end + 2;
+    if is_declaration {
+        let declaration = parse_declaration(iter, start, &pi_data)?;
+        iter.st.version = declaration.version.clone();
+        Ok(Event::Declaration(declaration))
+    } else {
+        Ok(Event::ProcessingInstruction(pi_data))
     }
 }
 
-pub(crate) fn parse_pi(iter: &mut Iter) -> Result<PIData> {
-    iter.expect('<')?;
-    iter.advance_or_die()?;
-    iter.expect('?')?;
-    iter.advance_or_die()?;
-    let mut processor = PIProcessor::new();
+/// Parses the `key="value"` pairs of a processing instruction that ends at byte offset `end`.
+fn parse_instructions<'a>(iter: &mut Iter<'a>, end: usize) -> Result<Vec<(&'a str, Cow<'a, str>)>> {
+    let mut instructions: Vec<(&'a str, Cow<'a, str>)> = Vec::new();
     loop {
-        take_processing_instruction_char(iter, &mut processor)?;
-        if processor.status == PIStatus::Close {
-            break;
+        let had_whitespace = iter.skip_whitespace();
+        if iter.pos >= end {
+            return Ok(instructions);
+        }
+        // instructions must be separated from the target and from each other by whitespace
+        if !had_whitespace {
+            return Err(iter.err(file!(), line!()));
+        }
+        let key_start = iter.pos;
+        let key = iter.parse_name()?;
+        if instructions.iter().any(|(k, _)| *k == key) {
+            return Err(iter.err_at(key_start, file!(), line!()));
         }
-        iter.advance_or_die()?;
+        iter.skip_whitespace();
+        iter.expect(b'=')?;
+        iter.skip_whitespace();
+        let quote = match iter.peek() {
+            Some(q @ b'"') | Some(q @ b'\'') if iter.pos < end => q,
+            _ => return Err(iter.err(file!(), line!())),
+        };
+        iter.pos += 1;
+        let value_end = match memchr(quote, &iter.bytes[iter.pos..end]) {
+            Some(i) => iter.pos + i,
+            None => return Err(iter.err_at(end, file!(), line!())),
+        };
+        instructions.push((key, checked_text(iter, iter.pos, value_end)?));
+        iter.pos = value_end + 1;
     }
-
-    Ok(processor.pi_data)
 }
 
-fn take_processing_instruction_char(iter: &mut Iter, processor: &mut PIProcessor) -> Result<()> {
-    //let ch = iter.st.c;
-    //println!("{}", ch);
-    match processor.status {
-        PIStatus::BeforeTarget => {
-            if !is_name_start_char(iter.st.c) {
-                return Err(iter.err(file!(), line!()));
-            } else {
-                processor.pi_data.target.push(iter.st.c);
-                processor.status = PIStatus::InsideTarget;
-            }
+fn parse_declaration(iter: &Iter<'_>, start: usize, pi_data: &PIData) -> Result<Declaration> {
+    let mut declaration = Declaration::default();
+    for key in pi_data.instructions.map().keys() {
+        match key.as_str() {
+            // TODO - standalone is accepted but not stored
+            "version" | "encoding" | "standalone" => {}
+            _ => return Err(iter.err_at(start, file!(), line!())),
         }
-        PIStatus::InsideTarget => {
-            if iter.st.c.is_ascii_whitespace() {
-                processor.status = PIStatus::AfterTarget;
-            } else if !is_name_char(iter.st.c) {
-                return Err(iter.err(file!(), line!()));
-            } else {
-                processor.pi_data.target.push(iter.st.c);
-            }
-        }
-        PIStatus::AfterTarget => {
-            if is_name_start_char(iter.st.c) {
-                processor.key_buffer.push(iter.st.c);
-                processor.status = PIStatus::InsideKey;
-            } else if !iter.st.c.is_ascii_whitespace() {
-                return Err(iter.err(file!(), line!()));
-            }
-        }
-        PIStatus::InsideKey => {
-            if is_name_char(iter.st.c) {
-                processor.key_buffer.push(iter.st.c);
-                processor.status = PIStatus::InsideKey;
-            } else if iter.st.c == '=' {
-                processor.status = PIStatus::Equals;
-            } else if iter.st.c.is_ascii_whitespace() {
-                processor.status = PIStatus::AfterKey;
-            } else {
-                return Err(iter.err(file!(), line!()));
-            }
-        }
-        PIStatus::AfterKey => {
-            if iter.st.c == '=' {
-                processor.status = PIStatus::Equals;
-            } else if !iter.st.c.is_ascii_whitespace() {
-                return Err(iter.err(file!(), line!()));
-            }
-        }
-        PIStatus::Equals | PIStatus::AfterEquals => {
-            if iter.st.c == '"' {
-                processor.status = PIStatus::ValOpenQuote;
-            } else if iter.st.c.is_ascii_whitespace() {
-                processor.status = PIStatus::AfterEquals;
-            } else {
-                return Err(iter.err(file!(), line!()));
+    }
+    if let Some(val) = pi_data.instructions.map().get("version") {
+        match val.as_str() {
+            "1.0" => {
+                declaration.version = Version::One;
             }
-        }
-        PIStatus::ValOpenQuote | PIStatus::InsideVal => {
-            if iter.st.c == '"' {
-                processor.take_buffers()?;
-                processor.status = PIStatus::ValCloseQuote;
-            } else {
-                // TODO - handle escape sequences
-                processor.value_buffer.push(iter.st.c);
-                processor.status = PIStatus::InsideVal;
+            "1.1" => {
+                declaration.version = Version::OneDotOne;
             }
-        }
-        PIStatus::ValCloseQuote => {
-            if iter.st.c.is_ascii_whitespace() {
-                processor.status = PIStatus::AfterVal;
-            } else if iter.st.c == '?' {
-                processor.status = PIStatus::QuestionMark;
-            } else {
-                return Err(iter.err(file!(), line!()));
+            _ => {
+                return Err(iter.err_at(start, file!(), line!()));
             }
         }
-        PIStatus::AfterVal => {
-            if iter.st.c.is_ascii_whitespace() {
-                processor.status = PIStatus::AfterVal;
-            } else if iter.st.c == '?' {
-                processor.status = PIStatus::QuestionMark;
-            } else if is_name_start_char(iter.st.c) {
-                processor.key_buffer.push(iter.st.c);
-                processor.status = PIStatus::InsideKey;
-            } else {
-                return Err(iter.err(file!(), line!()));
-            }
-        }
-        PIStatus::QuestionMark => {
-            if iter.st.c == '>' {
-                processor.status = PIStatus::Close;
-            } else {
-                return Err(iter.err(file!(), line!()));
-            }
+    }
+    if let Some(val) = pi_data.instructions.map().get("encoding") {
+        if val.eq_ignore_ascii_case("UTF-8") {
+            declaration.encoding = Encoding::Utf8;
+        } else {
+            // TODO - support encodings other than UTF-8
+            return Err(iter.err_at(start, file!(), line!()));
         }
-        PIStatus::Close => { /* done */ }
     }
-    Ok(())
+    Ok(declaration)
 }
@@ -0,0 +1,431 @@
+use std::mem;
+use std::str;
+
+use memchr::memchr;
+use xdoc::Version;
+
+use crate::error::{Error, Result};
+use crate::parser::chars::is_whitespace_byte;
+use crate::parser::{DocStatus, Event, Iter, ParseOpts, ParserState, Position};
+
+/// A parser that is fed a document in pieces, for example as it arrives over a socket. Each call to
+/// `feed` returns the events that are complete so far. Input that ends part way through a tag, a
+/// UTF-8 sequence or an entity reference is held back until the rest of it arrives, so the events
+/// are the same wherever the chunk boundaries fall.
+///
+/// When fed input completes some events and then turns out to be malformed, the events are returned
+/// and the error is returned by the next call. Once an error has been returned the document cannot
+/// be parsed any further, and every later call returns the same error again.
+///
+/// A token that is still unfinished is not parsed again until input arrives that could end it, so
+/// the work done is linear in the length of the document however it is split. An error within such
+/// a token is therefore returned once the token ends, or by `finish`.
+///
+/// ```
+/// use ezxml::{Event, PushParser};
+///
+/// let mut parser = PushParser::new();
+/// let mut events = parser.feed(b"<cat name=\"bo").unwrap();
+/// assert!(events.is_empty());
+/// events.extend(parser.feed(b"nes\">meow</c").unwrap());
+/// assert_eq!(events.len(), 2);
+/// events.extend(parser.feed(b"at>").unwrap());
+/// events.extend(parser.finish().unwrap());
+/// assert!(matches!(events.last(), Some(Event::End { name }) if name == "cat"));
+/// ```
+#[derive(Debug, Default)]
+pub struct PushParser {
+    opts: ParseOpts,
+    /// Input that has been fed but not yet returned as events.
+    buf: Vec<u8>,
+    st: ParserState,
+    /// The position just before the first byte of `buf`.
+    base: Position,
+    /// The length of the start of `buf` that is known to be valid UTF-8.
+    valid: usize,
+    /// Set when `buf` holds a byte sequence that can never be valid UTF-8.
+    is_invalid: bool,
+    /// The position at the end of the valid UTF-8 in `buf`, and the char that ends it. It is
+    /// counted with the rules of `end_version`, which the XML declaration may change.
+    end: Position,
+    end_char: Option<char>,
+    end_version: Version,
+    /// How far the unfinished token at the start of `buf` has been searched for a byte that could
+    /// end it. The token is only parsed again once one turns up, so a long token that arrives in
+    /// many chunks is not scanned from its start for each of them.
+    scan: Option<Scan>,
+    /// An error that followed the events returned by the last call.
+    deferred: Option<Error>,
+    /// The number of bytes handed to the tokenizer.
+    #[cfg(test)]
+    lexed: usize,
+}
+
+impl PushParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_opts(opts: &ParseOpts) -> Self {
+        PushParser {
+            opts: opts.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// The position just before the input that has not yet been returned as events.
+    pub(crate) fn position(&self) -> Position {
+        self.base
+    }
+
+    /// The number of bytes handed to the tokenizer so far.
+    #[cfg(test)]
+    pub(crate) fn lexed(&self) -> usize {
+        self.lexed
+    }
+
+    /// Adds `input` to the document and returns the events that it completed.
+    pub fn feed(&mut self, input: &[u8]) -> Result<Vec<Event<'static>>> {
+        self.buf.extend_from_slice(input);
+        self.validate();
+        let is_ready = match &mut self.scan {
+            Some(scan) => scan.advance(&self.buf),
+            None => true,
+        };
+        if !is_ready && !self.is_invalid && self.deferred.is_none() {
+            return Ok(Vec::new());
+        }
+        self.parse(false)
+    }
+
+    /// Signals the end of the document and returns the remaining events. An error is returned if
+    /// the document is incomplete.
+    pub fn finish(mut self) -> Result<Vec<Event<'static>>> {
+        self.parse(true)
+    }
+
+    /// Extends the valid UTF-8 at the start of `buf` over the input that has been added since, and
+    /// counts the position at its end.
+    fn validate(&mut self) {
+        let (valid_len, is_invalid) = match str::from_utf8(&self.buf[self.valid..]) {
+            Ok(_) => (self.buf.len() - self.valid, false),
+            Err(e) => (e.valid_up_to(), e.error_len().is_some()),
+        };
+        let end = self.valid + valid_len;
+        if let Ok(s) = str::from_utf8(&self.buf[self.valid..end]) {
+            for c in s.chars() {
+                self.end.increment(c, self.end_char, &self.end_version);
+                self.end_char = Some(c);
+            }
+        }
+        self.valid = end;
+        self.is_invalid = is_invalid;
+    }
+
+    fn parse(&mut self, is_final: bool) -> Result<Vec<Event<'static>>> {
+        if let Some(e) = self.deferred.take() {
+            return Err(e);
+        }
+        // a UTF-8 sequence that is cut off at the end of the buffer is left for the next chunk
+        let is_valid = !self.is_invalid && (self.valid == self.buf.len() || !is_final);
+        let src = match str::from_utf8(&self.buf[..self.valid]) {
+            Ok(src) => src,
+            Err(_) => {
+                return Err(Error::Bug {
+                    message: "The valid UTF-8 prefix could not be decoded.".to_string(),
+                })
+            }
+        };
+        #[cfg(test)]
+        {
+            self.lexed += src.len();
+        }
+        // the XML declaration may change how the end position is counted, so it is only trusted
+        // once the declaration is behind us
+        let end_position = match self.st.doc_status {
+            DocStatus::BeforeDeclaration => None,
+            _ => Some(self.end),
+        };
+        let mut iter = Iter::resume(src, &self.opts, mem::take(&mut self.st), self.base);
+        iter.end_position = end_position;
+        let mut events = Vec::new();
+        // the end of the last complete event
+        let mut consumed = 0;
+        let result = loop {
+            iter.hit_end.set(false);
+            match iter.next_event() {
+                // the text may continue in the next chunk
+                Ok(Some(Event::Text(_))) if !is_final && iter.pos == src.len() => break Ok(()),
+                Ok(Some(event)) => {
+                    events.push(event.into_owned());
+                    consumed = iter.pos;
+                }
+                Ok(None) => break Ok(()),
+                // the input ended too soon, more of it may fix that
+                Err(_) if !is_final && iter.hit_end.get() => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        let result = match result {
+            Ok(()) if !is_valid => Err(iter.err_at(src.len(), file!(), line!())),
+            result => result,
+        };
+        self.st = mem::take(&mut iter.st);
+        let mut previous = None;
+        for c in src[..consumed].chars() {
+            self.base.increment(c, previous, &self.st.version);
+            previous = Some(c);
+        }
+        self.buf.drain(..consumed);
+        self.valid -= consumed;
+        if self.end_version != self.st.version {
+            self.recount_end();
+        }
+        self.scan = match (&result, self.scan.take()) {
+            // after an error, every call parses again so that it returns the error again
+            (Err(_), _) => None,
+            // nothing was consumed, so the token and how far it has been searched are unchanged,
+            // unless all that was known of it was that it started with whitespace
+            (Ok(()), Some(scan)) if consumed == 0 && scan.pending != Pending::Whitespace => {
+                Some(scan)
+            }
+            (Ok(()), _) => Scan::new(&self.buf),
+        };
+        match result {
+            Ok(()) => Ok(events),
+            // nothing follows `finish`, so its error cannot wait
+            Err(e) if events.is_empty() || is_final => Err(e),
+            Err(e) => {
+                self.deferred = Some(e);
+                Ok(events)
+            }
+        }
+    }
+
+    /// Counts the position at the end of the valid UTF-8 from `base`, with the rules of the
+    /// current version.
+    fn recount_end(&mut self) {
+        self.end = self.base;
+        self.end_char = None;
+        self.end_version = self.st.version.clone();
+        if let Ok(s) = str::from_utf8(&self.buf[..self.valid]) {
+            for c in s.chars() {
+                self.end.increment(c, self.end_char, &self.end_version);
+                self.end_char = Some(c);
+            }
+        }
+    }
+}
+
+/// The kinds of token that can be left unfinished at the end of the input, by what ends them.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Pending {
+    /// Whitespace, which may turn out to be the start of text or be followed by markup.
+    Whitespace,
+    /// Text, which ends at the next '<'.
+    Text,
+    /// A start or end tag, which ends at a '>' outside of quotes. A '<' is an error.
+    Tag,
+    /// A comment, which ends at '-->'.
+    Comment,
+    /// A CDATA section, which ends at ']]>'.
+    CData,
+    /// A processing instruction or the XML declaration, which ends at '?>'.
+    ProcessingInstruction,
+    /// The DOCTYPE, which ends at a '>' that is neither quoted nor inside the internal subset.
+    DocType,
+}
+
+/// A search of an unfinished token for the bytes that could end it, which picks up where it left
+/// off as more input arrives. It may report an end that the parser then finds is not one, but it
+/// never misses one.
+#[derive(Debug, Clone, Copy)]
+struct Scan {
+    pending: Pending,
+    /// The offset in `buf` where the token's terminator may begin, i.e. past its opening keyword.
+    from: usize,
+    /// The offset in `buf` of the next byte to search.
+    pos: usize,
+    quote: Option<u8>,
+    in_subset: bool,
+}
+
+impl Scan {
+    /// Starts a search of the unfinished token at the start of `buf`, and skips past the input that
+    /// the parser has already seen. Returns `None` when `buf` is too short to tell what the token
+    /// is, in which case it is simply parsed again.
+    fn new(buf: &[u8]) -> Option<Scan> {
+        let start = match buf.iter().position(|&b| !is_whitespace_byte(b)) {
+            Some(start) => start,
+            None => return Some(Scan::with(Pending::Whitespace, 0).skip(buf)),
+        };
+        let rest = &buf[start..];
+        let (pending, keyword) = if rest[0] != b'<' {
+            (Pending::Text, 0)
+        } else if rest.starts_with(b"<!--") {
+            (Pending::Comment, 4)
+        } else if rest.starts_with(b"<![CDATA[") {
+            (Pending::CData, 9)
+        } else if rest.starts_with(b"<!DOCTYPE") {
+            (Pending::DocType, 9)
+        } else if rest.starts_with(b"<?") {
+            (Pending::ProcessingInstruction, 2)
+        } else if rest.len() < 2 || rest.starts_with(b"<!") {
+            return None;
+        } else {
+            (Pending::Tag, 1)
+        };
+        Some(Scan::with(pending, start + keyword).skip(buf))
+    }
+
+    fn with(pending: Pending, from: usize) -> Scan {
+        Scan {
+            pending,
+            from,
+            pos: from,
+            quote: None,
+            in_subset: false,
+        }
+    }
+
+    /// Searches all of `buf`. The parser has already found that it does not hold the end of the
+    /// token, so anything that looks like one is ignored.
+    fn skip(mut self, buf: &[u8]) -> Scan {
+        while self.advance(buf) {}
+        self
+    }
+
+    /// Searches the rest of `buf` and returns `true` if the token may end within it.
+    fn advance(&mut self, buf: &[u8]) -> bool {
+        if self.pending == Pending::Text {
+            let found = memchr(b'<', &buf[self.pos..]);
+            self.pos = found.map_or(buf.len(), |i| self.pos + i + 1);
+            return found.is_some();
+        }
+        while self.pos < buf.len() {
+            let b = buf[self.pos];
+            self.pos += 1;
+            let ends_with = |terminator: &[u8]| {
+                self.pos >= self.from + terminator.len() && buf[..self.pos].ends_with(terminator)
+            };
+            let is_end = match self.pending {
+                Pending::Whitespace => !is_whitespace_byte(b),
+                Pending::Text => b == b'<',
+                Pending::Comment => b == b'>' && ends_with(b"-->"),
+                Pending::CData => b == b'>' && ends_with(b"]]>"),
+                Pending::ProcessingInstruction => b == b'>' && ends_with(b"?>"),
+                Pending::Tag => match (self.quote, b) {
+                    (_, b'<') => true,
+                    (Some(q), _) if b == q => {
+                        self.quote = None;
+                        false
+                    }
+                    (Some(_), _) => false,
+                    (None, b'"') | (None, b'\'') => {
+                        self.quote = Some(b);
+                        false
+                    }
+                    (None, b) => b == b'>',
+                },
+                // the same rules as `parse_doctype`
+                Pending::DocType => match (self.quote, b) {
+                    (Some(q), _) if b == q => {
+                        self.quote = None;
+                        false
+                    }
+                    (Some(_), _) => false,
+                    (None, b'"') | (None, b'\'') => {
+                        self.quote = Some(b);
+                        false
+                    }
+                    (None, b'[') => {
+                        self.in_subset = true;
+                        false
+                    }
+                    (None, b']') => {
+                        self.in_subset = false;
+                        false
+                    }
+                    (None, b) => b == b'>' && !self.in_subset,
+                },
+            };
+            if is_end {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<cats>
+  <cat name="bones"/>
+  <cat name="bishop">punks &amp; 🐈</cat>
+</cats>"#;
+
+    #[test]
+    fn same_events_as_iter() {
+        let mut iter = Iter::new(XML, &ParseOpts::default());
+        let mut expected = Vec::new();
+        while let Some(event) = iter.next_event().unwrap() {
+            expected.push(event.into_owned());
+        }
+        let mut parser = PushParser::new();
+        let mut events = Vec::new();
+        for chunk in XML.as_bytes().chunks(7) {
+            events.extend(parser.feed(chunk).unwrap());
+        }
+        events.extend(parser.finish().unwrap());
+        assert_eq!(events, expected);
+    }
+
+    /// Feeds each document, which is mostly one long token, in small chunks, and checks that the
+    /// parser hands each byte to the tokenizer only a few times.
+    #[test]
+    fn long_tokens_are_not_rescanned() {
+        let long = "x".repeat(4 * 1024 * 1024);
+        let documents = vec![
+            format!("<a>{}</a>", long),
+            format!("<a b='{}'/>", long),
+            format!("<a><!--{}--></a>", long),
+            format!("<a><![CDATA[{}]]></a>", long),
+            format!("<a><?pi k='{}'?></a>", long),
+            format!("<!DOCTYPE a [{}]><a/>", long),
+            format!("<a>{}</a>", " ".repeat(long.len())),
+        ];
+        for xml in documents {
+            let mut parser = PushParser::new();
+            let mut events = Vec::new();
+            for chunk in xml.as_bytes().chunks(1024) {
+                events.extend(parser.feed(chunk).unwrap());
+            }
+            assert!(
+                parser.lexed() < 2 * xml.len(),
+                "{} bytes lexed for {}",
+                parser.lexed(),
+                &xml[..12]
+            );
+            events.extend(parser.finish().unwrap());
+            assert!(matches!(events.last(), Some(Event::End { name }) if name == "a"));
+        }
+    }
+
+    #[test]
+    fn error_position_counts_earlier_chunks() {
+        let xml = "<cats>\n  <cat>\n  </dog>\n</cats>";
+        let expected = match crate::parse_str(xml).err().unwrap() {
+            Error::Parse { position, .. } => position,
+            e => panic!("expected Error::Parse, got {}", e),
+        };
+        let mut parser = PushParser::new();
+        parser.feed(&xml.as_bytes()[..18]).unwrap();
+        match parser.feed(&xml.as_bytes()[18..]).err().unwrap() {
+            Error::Parse { position, .. } => assert_eq!(position, expected),
+            e => panic!("expected Error::Parse, got {}", e),
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+use std::io::Read;
+
+use snafu::ResultExt;
+
+use crate::error::{IoRead, ParseLocation, Result};
+use crate::parser::{Event, ParseOpts, PushParser};
+
+/// The number of bytes the readers request from their source at a time.
+pub(crate) const CHUNK_SIZE: usize = 8 * 1024;
+
+/// The state shared by the sync and async readers: a push parser, and the events it has returned
+/// that have not yet been handed out.
+#[derive(Debug)]
+pub(crate) struct ReadState {
+    /// `None` once the end of the input has been reached.
+    parser: Option<PushParser>,
+    events: VecDeque<Event<'static>>,
+    pub(crate) buf: Vec<u8>,
+}
+
+impl ReadState {
+    pub(crate) fn new(opts: &ParseOpts) -> Self {
+        ReadState {
+            parser: Some(PushParser::new_opts(opts)),
+            events: VecDeque::new(),
+            buf: vec![0; CHUNK_SIZE],
+        }
+    }
+
+    /// Returns the next event if one is ready. `None` means that more input is needed, unless
+    /// `is_done` is true.
+    pub(crate) fn next_ready(&mut self) -> Option<Event<'static>> {
+        self.events.pop_front()
+    }
+
+    /// The number of bytes the parser has handed to the tokenizer so far.
+    #[cfg(test)]
+    pub(crate) fn lexed(&self) -> usize {
+        self.parser.as_ref().map_or(0, PushParser::lexed)
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.parser.is_none() && self.events.is_empty()
+    }
+
+    /// Parses the first `n` bytes of `buf`, which hold the next chunk of input. A chunk of zero
+    /// bytes marks the end of the input.
+    pub(crate) fn parse_chunk(&mut self, n: usize) -> Result<()> {
+        let events = match self.parser.take() {
+            None => return Ok(()),
+            Some(parser) if n == 0 => parser.finish()?,
+            Some(mut parser) => {
+                let events = parser.feed(&self.buf[..n]);
+                self.parser = Some(parser);
+                events?
+            }
+        };
+        self.events.extend(events);
+        Ok(())
+    }
+
+    /// Wraps an error from the source of the input.
+    pub(crate) fn io_err<T>(&self, result: std::io::Result<T>) -> Result<T> {
+        let position = self
+            .parser
+            .as_ref()
+            .map(PushParser::position)
+            .unwrap_or_default();
+        result.context(IoRead {
+            parse_location: ParseLocation {
+                line: position.line,
+                column: position.column,
+            },
+        })
+    }
+}
+
+/// A pull parser that reads a document from a `std::io::Read` a chunk at a time, so the whole
+/// document never needs to be in memory.
+///
+/// ```
+/// use ezxml::{Event, EventReader};
+///
+/// let mut reader = EventReader::new("<cat>bones</cat>".as_bytes());
+/// let mut names = Vec::new();
+/// while let Some(event) = reader.next_event().unwrap() {
+///     if let Event::Start { name, .. } = event {
+///         names.push(name);
+///     }
+/// }
+/// assert_eq!(names, vec!["cat"]);
+/// ```
+#[derive(Debug)]
+pub struct EventReader<R: Read> {
+    source: R,
+    st: ReadState,
+}
+
+impl<R: Read> EventReader<R> {
+    pub fn new(source: R) -> Self {
+        Self::new_opts(source, &ParseOpts::default())
+    }
+
+    pub fn new_opts(source: R, opts: &ParseOpts) -> Self {
+        EventReader {
+            source,
+            st: ReadState::new(opts),
+        }
+    }
+
+    /// Returns the next event, or `None` once the end of the document has been reached.
+    pub fn next_event(&mut self) -> Result<Option<Event<'static>>> {
+        loop {
+            if let Some(event) = self.st.next_ready() {
+                return Ok(Some(event));
+            }
+            if self.st.is_done() {
+                return Ok(None);
+            }
+            let n = loop {
+                match self.source.read(&mut self.st.buf) {
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    result => break self.st.io_err(result)?,
+                }
+            };
+            self.st.parse_chunk(n)?;
+        }
+    }
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = Result<Event<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_text_is_read_in_linear_time() {
+        // a text node far longer than a chunk
+        let xml = format!("<a>{}</a>", "x".repeat(8 * 1024 * 1024));
+        let mut reader = EventReader::new(xml.as_bytes());
+        assert!(matches!(reader.next_event(), Ok(Some(Event::Start { .. }))));
+        match reader.next_event() {
+            Ok(Some(Event::Text(text))) => assert_eq!(text.len(), xml.len() - 7),
+            other => panic!("expected Event::Text, got {:?}", other),
+        }
+        assert!(matches!(reader.next_event(), Ok(Some(Event::End { .. }))));
+        // the parser is still there to ask until the end of the input is read
+        assert!(reader.st.lexed() < 2 * xml.len());
+        assert!(matches!(reader.next_event(), Ok(None)));
+    }
+}
@@ -0,0 +1,187 @@
+use std::borrow::Cow;
+
+use memchr::{memchr, memchr3};
+
+use crate::error::Result;
+use crate::parser::chars::is_char;
+use crate::parser::Iter;
+
+/// Parses character data up to the next '<' or the end of input, decoding entity references. The
+/// text is borrowed from the input unless an entity reference had to be decoded.
+pub(crate) fn parse_text<'a>(iter: &mut Iter<'a>) -> Result<Cow<'a, str>> {
+    let start = iter.pos;
+    // decoded text, only allocated once we find an entity reference
+    let mut owned: Option<String> = None;
+    // the start of the input that has not yet been copied into `owned`
+    let mut copied = start;
+    loop {
+        let found = memchr3(b'<', b'&', b']', iter.rest()).map(|i| iter.pos + i);
+        let i = match found {
+            None => iter.bytes.len(),
+            Some(i) => i,
+        };
+        iter.pos = i;
+        match iter.peek() {
+            None | Some(b'<') => break,
+            Some(b'&') => {
+                let s = owned.get_or_insert_with(String::new);
+                s.push_str(&checked_text(iter, copied, i)?);
+                decode_reference(iter, s)?;
+                copied = iter.pos;
+            }
+            _ => {
+                // ']]>' is not allowed in character data
+                if iter.starts_with(b"]]>") {
+                    return Err(iter.err(file!(), line!()));
+                }
+                iter.pos += 1;
+            }
+        }
+    }
+    Ok(match owned {
+        None => checked_text(iter, start, iter.pos)?,
+        Some(mut s) => {
+            s.push_str(&checked_text(iter, copied, iter.pos)?);
+            Cow::Owned(s)
+        }
+    })
+}
+
+/// Parses a quoted attribute value and leaves the iter just past the closing quote.
+pub(crate) fn parse_attribute_value<'a>(iter: &mut Iter<'a>) -> Result<Cow<'a, str>> {
+    let quote = match iter.peek() {
+        Some(q @ b'"') | Some(q @ b'\'') => q,
+        Some(_) => return Err(iter.err(file!(), line!())),
+        None => return Err(iter.end_err(file!(), line!())),
+    };
+    iter.pos += 1;
+    let start = iter.pos;
+    let mut owned: Option<String> = None;
+    let mut copied = start;
+    loop {
+        let i = match memchr3(quote, b'<', b'&', iter.rest()) {
+            Some(i) => iter.pos + i,
+            None => return Err(iter.end_err(file!(), line!())),
+        };
+        iter.pos = i;
+        match iter.peek() {
+            Some(b'&') => {
+                let s = owned.get_or_insert_with(String::new);
+                s.push_str(&checked_text(iter, copied, i)?);
+                decode_reference(iter, s)?;
+                copied = iter.pos;
+            }
+            Some(b'<') => return Err(iter.err(file!(), line!())),
+            _ => break,
+        }
+    }
+    let value = match owned {
+        None => checked_text(iter, start, iter.pos)?,
+        Some(mut s) => {
+            s.push_str(&checked_text(iter, copied, iter.pos)?);
+            Cow::Owned(s)
+        }
+    };
+    // step past the closing quote
+    iter.pos += 1;
+    Ok(value)
+}
+
+/// Decodes the entity or character reference that the iter is pointing at, appends it to `out`,
+/// and leaves the iter just past the terminating ';'.
+fn decode_reference(iter: &mut Iter<'_>, out: &mut String) -> Result<()> {
+    let start = iter.pos;
+    let end = match memchr(b';', iter.rest()) {
+        Some(i) => iter.pos + i,
+        None => return Err(iter.end_err(file!(), line!())),
+    };
+    let reference = &iter.src[start + 1..end];
+    let c = match reference {
+        "lt" => '<',
+        "gt" => '>',
+        "amp" => '&',
+        "apos" => '\'',
+        "quot" => '"',
+        _ => {
+            let code = if let Some(hex) = reference.strip_prefix("#x") {
+                parse_code_point(hex, 16)
+            } else if let Some(dec) = reference.strip_prefix('#') {
+                parse_code_point(dec, 10)
+            } else {
+                None
+            };
+            match code.and_then(std::char::from_u32) {
+                Some(c) if is_char(c, iter.is_1_1()) => c,
+                _ => return Err(iter.err_at(start, file!(), line!())),
+            }
+        }
+    };
+    out.push(c);
+    iter.pos = end + 1;
+    Ok(())
+}
+
+/// Checks that `iter.src[start..end]` holds only chars that are allowed to appear literally in the
+/// document's version of XML, and returns it with each line end normalized to '\n'. The text is
+/// borrowed unless a line end had to be replaced.
+pub(crate) fn checked_text<'a>(iter: &Iter<'a>, start: usize, end: usize) -> Result<Cow<'a, str>> {
+    let is_1_1 = iter.is_1_1();
+    let bytes = &iter.bytes[..end];
+    // the text with line ends replaced, only allocated once we find one that needs replacing
+    let mut owned: Option<String> = None;
+    let mut copied = start;
+    let mut i = start;
+    while i < end {
+        // the length of a line end that must be replaced with '\n'
+        let line_end = match bytes[i] {
+            b'\t' | b'\n' | 0x20..=0x7E => 0,
+            b'\r' => match bytes.get(i + 1) {
+                Some(b'\n') => 2,
+                Some(0xC2) if is_1_1 && bytes.get(i + 2) == Some(&0x85) => 3,
+                _ => 1,
+            },
+            0x7F if !is_1_1 => 0,
+            // U+0080 to U+009F, NEL and the other C1 controls, are restricted in XML 1.1
+            0xC2 if is_1_1 => match bytes.get(i + 1) {
+                Some(0x85) => 2,
+                Some(0x80..=0x9F) => return Err(iter.err_at(i, file!(), line!())),
+                _ => 0,
+            },
+            // U+2028, LINE SEPARATOR
+            0xE2 if is_1_1 && bytes[i..].starts_with(&[0xE2, 0x80, 0xA8]) => 3,
+            // U+FFFE and U+FFFF
+            0xEF if bytes[i..].starts_with(&[0xEF, 0xBF, 0xBE])
+                || bytes[i..].starts_with(&[0xEF, 0xBF, 0xBF]) =>
+            {
+                return Err(iter.err_at(i, file!(), line!()))
+            }
+            0x80..=0xFF => 0,
+            // the other C0 controls, and DEL in XML 1.1
+            _ => return Err(iter.err_at(i, file!(), line!())),
+        };
+        if line_end == 0 {
+            i += 1;
+            continue;
+        }
+        let s = owned.get_or_insert_with(String::new);
+        s.push_str(&iter.src[copied..i]);
+        s.push('\n');
+        i += line_end;
+        copied = i;
+    }
+    Ok(match owned {
+        None => Cow::Borrowed(&iter.src[start..end]),
+        Some(mut s) => {
+            s.push_str(&iter.src[copied..end]);
+            Cow::Owned(s)
+        }
+    })
+}
+
+fn parse_code_point(digits: &str, radix: u32) -> Option<u32> {
+    // from_str_radix would also accept a leading '+'
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    u32::from_str_radix(digits, radix).ok()
+}
@@ -0,0 +1,97 @@
+use ezxml::ParseOpts;
+use xdoc::arena;
+use xdoc::c14n::{canonicalize, canonicalize_node, C14nOpts, Method};
+
+fn parse(xml: &str) -> xdoc::Document {
+    let opts = ParseOpts::default().with_preserve_whitespace(true);
+    ezxml::parse_str_opts(xml, &opts).unwrap()
+}
+
+fn opts(method: Method, with_comments: bool) -> C14nOpts {
+    C14nOpts {
+        method,
+        with_comments,
+        ..C14nOpts::default()
+    }
+}
+
+/// The examples from section 3 of Canonical XML 1.1, which exclusive canonicalization gives the
+/// same result for since every namespace is used where it is declared.
+const EXAMPLES: &[(&str, &str, &str)] = &[
+    (
+        "3.1",
+        include_str!("c14n/example-3.1.xml"),
+        include_str!("c14n/example-3.1.c14n"),
+    ),
+    (
+        "3.2",
+        include_str!("c14n/example-3.2.xml"),
+        include_str!("c14n/example-3.2.c14n"),
+    ),
+    (
+        "3.4",
+        include_str!("c14n/example-3.4.xml"),
+        include_str!("c14n/example-3.4.c14n"),
+    ),
+    (
+        "3.6",
+        include_str!("c14n/example-3.6.xml"),
+        include_str!("c14n/example-3.6.c14n"),
+    ),
+];
+
+#[test]
+fn canonical_xml_examples() {
+    for (name, input, expected) in EXAMPLES {
+        let doc = parse(input);
+        for method in &[Method::Inclusive, Method::Exclusive] {
+            let actual = canonicalize(&doc, &opts(*method, false)).unwrap();
+            assert_eq!(&actual, expected, "example {} with {:?}", name, method);
+        }
+    }
+}
+
+#[test]
+fn canonical_xml_with_comments() {
+    let doc = parse(include_str!("c14n/example-3.1.xml"));
+    let expected = include_str!("c14n/example-3.1.c14n-comments");
+    for method in &[Method::Inclusive, Method::Exclusive] {
+        assert_eq!(canonicalize(&doc, &opts(*method, true)).unwrap(), expected);
+    }
+}
+
+#[test]
+fn canonical_xml_namespaces() {
+    let doc = parse(include_str!("c14n/example-3.3.xml"));
+    let expected = include_str!("c14n/example-3.3.c14n");
+    assert_eq!(
+        canonicalize(&doc, &opts(Method::Inclusive, false)).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn exclusive_subtrees() {
+    let cases = &[
+        (
+            include_str!("c14n/exclusive-2.2-a.xml"),
+            include_str!("c14n/exclusive-2.2-a.c14n"),
+        ),
+        (
+            include_str!("c14n/exclusive-2.2-b.xml"),
+            include_str!("c14n/exclusive-2.2-b.c14n"),
+        ),
+    ];
+    let exclusive = include_str!("c14n/exclusive-2.2.exc-c14n");
+    for (input, inclusive) in cases {
+        let doc = arena::Document::from(&parse(input));
+        let elem2 = doc
+            .descendants(doc.document_node())
+            .find(|&id| doc.element(id).is_some_and(|e| e.name.as_str() == "elem2"))
+            .unwrap();
+        let actual = canonicalize_node(&doc, elem2, &opts(Method::Inclusive, false)).unwrap();
+        assert_eq!(&actual, inclusive);
+        let actual = canonicalize_node(&doc, elem2, &opts(Method::Exclusive, false)).unwrap();
+        assert_eq!(actual, exclusive);
+    }
+}
@@ -5,3 +5,59 @@ fn good_syntax_ezfile_test() {
     let parse_result = ezxml::parse_str(xml_str.as_str());
     assert!(parse_result.is_ok());
 }
+
+fn nested_xml(depth: usize) -> String {
+    let mut xml = String::new();
+    for _ in 0..depth {
+        xml.push_str("<a>");
+    }
+    for _ in 0..depth {
+        xml.push_str("</a>");
+    }
+    xml
+}
+
+#[test]
+fn deeply_nested_elements_test() {
+    let depth = 5000;
+    let xml = nested_xml(depth);
+    let opts = ezxml::ParseOpts::default().with_max_depth(depth);
+    // a recursive parser overflows this stack long before reaching the bottom of the document
+    let handle = std::thread::Builder::new()
+        .stack_size(2 * 1024 * 1024)
+        .spawn(move || {
+            let doc = ezxml::parse_str_opts(xml.as_str(), &opts).unwrap();
+            let mut found = 1;
+            let mut element = doc.root();
+            while let Some(ezxml::Node::Element(child)) = element.nodes.first() {
+                found += 1;
+                element = child;
+            }
+            found
+        })
+        .unwrap();
+    assert_eq!(handle.join().unwrap(), depth);
+}
+
+#[test]
+fn max_depth_exceeded_test() {
+    let xml = nested_xml(100_000);
+    let parse_result = ezxml::parse_str(xml.as_str());
+    match parse_result.err().unwrap() {
+        ezxml::error::Error::MaxDepth {
+            max_depth,
+            position,
+            ..
+        } => {
+            assert_eq!(max_depth, ezxml::ParseOpts::default().max_depth);
+            assert_eq!(position.absolute, 3 * max_depth as u64 + 1);
+        }
+        e => panic!("expected ezxml::error::Error::MaxDepth, got {}", e),
+    }
+}
+
+#[test]
+fn second_root_element_test() {
+    let parse_result = ezxml::parse_str("<a/><b/>");
+    assert!(parse_result.is_err());
+}
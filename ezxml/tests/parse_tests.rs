@@ -1,18 +1,30 @@
 //! `parse_tests.rs` is generated by build.rs
 #[test]
-fn bad_syntax_unescaped_angle_test() {
-    let info = xtest::load("unescaped-angle");
+fn good_syntax_prolog_and_epilog_test() {
+    let info = xtest::load("prolog-and-epilog");
     let xml_str = info.read_xml_file();
     let parse_result = ezxml::parse_str(xml_str.as_str());
-    assert!(parse_result.is_err());
-    let err = parse_result.err().unwrap();
-    match err {
-        ezxml::error::Error::Parse { position, .. } => {
-            assert_eq!(position.absolute, 95);
-            assert_eq!(position.line, 4);
-            assert_eq!(position.column, 39);
+    if let Err(e) = parse_result {
+        panic!("expected parse_result to be Ok, got Err: {}", e);
+    }
+    let actual = parse_result.unwrap();
+    let expected = &info.metadata.expected.unwrap();
+    let equal = expected == &actual;
+    if !equal {
+        let diff = xdoc::diff::Diff::new(expected, &actual);
+        if !diff.is_empty() {
+            panic!(
+                "the parsed document differs from the expected one:\n{}",
+                diff
+            );
+        }
+        let expected_str = expected.to_string();
+        let actual_str = actual.to_string();
+        if expected_str != actual_str {
+            assert_eq!(expected_str, actual_str);
+        } else {
+            assert!(equal);
         }
-        _ => panic!("Error was expected to be of type ezxml::error::Error::Parse, but was not."),
     }
 }
 
@@ -45,6 +57,13 @@ fn good_syntax_ezfile_test() {
     let expected = &info.metadata.expected.unwrap();
     let equal = expected == &actual;
     if !equal {
+        let diff = xdoc::diff::Diff::new(expected, &actual);
+        if !diff.is_empty() {
+            panic!(
+                "the parsed document differs from the expected one:\n{}",
+                diff
+            );
+        }
         let expected_str = expected.to_string();
         let actual_str = actual.to_string();
         if expected_str != actual_str {
@@ -54,3 +73,30 @@ fn good_syntax_ezfile_test() {
         }
     }
 }
+
+#[test]
+fn bad_syntax_unescaped_angle_test() {
+    let info = xtest::load("unescaped-angle");
+    let xml_str = info.read_xml_file();
+    let parse_result = ezxml::parse_str(xml_str.as_str());
+    assert!(parse_result.is_err());
+    let err = parse_result.err().unwrap();
+    match err {
+        ezxml::error::Error::Parse { position, .. } => {
+            assert_eq!(position.absolute, 95);
+            assert_eq!(position.line, 4);
+            assert_eq!(position.column, 39);
+        }
+        _ => panic!("Error was expected to be of type ezxml::error::Error::Parse, but was not."),
+    }
+}
+
+#[test]
+fn good_syntax_library_test() {
+    let info = xtest::load("library");
+    let xml_str = info.read_xml_file();
+    let parse_result = ezxml::parse_str(xml_str.as_str());
+    if let Err(e) = parse_result {
+        panic!("expected parse_result to be Ok, got Err: {}", e);
+    }
+}
@@ -0,0 +1,63 @@
+use xdoc::patch::Patch;
+use xdoc::Document;
+
+const PATCH: &str = r#"<?xml version="1.0"?>
+<diff xmlns:meta="urn:meta" xmlns:x="urn:extra">
+  <!-- operations run in order, each against the result of the last -->
+  <add sel="/library/shelf[@name='fiction']" pos="prepend"><book id="b0"/></add>
+  <add sel="/library/shelf[@name='fiction']/book[@id='b0']" type="@year">2020</add>
+  <replace sel="//book[@id='b1']/price/@currency">EUR</replace>
+  <replace sel="//book[@id='b2']/title"><title>The Short Road</title></replace>
+  <remove sel="//book[@id='b1']/meta:rating"/>
+  <remove sel="/library/@meta:updated"/>
+  <remove sel="/library/x:note"/>
+  <add sel="/library/shelf[last()]" pos="after"><shelf name="drama"/></add>
+</diff>
+"#;
+
+fn load(name: &str) -> Document {
+    let xml = xtest::load(name).read_xml_file();
+    ezxml::parse_str(xml.as_str()).unwrap()
+}
+
+#[test]
+fn apply_patch_document() {
+    let patch = Patch::from_document(&ezxml::parse_str(PATCH).unwrap()).unwrap();
+    assert_eq!(patch.operations.len(), 8);
+    let mut doc = load("library");
+    patch.apply(&mut doc).unwrap();
+
+    let library = &doc.root;
+    assert_eq!(library.attribute("m:updated"), None);
+    assert!(library.first_child_named("x:note").is_none());
+    let shelves = library.children_elements().collect::<Vec<_>>();
+    let names = shelves
+        .iter()
+        .map(|shelf| shelf.attribute("name").unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["fiction", "poetry", "drama"]);
+
+    let books = shelves[0].children_elements().collect::<Vec<_>>();
+    assert_eq!(books[0].attribute("id"), Some("b0"));
+    assert_eq!(books[0].attribute("year"), Some("2020"));
+    let price = books[1].first_child_named("price").unwrap();
+    assert_eq!(price.attribute("currency"), Some("EUR"));
+    assert!(books[1].first_child_named("m:rating").is_none());
+    let title = books[2].first_child_named("title").unwrap();
+    assert_eq!(title.inner_text(), "The Short Road");
+
+    // the patch survives being written and read back
+    let written = patch.to_document().to_string();
+    let reread = Patch::from_document(&ezxml::parse_str(written.as_str()).unwrap()).unwrap();
+    assert_eq!(reread, patch);
+}
+
+#[test]
+fn failed_patch_document() {
+    let patch = r#"<diff><remove sel="//book"/></diff>"#;
+    let patch = Patch::from_document(&ezxml::parse_str(patch).unwrap()).unwrap();
+    let mut doc = load("library");
+    let err = patch.apply(&mut doc).unwrap_err();
+    assert!(err.to_string().contains("selects 3 nodes"));
+    assert_eq!(doc, load("library"));
+}
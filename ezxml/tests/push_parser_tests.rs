@@ -0,0 +1,152 @@
+use ezxml::error::Error;
+use ezxml::{Event, PushParser};
+
+/// Documents that exercise the chunk boundaries the corpus does not: entity and character
+/// references, multi-byte UTF-8 sequences, line ends, and the `<!` and `<?` constructs.
+const EXTRA_DOCUMENTS: &[&str] = &[
+    r#"<?xml version="1.0"?>
+<!DOCTYPE cats [ <!ENTITY x "y>"> ]>
+<!-- prolog -->
+<cats a='1 &amp; 2' b="&#x1F408;">
+  <cat name="bones"/>
+  <cät näme="bïshop">pünks &lt;&#65;&gt; 🐈</cät>
+  <![CDATA[ <raw> ]]>
+  <?pi key="value"?>
+  <!-- comment -->
+</cats>
+<!-- epilog -->
+"#,
+    "<a>text ]] ]]&gt; done</a>",
+    "<a><b></a>",
+    "<a>&bogus;</a>",
+    "<a b='1' b='2'/>",
+    "<a/><b/>",
+    "<a><!-x--></a>",
+    "<a>\u{10348}\u{10348}",
+    "<?xml version=\"1.0\"?>\r\n<a b='\r\n'>\r\n<b>x\r\ny\r</b>\r\n</a>\r\n",
+    "<a>\r\n<b>\r\n</c>\r\n</a>",
+    "<?xml version=\"1.1\"?>\r\u{85}<a>\u{85}\r\u{85}\u{2028}<b>\u{80}</b></a>",
+];
+
+/// Reduces a parse result to something comparable: the events, or the position of the error.
+fn outcome(
+    result: ezxml::error::Result<Vec<Event<'static>>>,
+) -> Result<Vec<Event<'static>>, String> {
+    result.map_err(|e| match e {
+        Error::Parse { position, .. } | Error::MaxDepth { position, .. } => {
+            format!("{:?}", position)
+        }
+        e => panic!("unexpected error {}", e),
+    })
+}
+
+fn parse_chunks(chunks: &[&[u8]]) -> Result<Vec<Event<'static>>, String> {
+    let mut parser = PushParser::new();
+    let mut events = Vec::new();
+    for chunk in chunks {
+        events.extend(outcome(parser.feed(chunk))?);
+    }
+    events.extend(outcome(parser.finish())?);
+    Ok(events)
+}
+
+fn documents() -> Vec<(String, String)> {
+    let mut documents: Vec<(String, String)> = xtest::load_all()
+        .into_iter()
+        .map(|f| (f.name.clone(), f.read_xml_file()))
+        .collect();
+    for (i, &xml) in EXTRA_DOCUMENTS.iter().enumerate() {
+        documents.push((format!("extra-{}", i), xml.to_string()));
+    }
+    documents
+}
+
+fn error_position(err: Error) -> (u64, u64, u64) {
+    match err {
+        Error::Parse { position, .. } => (position.absolute, position.line, position.column),
+        e => panic!("unexpected error {}", e),
+    }
+}
+
+#[test]
+fn corpus_outcomes_test() {
+    for f in xtest::load_all() {
+        let xml = f.read_xml_file();
+        let mut parser = PushParser::new();
+        let result = parser.feed(xml.as_bytes()).and_then(|_| parser.finish());
+        match f.metadata.syntax {
+            xtest::Syntax::Good {} => assert!(result.is_ok(), "{}", f.name),
+            xtest::Syntax::Bad {
+                character_position,
+                line,
+                column,
+            } => {
+                let position = error_position(result.err().unwrap());
+                assert_eq!(position, (character_position, line, column), "{}", f.name);
+                let expected = error_position(ezxml::parse_str(&xml).err().unwrap());
+                assert_eq!(position, expected, "{}", f.name);
+            }
+        }
+    }
+}
+
+#[test]
+fn split_at_every_offset_test() {
+    for (name, xml) in documents() {
+        let bytes = xml.as_bytes();
+        let expected = parse_chunks(&[bytes]);
+        for i in 0..=bytes.len() {
+            let (a, b) = bytes.split_at(i);
+            assert_eq!(parse_chunks(&[a, b]), expected, "{} split at {}", name, i);
+        }
+    }
+}
+
+#[test]
+fn one_byte_at_a_time_test() {
+    for (name, xml) in documents() {
+        let bytes = xml.as_bytes();
+        let chunks: Vec<&[u8]> = bytes.chunks(1).collect();
+        assert_eq!(parse_chunks(&chunks), parse_chunks(&[bytes]), "{}", name);
+    }
+}
+
+#[test]
+fn events_are_returned_as_they_complete_test() {
+    let mut parser = PushParser::new();
+    assert_eq!(parser.feed(b"<cats><cat>Bo").unwrap().len(), 2);
+    let events = parser.feed(b"nes &am").unwrap();
+    assert!(events.is_empty());
+    let events = parser.feed(b"p; co</cat>").unwrap();
+    assert_eq!(
+        events,
+        vec![
+            Event::Text("Bones & co".into()),
+            Event::End { name: "cat".into() }
+        ]
+    );
+    assert_eq!(parser.feed(b"</cats>").unwrap().len(), 1);
+    assert!(parser.finish().unwrap().is_empty());
+}
+
+#[test]
+fn incomplete_document_test() {
+    let mut parser = PushParser::new();
+    parser.feed("<a>\u{e9}".as_bytes()).unwrap();
+    assert!(parser.finish().is_err());
+
+    let mut parser = PushParser::new();
+    parser.feed(&"<a>\u{e9}</a>".as_bytes()[..4]).unwrap();
+    assert!(parser.finish().is_err());
+}
+
+#[test]
+fn invalid_utf8_test() {
+    let mut parser = PushParser::new();
+    // the start tag is returned before the error
+    assert_eq!(parser.feed(b"<a>\xff</a>").unwrap().len(), 1);
+    assert!(parser.feed(b"").is_err());
+    // the error is returned again rather than parsing past it
+    assert!(parser.feed(b"").is_err());
+    assert!(parser.finish().is_err());
+}
@@ -0,0 +1,126 @@
+use std::io::Read;
+
+use ezxml::error::Error;
+use ezxml::{Event, EventReader, PushParser};
+
+const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<cats>
+  <cat name="bones"/>
+  <cat name="bïshop">punks &amp; 🐈</cat>
+</cats>
+"#;
+
+fn expected_events() -> Vec<Event<'static>> {
+    let mut parser = PushParser::new();
+    let mut events = parser.feed(XML.as_bytes()).unwrap();
+    events.extend(parser.finish().unwrap());
+    events
+}
+
+/// A source that returns at most `chunk_size` bytes from each read.
+struct Trickle<'a> {
+    data: &'a [u8],
+    chunk_size: usize,
+}
+
+impl<'a> Read for Trickle<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.chunk_size.min(buf.len()).min(self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+/// A source that fails after returning `data`.
+struct Broken<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Read for Broken<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.data.is_empty() {
+            return Err(std::io::Error::other("connection reset"));
+        }
+        self.data.read(buf)
+    }
+}
+
+#[test]
+fn event_reader_test() {
+    let reader = EventReader::new(XML.as_bytes());
+    let events: Vec<Event<'static>> = reader.collect::<ezxml::error::Result<_>>().unwrap();
+    assert_eq!(events, expected_events());
+}
+
+#[test]
+fn event_reader_small_reads_test() {
+    for chunk_size in 1..8 {
+        let source = Trickle {
+            data: XML.as_bytes(),
+            chunk_size,
+        };
+        let events: Vec<Event<'static>> = EventReader::new(source)
+            .collect::<ezxml::error::Result<_>>()
+            .unwrap();
+        assert_eq!(events, expected_events(), "chunk size {}", chunk_size);
+    }
+}
+
+#[test]
+fn event_reader_parse_error_test() {
+    let mut reader = EventReader::new("<cats></cat>".as_bytes());
+    assert!(matches!(reader.next_event(), Ok(Some(Event::Start { .. }))));
+    assert!(matches!(reader.next_event(), Err(Error::Parse { .. })));
+}
+
+#[test]
+fn event_reader_io_error_test() {
+    let source = Broken {
+        data: "<cats>\n<cat>".as_bytes(),
+    };
+    let mut reader = EventReader::new(source);
+    assert!(matches!(reader.next_event(), Ok(Some(Event::Start { .. }))));
+    assert!(matches!(reader.next_event(), Ok(Some(Event::Text(_)))));
+    assert!(matches!(reader.next_event(), Ok(Some(Event::Start { .. }))));
+    match reader.next_event() {
+        Err(Error::IoRead { parse_location, .. }) => assert_eq!(parse_location.line, 2),
+        other => panic!("expected Error::IoRead, got {:?}", other),
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_event_reader_test() {
+    use tokio::io::AsyncWriteExt;
+
+    let (mut writer, reader) = tokio::io::duplex(16);
+    // the document is written a few bytes at a time while it is being parsed
+    let writing = tokio::spawn(async move {
+        for chunk in XML.as_bytes().chunks(5) {
+            writer.write_all(chunk).await.unwrap();
+            tokio::task::yield_now().await;
+        }
+    });
+    let mut reader = ezxml::AsyncEventReader::new(reader);
+    let mut events = Vec::new();
+    while let Some(event) = reader.next_event().await.unwrap() {
+        events.push(event);
+    }
+    writing.await.unwrap();
+    assert_eq!(events, expected_events());
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_event_reader_parse_error_test() {
+    let mut reader = ezxml::AsyncEventReader::new("<cats></cat>".as_bytes());
+    assert!(matches!(
+        reader.next_event().await,
+        Ok(Some(Event::Start { .. }))
+    ));
+    assert!(matches!(
+        reader.next_event().await,
+        Err(Error::Parse { .. })
+    ));
+}
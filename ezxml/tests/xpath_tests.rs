@@ -0,0 +1,270 @@
+use xdoc::arena::{Document, Node};
+use xdoc::xpath::{string_value, NodeRef, Value, XPath, XPathOpts};
+
+enum Expected {
+    /// The nodes, in order, as written by `describe`.
+    Nodes(&'static [&'static str]),
+    Str(&'static str),
+    Num(f64),
+    Bool(bool),
+}
+
+use Expected::*;
+
+/// Each case is the name of an `xtest` file, an expression, and what the expression evaluates to
+/// with `opts` and the document node as the context node.
+const CASES: &[(&str, &str, Expected)] = &[
+    // paths and positions
+    (
+        "library",
+        "/library/shelf/book/title",
+        Nodes(&["title", "title", "title"]),
+    ),
+    ("library", "count(//book)", Num(3.0)),
+    ("library", "//book[@year > 2000]/@id", Nodes(&["@id=b2"])),
+    ("library", "count(//book[last()]/author)", Num(3.0)),
+    ("library", "string(//book[last()]/author)", Str("Ben Jones")),
+    ("library", "(//book)[last()]/@id", Nodes(&["@id=b3"])),
+    ("library", "//book[position() = 2]/@id", Nodes(&["@id=b2"])),
+    ("library", "//book[author[2]]/title/em", Nodes(&["em"])),
+    ("library", "//title[em]", Str("The Long Road")),
+    ("library", "//price[. = 8]", Nodes(&["price"])),
+    (
+        "library",
+        "//book[price > $min]/@id",
+        Nodes(&["@id=b1", "@id=b3"]),
+    ),
+    (
+        "library",
+        "//shelf/@name | //book[1]/@id",
+        Nodes(&["@name=fiction", "@id=b1", "@name=poetry", "@id=b3"]),
+    ),
+    (
+        "library",
+        "count(/descendant::*[self::title or self::author])",
+        Num(7.0),
+    ),
+    // axes
+    (
+        "library",
+        "//price[@currency = 'GBP']/ancestor::shelf/@name",
+        Nodes(&["@name=fiction"]),
+    ),
+    (
+        "library",
+        "//author[. = 'Cara Lee']/preceding-sibling::*",
+        Nodes(&["title", "author"]),
+    ),
+    (
+        "library",
+        "name(//author[. = 'Cara Lee']/preceding-sibling::*[1])",
+        Str("author"),
+    ),
+    (
+        "library",
+        "//author[. = 'Cara Lee']/following-sibling::*[1]",
+        Str("8"),
+    ),
+    (
+        "library",
+        "//book[@id = 'b1']/following::book/@id",
+        Nodes(&["@id=b2", "@id=b3"]),
+    ),
+    (
+        "library",
+        "//book[@id = 'b3']/preceding::book[1]/@id",
+        Nodes(&["@id=b2"]),
+    ),
+    (
+        "library",
+        "count(//book[@id = 'b2']/descendant::node())",
+        Num(13.0),
+    ),
+    ("library", "count(//em/ancestor-or-self::*)", Num(5.0)),
+    (
+        "library",
+        "//@*[starts-with(name(), 'xml:')]",
+        Nodes(&["@xml:lang=en", "@xml:lang=en-GB", "@xml:lang=fr"]),
+    ),
+    // namespaces
+    ("library", "sum(//m:rating)", Num(9.0)),
+    ("library", "sum(//o:rating)", Num(3.0)),
+    ("library", "count(//*[local-name() = 'rating'])", Num(3.0)),
+    (
+        "library",
+        "/library/@m:updated",
+        Nodes(&["@m:updated=2020-05-01"]),
+    ),
+    ("library", "local-name(/library/@m:updated)", Str("updated")),
+    (
+        "library",
+        "namespace-uri(/library/@m:updated)",
+        Str("urn:meta"),
+    ),
+    (
+        "library",
+        "namespace-uri(//shelf[2]/book/*[4])",
+        Str("urn:other"),
+    ),
+    ("library", "count(//shelf[2]/namespace::*)", Num(3.0)),
+    (
+        "library",
+        "string(//shelf[2]/namespace::m)",
+        Str("urn:other"),
+    ),
+    ("library", "name(//x:*)", Str("x:note")),
+    // text, comments and processing instructions
+    ("library", "//x:note", Str("5 < 6 and more")),
+    ("library", "count(//x:note/text())", Num(1.0)),
+    (
+        "library",
+        "//comment()",
+        Nodes(&[
+            "<!-- a small library catalog to run queries against -->",
+            "<!-- out of print -->",
+        ]),
+    ),
+    ("library", "name(/processing-instruction())", Str("catalog")),
+    (
+        "library",
+        "string(/processing-instruction('catalog'))",
+        Str("version=\"2\""),
+    ),
+    (
+        "library",
+        "//author[. = 'Dan Roux']/text()",
+        Nodes(&["\"Dan Roux\""]),
+    ),
+    // functions
+    (
+        "library",
+        "normalize-space(//book[@id = 'b3']/title)",
+        Str("Les Fleurs"),
+    ),
+    (
+        "library",
+        "substring-before(//book[1]/title, ' &')",
+        Str("Stone"),
+    ),
+    (
+        "library",
+        "substring-after(//book/@xml:lang[. = 'en-GB'], '-')",
+        Str("GB"),
+    ),
+    (
+        "library",
+        "translate(//author[1], 'abcdefghijklmnopqrstuvwxyz', 'ABCDEFGHIJKLMNOPQRSTUVWXYZ')",
+        Str("ANN SMITH"),
+    ),
+    (
+        "library",
+        "concat(count(//shelf), ' shelves, ', count(//author), ' authors')",
+        Str("2 shelves, 4 authors"),
+    ),
+    (
+        "library",
+        "string-length(//book[@id = 'b2']/title)",
+        Num(13.0),
+    ),
+    ("library", "substring('12345', 1.5, 2.6)", Str("234")),
+    ("library", "substring('12345', 0 div 0, 3)", Str("")),
+    (
+        "library",
+        "round(sum(//price) div count(//price))",
+        Num(14.0),
+    ),
+    ("library", "floor(-1.5) + ceiling(1.2) + 7 mod 3", Num(1.0)),
+    ("library", "string(1 div 0)", Str("Infinity")),
+    (
+        "library",
+        "//book[lang('en')]/@id",
+        Nodes(&["@id=b1", "@id=b2"]),
+    ),
+    ("library", "count(id('b3 b1')/title)", Num(2.0)),
+    ("library", "id('b3 b1')/title", Str("Stone & Sword")),
+    ("library", "boolean(//book[@id = 'b9'])", Bool(false)),
+    // comparisons
+    ("library", "//book/@year = '2004'", Bool(true)),
+    ("library", "//book/@year != '2004'", Bool(true)),
+    ("library", "//book/@year = 1900", Bool(false)),
+    ("library", "//book/@year < //price", Bool(false)),
+    ("library", "//book = true()", Bool(true)),
+    ("library", "'1' = 1.0 and '1' != '1.0'", Bool(true)),
+    // other files
+    ("ezfile", "//cat[text()]/@name", Nodes(&["@name=bishop"])),
+    ("prolog-and-epilog", "count(/node())", Num(4.0)),
+    ("prolog-and-epilog", "//@name", Nodes(&["@name=bones"])),
+];
+
+fn opts() -> XPathOpts {
+    let mut opts = XPathOpts::default();
+    for (prefix, uri) in &[("m", "urn:meta"), ("o", "urn:other"), ("x", "urn:extra")] {
+        opts.namespaces.insert(prefix.to_string(), uri.to_string());
+    }
+    opts.variables
+        .insert("min".to_string(), Value::Number(10.0));
+    opts
+}
+
+fn load(name: &str) -> Document {
+    let xml = xtest::load(name).read_xml_file();
+    Document::from(&ezxml::parse_str(xml.as_str()).unwrap())
+}
+
+fn describe(doc: &Document, node: &NodeRef) -> String {
+    match node {
+        NodeRef::Node(id) => match doc.node(*id) {
+            Node::Document => "/".to_string(),
+            Node::Element(element) => match &element.namespace {
+                Some(prefix) => format!("{}:{}", prefix, element.name),
+                None => element.name.to_string(),
+            },
+            Node::String(_) | Node::CData(_) => format!("{:?}", string_value(doc, node)),
+            Node::Comment(comment) => format!("<!--{}-->", comment),
+            Node::ProcessingInstruction(pi) => format!("<?{}?>", pi.target),
+            Node::DocType(_) => "<!DOCTYPE>".to_string(),
+        },
+        NodeRef::Attribute(_, name) => format!("@{}={}", name, string_value(doc, node)),
+        NodeRef::Namespace(_, prefix) => format!("xmlns:{}", prefix),
+    }
+}
+
+#[test]
+fn xpath_expressions() {
+    let opts = opts();
+    for (file, expression, expected) in CASES {
+        let doc = load(file);
+        let value = XPath::parse(expression)
+            .and_then(|xpath| xpath.evaluate(&doc, &opts))
+            .unwrap_or_else(|e| panic!("{}: {}", expression, e));
+        match expected {
+            Nodes(names) => match &value {
+                Value::Nodes(nodes) => {
+                    let found = nodes.iter().map(|n| describe(&doc, n)).collect::<Vec<_>>();
+                    assert_eq!(&found, names, "{}", expression);
+                }
+                other => panic!("{}: expected nodes, found {:?}", expression, other),
+            },
+            Str(s) => assert_eq!(value.string(&doc), *s, "{}", expression),
+            Num(n) => assert_eq!(value, Value::Number(*n), "{}", expression),
+            Bool(b) => assert_eq!(value, Value::Boolean(*b), "{}", expression),
+        }
+    }
+}
+
+#[test]
+fn xpath_errors() {
+    let doc = load("library");
+    let opts = opts();
+    for expression in &[
+        "//q:book",
+        "//book[frob()]",
+        "count()",
+        "count(1)",
+        "$max",
+        "1 | 2",
+    ] {
+        let result = XPath::parse(expression).and_then(|xpath| xpath.evaluate(&doc, &opts));
+        assert!(result.is_err(), "{}", expression);
+    }
+}
@@ -32,8 +32,12 @@ mod tests {
     #[test]
     fn test_self_dir() {
         let dir = self_dir();
-        let components = dir.components();
-        let last_component = components.last().unwrap().as_os_str().to_string_lossy();
+        let mut components = dir.components();
+        let last_component = components
+            .next_back()
+            .unwrap()
+            .as_os_str()
+            .to_string_lossy();
         assert!(last_component.as_ref() == "test-dir");
     }
 }
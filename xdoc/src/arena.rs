@@ -0,0 +1,1048 @@
+//! A document model that keeps every node in one arena and links them by `NodeId`, so that any
+//! node can reach its parent and siblings, and a subtree can be moved by relinking it rather than
+//! copying it. Convert from and to the owned `xdoc::Document` with `Document::from` and
+//! `Document::to_document`.
+//!
+//! A `NodeId` stays valid for the life of the document it came from. Nodes that are detached are
+//! not freed, they can be attached again. Passing a `NodeId` from another document is a bug, and
+//! may panic.
+
+use std::iter::once;
+use std::sync::OnceLock;
+
+use crate::builder::split_name;
+use crate::error::Result;
+use crate::{Declaration, OrdMap, PIData, Symbol};
+
+/// Identifies a node in an arena `Document`.
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialOrd, PartialEq, Hash)]
+pub struct NodeId(usize);
+
+/// An element's name and attributes. Its children are linked in the arena.
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
+pub struct ElementData {
+    pub namespace: Option<Symbol>,
+    pub name: Symbol,
+    pub attributes: OrdMap,
+}
+
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash)]
+pub enum Node {
+    // the parent of the root element and of the prolog and epilog nodes
+    Document,
+
+    // <element>
+    Element(ElementData),
+
+    // normal text data, i.e. 'text &lt;'
+    String(String),
+
+    // <![CDATA[text]]>
+    CData(String),
+
+    // <!-- comment -->
+    Comment(String),
+
+    // <?target data1 data2 data3?>'
+    ProcessingInstruction(PIData),
+
+    // <!DOCTYPE doc> Contents are a blob
+    DocType(String),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Entry {
+    node: Node,
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Document {
+    pub declaration: Declaration,
+    entries: Vec<Entry>,
+    order: Order,
+}
+
+/// The position in document order of each attached node, by `NodeId`, worked out when it is first
+/// needed and forgotten whenever a node is attached or detached. It is not part of the document's
+/// value, so it never makes two documents unequal.
+#[derive(Debug, Clone, Default)]
+struct Order(OnceLock<Vec<usize>>);
+
+impl PartialEq for Order {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Order {}
+
+impl Document {
+    /// The id of the `Node::Document` that every other attached node descends from.
+    pub fn document_node(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// The id of the root element, if it is attached.
+    pub fn root(&self) -> Option<NodeId> {
+        self.children(self.document_node())
+            .find(|&id| matches!(self.node(id), Node::Element(_)))
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.entries[id.0].node
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.entries[id.0].node
+    }
+
+    /// Returns the element data if the node is an element.
+    pub fn element(&self, id: NodeId) -> Option<&ElementData> {
+        match self.node(id) {
+            Node::Element(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.entries[id.0].parent
+    }
+
+    pub fn first_child(&self, id: NodeId) -> Option<NodeId> {
+        self.entries[id.0].first_child
+    }
+
+    pub fn last_child(&self, id: NodeId) -> Option<NodeId> {
+        self.entries[id.0].last_child
+    }
+
+    pub fn previous_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.entries[id.0].previous_sibling
+    }
+
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.entries[id.0].next_sibling
+    }
+
+    /// The node's position in document order, counting from the document node, or `None` if the
+    /// node is not attached.
+    pub(crate) fn document_position(&self, id: NodeId) -> Option<usize> {
+        let order = self.order.0.get_or_init(|| {
+            let mut order = vec![usize::MAX; self.entries.len()];
+            let top = self.document_node();
+            for (position, id) in once(top).chain(self.descendants(top)).enumerate() {
+                order[id.0] = position;
+            }
+            order
+        });
+        order
+            .get(id.0)
+            .copied()
+            .filter(|&position| position != usize::MAX)
+    }
+
+    /// Returns the node's children in document order.
+    pub fn children(&self, id: NodeId) -> Siblings<'_> {
+        Siblings {
+            doc: self,
+            next: self.first_child(id),
+            forward: true,
+        }
+    }
+
+    /// Returns the siblings after the node, nearest first.
+    pub fn following_siblings(&self, id: NodeId) -> Siblings<'_> {
+        Siblings {
+            doc: self,
+            next: self.next_sibling(id),
+            forward: true,
+        }
+    }
+
+    /// Returns the siblings before the node, nearest first.
+    pub fn preceding_siblings(&self, id: NodeId) -> Siblings<'_> {
+        Siblings {
+            doc: self,
+            next: self.previous_sibling(id),
+            forward: false,
+        }
+    }
+
+    /// Returns the node's parent, its parent's parent and so on, ending with the document node if
+    /// the node is attached.
+    pub fn ancestors(&self, id: NodeId) -> Ancestors<'_> {
+        Ancestors {
+            doc: self,
+            next: self.parent(id),
+        }
+    }
+
+    /// Returns every node below the node, in document order.
+    pub fn descendants(&self, id: NodeId) -> Descendants<'_> {
+        Descendants {
+            doc: self,
+            top: id,
+            next: self.first_child(id),
+        }
+    }
+
+    /// Adds a node to the arena without attaching it anywhere.
+    pub fn create(&mut self, node: Node) -> NodeId {
+        self.entries.push(Entry {
+            node,
+            parent: None,
+            first_child: None,
+            last_child: None,
+            previous_sibling: None,
+            next_sibling: None,
+        });
+        NodeId(self.entries.len() - 1)
+    }
+
+    /// Makes `child` the last child of `parent`, moving it and its subtree from wherever it was.
+    pub fn append(&mut self, parent: NodeId, child: NodeId) -> Result<()> {
+        if let Err(e) = self.check_move(parent, child, None, None) {
+            return wrap!(e);
+        }
+        self.detach(child);
+        let previous = self.last_child(parent);
+        self.link(parent, previous, child, None);
+        Ok(())
+    }
+
+    /// Makes `node` the sibling before `sibling`, moving it and its subtree from wherever it was.
+    pub fn insert_before(&mut self, sibling: NodeId, node: NodeId) -> Result<()> {
+        let parent = match self.parent(sibling) {
+            Some(parent) => parent,
+            None => return raise!("Cannot insert a sibling of a node that has no parent."),
+        };
+        if sibling == node {
+            return Ok(());
+        }
+        if let Err(e) = self.check_move(parent, node, Some(sibling), None) {
+            return wrap!(e);
+        }
+        self.detach(node);
+        let previous = self.previous_sibling(sibling);
+        self.link(parent, previous, node, Some(sibling));
+        Ok(())
+    }
+
+    /// Makes `node` the sibling after `sibling`, moving it and its subtree from wherever it was.
+    pub fn insert_after(&mut self, sibling: NodeId, node: NodeId) -> Result<()> {
+        let parent = match self.parent(sibling) {
+            Some(parent) => parent,
+            None => return raise!("Cannot insert a sibling of a node that has no parent."),
+        };
+        if sibling == node {
+            return Ok(());
+        }
+        let next = self.following_siblings(sibling).find(|&id| id != node);
+        if let Err(e) = self.check_move(parent, node, next, None) {
+            return wrap!(e);
+        }
+        self.detach(node);
+        let next = self.next_sibling(sibling);
+        self.link(parent, Some(sibling), node, next);
+        Ok(())
+    }
+
+    /// Unlinks the node, and its subtree, from its parent and siblings. The node can be attached
+    /// again later. The root element cannot be removed, only replaced.
+    pub fn remove(&mut self, id: NodeId) -> Result<()> {
+        if self.is_root(id) {
+            return raise!("The root element cannot be removed.");
+        }
+        self.detach(id);
+        Ok(())
+    }
+
+    /// Puts `new` in the place of `old`, which is detached.
+    pub fn replace_with(&mut self, old: NodeId, new: NodeId) -> Result<()> {
+        let parent = match self.parent(old) {
+            Some(parent) => parent,
+            None => return raise!("Cannot replace a node that has no parent."),
+        };
+        if old == new {
+            return Ok(());
+        }
+        if self.is_root(old) && self.element(new).is_none() {
+            return raise!("The root element can only be replaced by an element.");
+        }
+        let next = self.following_siblings(old).find(|&id| id != new);
+        if let Err(e) = self.check_move(parent, new, next, Some(old)) {
+            return wrap!(e);
+        }
+        self.detach(new);
+        let previous = self.previous_sibling(old);
+        let next = self.next_sibling(old);
+        self.detach(old);
+        self.link(parent, previous, new, next);
+        Ok(())
+    }
+
+    /// Puts a new element in the place of the node and moves the node into it. Returns the id of
+    /// the new element.
+    pub fn wrap(&mut self, id: NodeId, element: ElementData) -> Result<NodeId> {
+        match self.node(id) {
+            Node::Document | Node::DocType(_) => {
+                return raise!("Only the contents of an element can be wrapped.")
+            }
+            _ => {}
+        }
+        let parent = self.parent(id);
+        if parent.is_some_and(|parent| self.is_document_node(parent)) && !self.is_root(id) {
+            return raise!("Wrapping a node outside of the root element would add a second root.");
+        }
+        let wrapper = self.create(Node::Element(element));
+        if parent.is_some() {
+            if let Err(e) = self.replace_with(id, wrapper) {
+                return wrap!(e);
+            }
+        }
+        self.link(wrapper, None, id, None);
+        Ok(wrapper)
+    }
+
+    /// Puts the element's children in its place and detaches it. The root element can only be
+    /// unwrapped if that leaves a single element and no text outside of it.
+    pub fn unwrap(&mut self, id: NodeId) -> Result<()> {
+        if self.element(id).is_none() {
+            return raise!("Only an element can be unwrapped.");
+        }
+        let parent = match self.parent(id) {
+            Some(parent) => parent,
+            None => return raise!("Cannot unwrap a node that has no parent."),
+        };
+        if self.is_root(id) {
+            let mut elements = 0;
+            for child in self.children(id) {
+                match self.node(child) {
+                    Node::Element(_) => elements += 1,
+                    Node::Comment(_) | Node::ProcessingInstruction(_) => {}
+                    _ => return raise!("Unwrapping the root element would leave text outside it."),
+                }
+            }
+            if elements != 1 {
+                return raise!("Unwrapping the root element must leave exactly one element.");
+            }
+        }
+        let next = self.next_sibling(id);
+        self.detach(id);
+        while let Some(child) = self.first_child(id) {
+            self.detach(child);
+            let previous = match next {
+                Some(next) => self.previous_sibling(next),
+                None => self.last_child(parent),
+            };
+            self.link(parent, previous, child, next);
+        }
+        Ok(())
+    }
+
+    /// Changes the element's name, which may have a namespace prefix, e.g. `"x:cat"`.
+    pub fn rename(&mut self, id: NodeId, name: &str) -> Result<()> {
+        let (namespace, local) = match split_name(name) {
+            Ok(split) => split,
+            Err(e) => return wrap!(e),
+        };
+        match self.node_mut(id) {
+            Node::Element(data) => {
+                data.namespace = namespace.map(Symbol::from);
+                data.name = local.into();
+                Ok(())
+            }
+            _ => raise!("Only an element can be renamed."),
+        }
+    }
+
+    /// Sets the attribute with the qualified name `name`, returning its old value.
+    pub fn set_attribute(&mut self, id: NodeId, name: &str, value: &str) -> Result<Option<String>> {
+        if let Err(e) = split_name(name) {
+            return wrap!(e);
+        }
+        match self.node_mut(id) {
+            Node::Element(data) => Ok(data
+                .attributes
+                .mut_map()
+                .insert(name.into(), value.to_string())),
+            _ => raise!("Only an element can have attributes."),
+        }
+    }
+
+    /// Removes the attribute with the qualified name `name`, returning its value.
+    pub fn remove_attribute(&mut self, id: NodeId, name: &str) -> Result<Option<String>> {
+        match self.node_mut(id) {
+            Node::Element(data) => Ok(data.attributes.mut_map().remove(name)),
+            _ => raise!("Only an element can have attributes."),
+        }
+    }
+
+    /// Joins each run of adjacent text nodes below the node into one, and removes empty text
+    /// nodes. CDATA sections are left as they are.
+    pub fn merge_text(&mut self, id: NodeId) {
+        let elements = std::iter::once(id)
+            .chain(self.descendants(id))
+            .filter(|&id| self.element(id).is_some() || self.is_document_node(id))
+            .collect::<Vec<_>>();
+        for element in elements {
+            let mut child = self.first_child(element);
+            while let Some(current) = child {
+                let next = self.next_sibling(current);
+                let text = match self.node(current) {
+                    Node::String(text) => text.clone(),
+                    _ => {
+                        child = next;
+                        continue;
+                    }
+                };
+                if text.is_empty() {
+                    self.detach(current);
+                    child = next;
+                    continue;
+                }
+                let mut merged = text;
+                let mut next = next;
+                while let Some(sibling) = next {
+                    match self.node(sibling) {
+                        Node::String(more) => merged.push_str(more),
+                        _ => break,
+                    }
+                    next = self.next_sibling(sibling);
+                    self.detach(sibling);
+                }
+                *self.node_mut(current) = Node::String(merged);
+                child = next;
+            }
+        }
+    }
+
+    fn is_document_node(&self, id: NodeId) -> bool {
+        matches!(self.node(id), Node::Document)
+    }
+
+    /// Returns `true` if the node is the root element.
+    fn is_root(&self, id: NodeId) -> bool {
+        self.element(id).is_some() && self.parent(id).is_some_and(|p| self.is_document_node(p))
+    }
+
+    fn detach(&mut self, id: NodeId) {
+        let entry = &self.entries[id.0];
+        let (parent, previous, next) = (entry.parent, entry.previous_sibling, entry.next_sibling);
+        let parent = match parent {
+            Some(parent) => parent,
+            None => return,
+        };
+        self.order = Order::default();
+        match previous {
+            Some(previous) => self.entries[previous.0].next_sibling = next,
+            None => self.entries[parent.0].first_child = next,
+        }
+        match next {
+            Some(next) => self.entries[next.0].previous_sibling = previous,
+            None => self.entries[parent.0].last_child = previous,
+        }
+        let entry = &mut self.entries[id.0];
+        entry.parent = None;
+        entry.previous_sibling = None;
+        entry.next_sibling = None;
+    }
+
+    /// Converts to the owned `xdoc::Document`, leaving out any detached nodes. The nodes before the
+    /// root element become the prolog and those after it the epilog.
+    pub fn to_document(&self) -> Result<crate::Document> {
+        let root = match self.root() {
+            Some(root) => root,
+            None => return raise!("The document has no root element."),
+        };
+        let mut doc = crate::Document {
+            declaration: self.declaration.clone(),
+            prolog: Vec::new(),
+            root: Default::default(),
+            epilog: Vec::new(),
+        };
+        let mut is_prolog = true;
+        for id in self.children(self.document_node()) {
+            if id == root {
+                if let crate::Node::Element(data) = self.to_node(id) {
+                    doc.root = data;
+                }
+                is_prolog = false;
+            } else if is_prolog {
+                doc.prolog.push(self.to_node(id));
+            } else {
+                doc.epilog.push(self.to_node(id));
+            }
+        }
+        Ok(doc)
+    }
+
+    /// Converts the node and its subtree to an owned `xdoc::Node`. The document node becomes an
+    /// empty element.
+    pub fn to_node(&self, id: NodeId) -> crate::Node {
+        // the elements being copied, each with its children that are still to be copied, so that
+        // deep documents do not recurse once per level
+        let mut stack: Vec<(crate::ElementData, Siblings<'_>)> = Vec::new();
+        let mut id = id;
+        loop {
+            let mut done = match self.node(id) {
+                Node::Element(data) => {
+                    stack.push((owned_element(data), self.children(id)));
+                    None
+                }
+                node => Some(owned_node(node)),
+            };
+            // go back up until an element has another child to copy
+            loop {
+                if let Some(node) = done.take() {
+                    match stack.last_mut() {
+                        Some((parent, _)) => parent.nodes.push(node),
+                        None => return node,
+                    }
+                }
+                let next = stack.last_mut().and_then(|(_, children)| children.next());
+                match next {
+                    Some(child) => {
+                        id = child;
+                        break;
+                    }
+                    None => {
+                        done = stack
+                            .pop()
+                            .map(|(element, _)| crate::Node::Element(element))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds a copy of the owned node and its subtree to the arena, without attaching it anywhere.
+    pub fn create_from(&mut self, node: &crate::Node) -> NodeId {
+        match node {
+            crate::Node::Element(data) => self.create_from_element(data),
+            node => self.create(arena_node(node)),
+        }
+    }
+
+    /// Adds a copy of the owned element and its subtree to the arena, without attaching it
+    /// anywhere.
+    fn create_from_element(&mut self, data: &crate::ElementData) -> NodeId {
+        let top = self.create(Node::Element(arena_element(data)));
+        // the elements that have been added, each with its children that are still to be added
+        let mut stack = vec![(top, data.nodes.iter())];
+        while let Some((parent, children)) = stack.last_mut() {
+            let parent = *parent;
+            let child = match children.next() {
+                Some(child) => child,
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            };
+            let id = self.create(arena_node(child));
+            let previous = self.last_child(parent);
+            self.link(parent, previous, id, None);
+            if let crate::Node::Element(data) = child {
+                stack.push((id, data.nodes.iter()));
+            }
+        }
+        top
+    }
+
+    /// Returns an error if moving `node` under `parent`, just before `next` or else at the end,
+    /// would not leave a well-formed document. The `replaced` node, if any, is about to be
+    /// detached, and is not counted among the siblings.
+    fn check_move(
+        &self,
+        parent: NodeId,
+        node: NodeId,
+        next: Option<NodeId>,
+        replaced: Option<NodeId>,
+    ) -> Result<()> {
+        if self.is_document_node(node) {
+            return raise!("The document node cannot be moved.");
+        }
+        if parent == node || self.ancestors(parent).any(|id| id == node) {
+            return raise!("A node cannot be moved into its own subtree.");
+        }
+        let is_sibling = |id: &NodeId| *id != node && Some(*id) != replaced;
+        let is_doc_type = |id: &NodeId| matches!(self.node(*id), Node::DocType(_));
+        let is_element = |id: &NodeId| self.element(*id).is_some();
+        // the siblings that will follow the node
+        let following = || {
+            Siblings {
+                doc: self,
+                next,
+                forward: true,
+            }
+            .filter(is_sibling)
+        };
+        match (self.node(parent), self.node(node)) {
+            (Node::Document, Node::Element(_)) => {
+                if self
+                    .children(parent)
+                    .filter(is_sibling)
+                    .any(|id| is_element(&id))
+                {
+                    return raise!("The document already has a root element.");
+                }
+                if following().any(|id| is_doc_type(&id)) {
+                    return raise!("The root element must come after the DOCTYPE.");
+                }
+            }
+            (Node::Document, Node::DocType(_)) => {
+                if self
+                    .children(parent)
+                    .filter(is_sibling)
+                    .any(|id| is_doc_type(&id))
+                {
+                    return raise!("The document already has a DOCTYPE.");
+                }
+                let has_root = self
+                    .children(parent)
+                    .filter(is_sibling)
+                    .any(|id| is_element(&id));
+                if has_root && !following().any(|id| is_element(&id)) {
+                    return raise!("The DOCTYPE must come before the root element.");
+                }
+            }
+            (Node::Document, Node::Comment(_))
+            | (Node::Document, Node::ProcessingInstruction(_)) => {}
+            (Node::Document, _) => return raise!("Text must be inside the root element."),
+            (Node::Element(_), Node::DocType(_)) => {
+                return raise!("The DOCTYPE must be outside the root element.")
+            }
+            (Node::Element(_), _) => {}
+            _ => return raise!("Only the document and elements can have children."),
+        }
+        Ok(())
+    }
+
+    /// Links the detached `node` under `parent`, between `previous` and `next`.
+    fn link(
+        &mut self,
+        parent: NodeId,
+        previous: Option<NodeId>,
+        node: NodeId,
+        next: Option<NodeId>,
+    ) {
+        self.order = Order::default();
+        let entry = &mut self.entries[node.0];
+        entry.parent = Some(parent);
+        entry.previous_sibling = previous;
+        entry.next_sibling = next;
+        match previous {
+            Some(previous) => self.entries[previous.0].next_sibling = Some(node),
+            None => self.entries[parent.0].first_child = Some(node),
+        }
+        match next {
+            Some(next) => self.entries[next.0].previous_sibling = Some(node),
+            None => self.entries[parent.0].last_child = Some(node),
+        }
+    }
+}
+
+impl From<&crate::Document> for Document {
+    fn from(doc: &crate::Document) -> Self {
+        let mut arena = Document {
+            declaration: doc.declaration.clone(),
+            entries: Vec::new(),
+            order: Order::default(),
+        };
+        let document_node = arena.create(Node::Document);
+        let mut ids = Vec::with_capacity(doc.prolog.len() + 1 + doc.epilog.len());
+        ids.extend(doc.prolog.iter().map(|node| arena.create_from(node)));
+        ids.push(arena.create_from_element(&doc.root));
+        ids.extend(doc.epilog.iter().map(|node| arena.create_from(node)));
+        for id in ids {
+            let previous = arena.last_child(document_node);
+            arena.link(document_node, previous, id, None);
+        }
+        arena
+    }
+}
+
+/// Copies the name and attributes of an owned element.
+fn arena_element(data: &crate::ElementData) -> ElementData {
+    ElementData {
+        namespace: data.namespace.clone(),
+        name: data.name.clone(),
+        attributes: data.attributes.clone(),
+    }
+}
+
+/// Copies an owned node, leaving out the children of an element.
+fn arena_node(node: &crate::Node) -> Node {
+    match node {
+        crate::Node::Element(data) => Node::Element(arena_element(data)),
+        crate::Node::String(s) => Node::String(s.clone()),
+        crate::Node::CData(s) => Node::CData(s.clone()),
+        crate::Node::Comment(s) => Node::Comment(s.clone()),
+        crate::Node::ProcessingInstruction(pi) => Node::ProcessingInstruction(pi.clone()),
+        crate::Node::DocType(s) => Node::DocType(s.clone()),
+    }
+}
+
+/// Copies an element's name and attributes to an owned element with no children.
+fn owned_element(data: &ElementData) -> crate::ElementData {
+    crate::ElementData {
+        namespace: data.namespace.clone(),
+        name: data.name.clone(),
+        attributes: data.attributes.clone(),
+        nodes: Vec::new(),
+    }
+}
+
+/// Copies a node to an owned node, leaving out the children of an element. The document node
+/// becomes an empty element.
+fn owned_node(node: &Node) -> crate::Node {
+    match node {
+        Node::Document => crate::Node::Element(crate::ElementData::default()),
+        Node::Element(data) => crate::Node::Element(owned_element(data)),
+        Node::String(s) => crate::Node::String(s.clone()),
+        Node::CData(s) => crate::Node::CData(s.clone()),
+        Node::Comment(s) => crate::Node::Comment(s.clone()),
+        Node::ProcessingInstruction(pi) => crate::Node::ProcessingInstruction(pi.clone()),
+        Node::DocType(s) => crate::Node::DocType(s.clone()),
+    }
+}
+
+/// An iterator over a run of siblings, see `Document::children`.
+#[derive(Debug, Clone)]
+pub struct Siblings<'a> {
+    doc: &'a Document,
+    next: Option<NodeId>,
+    forward: bool,
+}
+
+impl Iterator for Siblings<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next?;
+        self.next = if self.forward {
+            self.doc.next_sibling(id)
+        } else {
+            self.doc.previous_sibling(id)
+        };
+        Some(id)
+    }
+}
+
+/// An iterator over a node's ancestors, see `Document::ancestors`.
+#[derive(Debug, Clone)]
+pub struct Ancestors<'a> {
+    doc: &'a Document,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Ancestors<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next?;
+        self.next = self.doc.parent(id);
+        Some(id)
+    }
+}
+
+/// An iterator over the nodes below a node, see `Document::descendants`.
+#[derive(Debug, Clone)]
+pub struct Descendants<'a> {
+    doc: &'a Document,
+    top: NodeId,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next?;
+        // the first child, or else the next sibling of the nearest node that has one, without
+        // climbing above the top
+        self.next = self.doc.first_child(id).or_else(|| {
+            let mut current = id;
+            loop {
+                if current == self.top {
+                    return None;
+                }
+                if let Some(next) = self.doc.next_sibling(current) {
+                    return Some(next);
+                }
+                current = self.doc.parent(current)?;
+            }
+        });
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml;
+
+    fn ezfile() -> crate::Document {
+        let root = xml! {
+            cats {
+                cat(name = "bones"),
+                cat(name = "bishop") { "punks", @comment(" and ") },
+                cat(name = "jack"),
+            }
+        };
+        let mut doc = crate::Document::from_root(root.unwrap());
+        doc.prolog
+            .push(crate::Node::Comment(" prolog ".to_string()));
+        doc.epilog
+            .push(crate::Node::Comment(" epilog ".to_string()));
+        doc
+    }
+
+    fn name(doc: &Document, id: NodeId) -> String {
+        match doc.node(id) {
+            Node::Element(data) => data
+                .attributes
+                .map()
+                .get("name")
+                .cloned()
+                .unwrap_or_else(|| "#".to_string()),
+            Node::String(s) | Node::Comment(s) => s.clone(),
+            _ => "#".to_string(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let doc = ezfile();
+        let arena = Document::from(&doc);
+        assert_eq!(arena.to_document().unwrap(), doc);
+    }
+
+    #[test]
+    fn deep_documents_do_not_recurse() {
+        let depth = 200_000;
+        let a = || crate::ElementData {
+            name: "a".into(),
+            ..crate::ElementData::default()
+        };
+        let mut root = a();
+        for _ in 1..depth {
+            let mut parent = a();
+            parent.nodes.push(crate::Node::Element(root));
+            root = parent;
+        }
+        // dropping a deep owned tree recurses too, so it is taken apart a level at a time
+        fn take_apart(mut element: crate::ElementData) -> usize {
+            let mut levels = 1;
+            while let Some(crate::Node::Element(child)) = element.nodes.pop() {
+                element = child;
+                levels += 1;
+            }
+            levels
+        }
+        let handle = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(move || {
+                let doc = crate::Document::from_root(root);
+                let arena = Document::from(&doc);
+                let copy = arena.to_document().unwrap();
+                (take_apart(doc.root), take_apart(copy.root))
+            })
+            .unwrap();
+        assert_eq!(handle.join().unwrap(), (depth, depth));
+    }
+
+    #[test]
+    fn navigation() {
+        let arena = Document::from(&ezfile());
+        let root = arena.root().unwrap();
+        assert_eq!(arena.parent(root), Some(arena.document_node()));
+        let cats = arena.children(root).collect::<Vec<_>>();
+        assert_eq!(cats.len(), 3);
+        let bishop = cats[1];
+        assert_eq!(arena.previous_sibling(bishop), Some(cats[0]));
+        assert_eq!(arena.next_sibling(bishop), Some(cats[2]));
+        assert_eq!(arena.following_siblings(cats[0]).count(), 2);
+        assert_eq!(arena.preceding_siblings(cats[2]).next(), Some(bishop));
+        let punks = arena.first_child(bishop).unwrap();
+        assert_eq!(
+            arena.ancestors(punks).collect::<Vec<_>>(),
+            vec![bishop, root, arena.document_node()]
+        );
+        let names = arena
+            .descendants(arena.document_node())
+            .map(|id| name(&arena, id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![" prolog ", "#", "bones", "bishop", "punks", " and ", "jack", " epilog "]
+        );
+        assert_eq!(arena.descendants(bishop).count(), 2);
+        assert_eq!(arena.descendants(cats[2]).count(), 0);
+    }
+
+    #[test]
+    fn moving_subtrees() {
+        let mut arena = Document::from(&ezfile());
+        let root = arena.root().unwrap();
+        let cats = arena.children(root).collect::<Vec<_>>();
+        // move bishop, with its children, into jack
+        arena.append(cats[2], cats[1]).unwrap();
+        assert_eq!(arena.parent(cats[1]), Some(cats[2]));
+        assert_eq!(arena.next_sibling(cats[0]), Some(cats[2]));
+        arena.insert_before(cats[0], cats[1]).unwrap();
+        arena.insert_after(cats[0], cats[2]).unwrap();
+        let names = arena
+            .children(root)
+            .map(|id| name(&arena, id))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["bishop", "bones", "jack"]);
+        assert_eq!(arena.descendants(cats[1]).count(), 2);
+
+        assert!(arena.append(cats[1], root).is_err());
+        assert!(arena.append(cats[1], cats[1]).is_err());
+        let document_node = arena.document_node();
+        assert!(arena.append(cats[1], document_node).is_err());
+
+        arena.remove(cats[0]).unwrap();
+        assert_eq!(arena.children(root).count(), 2);
+        assert_eq!(arena.parent(cats[0]), None);
+        let doc = arena.to_document().unwrap();
+        assert_eq!(doc.root.nodes.len(), 2);
+    }
+
+    fn element(name: &str) -> Node {
+        Node::Element(ElementData {
+            name: name.into(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn remove_replace_wrap_unwrap() {
+        let mut arena = Document::from(&ezfile());
+        let root = arena.root().unwrap();
+        let cats = arena.children(root).collect::<Vec<_>>();
+        assert!(arena.remove(root).is_err());
+
+        let dog = arena.create(element("dog"));
+        arena.replace_with(cats[0], dog).unwrap();
+        assert_eq!(arena.parent(cats[0]), None);
+        assert_eq!(arena.first_child(root), Some(dog));
+
+        let pets = arena
+            .wrap(
+                cats[2],
+                ElementData {
+                    name: "pets".into(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(arena.parent(cats[2]), Some(pets));
+        assert_eq!(arena.next_sibling(cats[1]), Some(pets));
+        arena.unwrap(pets).unwrap();
+        assert_eq!(arena.next_sibling(cats[1]), Some(cats[2]));
+        assert_eq!(arena.parent(pets), None);
+
+        // bishop has text, so the root cannot be unwrapped into it, but it can be wrapped
+        arena.unwrap(cats[1]).unwrap();
+        assert!(arena.unwrap(root).is_err());
+        let document = arena
+            .wrap(
+                root,
+                ElementData {
+                    name: "document".into(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(arena.root(), Some(document));
+        arena.unwrap(document).unwrap();
+        assert_eq!(arena.root(), Some(root));
+        let names = arena
+            .children(root)
+            .map(|id| name(&arena, id))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["#", "punks", " and ", "jack"]);
+    }
+
+    #[test]
+    fn the_document_stays_well_formed() {
+        let mut arena = Document::from(&ezfile());
+        let document_node = arena.document_node();
+        let root = arena.root().unwrap();
+        let prolog = arena.first_child(document_node).unwrap();
+        let cat = arena.first_child(root).unwrap();
+
+        // a second root
+        assert!(arena.append(document_node, cat).is_err());
+        assert!(arena.insert_before(prolog, cat).is_err());
+        assert!(arena.wrap(prolog, ElementData::default()).is_err());
+        let comment = arena.create(Node::Comment("c".to_string()));
+        assert!(arena.replace_with(root, comment).is_err());
+        let text = arena.create(Node::String("t".to_string()));
+        assert!(arena.append(document_node, text).is_err());
+        assert!(arena.append(text, comment).is_err());
+
+        // a DOCTYPE must come before the root, and only once
+        let doc_type = arena.create(Node::DocType("cats".to_string()));
+        assert!(arena.append(document_node, doc_type).is_err());
+        assert!(arena.append(root, doc_type).is_err());
+        arena.insert_after(prolog, doc_type).unwrap();
+        let other = arena.create(Node::DocType("dogs".to_string()));
+        assert!(arena.insert_before(prolog, other).is_err());
+        assert!(arena.insert_before(prolog, root).is_err());
+
+        // the same checks allow moving the root within the document node
+        let epilog = arena.last_child(document_node).unwrap();
+        arena.insert_after(epilog, prolog).unwrap();
+        assert_eq!(arena.first_child(document_node), Some(doc_type));
+    }
+
+    #[test]
+    fn rename_attributes_and_text() {
+        let mut arena = Document::from(&ezfile());
+        let root = arena.root().unwrap();
+        let bishop = arena.children(root).nth(1).unwrap();
+        arena.rename(bishop, "x:kitten").unwrap();
+        assert!(arena.rename(bishop, "x:").is_err());
+        let punks = arena.first_child(bishop).unwrap();
+        assert!(arena.rename(punks, "text").is_err());
+        let data = arena.element(bishop).unwrap();
+        assert_eq!(data.namespace.as_deref(), Some("x"));
+        assert_eq!(data.name, "kitten");
+
+        assert_eq!(
+            arena.set_attribute(bishop, "name", "punk").unwrap(),
+            Some("bishop".to_string())
+        );
+        assert_eq!(arena.set_attribute(bishop, "age", "3").unwrap(), None);
+        assert!(arena.set_attribute(bishop, "1", "3").is_err());
+        assert_eq!(
+            arena.remove_attribute(bishop, "age").unwrap(),
+            Some("3".to_string())
+        );
+        assert!(arena.set_attribute(punks, "a", "b").is_err());
+
+        for text in &["", " rock", "", " on"] {
+            let id = arena.create(Node::String(text.to_string()));
+            arena.insert_after(punks, id).unwrap();
+        }
+        let comment = arena.last_child(bishop).unwrap();
+        let empty = arena.create(Node::String(String::new()));
+        arena.append(bishop, empty).unwrap();
+        arena.merge_text(root);
+        let texts = arena
+            .children(bishop)
+            .map(|id| name(&arena, id))
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec!["punks on rock", " and "]);
+        assert_eq!(arena.last_child(bishop), Some(comment));
+    }
+}
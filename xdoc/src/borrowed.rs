@@ -0,0 +1,174 @@
+//! A read-only document model that borrows its names and text from the buffer it was parsed from.
+//! Strings are only owned where decoding an entity reference changed the text. Use `into_owned`
+//! to convert to the owned `xdoc::Document`.
+
+use std::borrow::Cow;
+
+use crate::{Declaration, OrdMap, PIData};
+
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub struct Document<'a> {
+    pub declaration: Declaration,
+    /// The comments, processing instructions and DOCTYPE before the root, in document order.
+    pub prolog: Vec<Node<'a>>,
+    pub root: ElementData<'a>,
+    /// The comments and processing instructions after the root, in document order.
+    pub epilog: Vec<Node<'a>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub struct Attribute<'a> {
+    pub key: Cow<'a, str>,
+    pub value: Cow<'a, str>,
+}
+
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub struct ElementData<'a> {
+    pub namespace: Option<Cow<'a, str>>,
+    pub name: Cow<'a, str>,
+    /// Attributes in the order they appeared in the start tag.
+    pub attributes: Vec<Attribute<'a>>,
+    pub nodes: Vec<Node<'a>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Node<'a> {
+    // <element>
+    Element(ElementData<'a>),
+
+    // normal text data, i.e. 'text &lt;'
+    String(Cow<'a, str>),
+
+    // <![CDATA[text]]>
+    CData(Cow<'a, str>),
+
+    // <!-- comment -->
+    Comment(Cow<'a, str>),
+
+    // <?target data1 data2 data3?>'
+    ProcessingInstruction(PIData),
+
+    // <!DOCTYPE doc> Contents are a blob
+    DocType(Cow<'a, str>),
+}
+
+impl<'a> Document<'a> {
+    pub fn root(&self) -> &ElementData<'a> {
+        &self.root
+    }
+
+    /// Copies any borrowed strings to produce an owned `xdoc::Document`.
+    pub fn into_owned(self) -> crate::Document {
+        crate::Document {
+            declaration: self.declaration,
+            prolog: self.prolog.into_iter().map(Node::into_owned).collect(),
+            root: self.root.into_owned(),
+            epilog: self.epilog.into_iter().map(Node::into_owned).collect(),
+        }
+    }
+}
+
+impl<'a> ElementData<'a> {
+    pub fn fullname(&self) -> Cow<'_, str> {
+        match &self.namespace {
+            Some(ns) if !ns.is_empty() => Cow::Owned(format!("{}:{}", ns, self.name)),
+            _ => Cow::Borrowed(self.name.as_ref()),
+        }
+    }
+
+    /// Returns the value of the attribute named `key`.
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|a| a.key == key)
+            .map(|a| a.value.as_ref())
+    }
+
+    /// Copies any borrowed strings to produce an owned `xdoc::ElementData`.
+    pub fn into_owned(self) -> crate::ElementData {
+        let mut attributes = OrdMap::new();
+        for a in self.attributes {
+            attributes
+                .mut_map()
+                .insert(a.key.as_ref().into(), a.value.into_owned());
+        }
+        crate::ElementData {
+            namespace: self.namespace.map(|ns| ns.as_ref().into()),
+            name: self.name.as_ref().into(),
+            attributes,
+            nodes: self.nodes.into_iter().map(Node::into_owned).collect(),
+        }
+    }
+}
+
+impl<'a> Node<'a> {
+    /// Copies any borrowed strings to produce an owned `xdoc::Node`.
+    pub fn into_owned(self) -> crate::Node {
+        match self {
+            Node::Element(data) => crate::Node::Element(data.into_owned()),
+            Node::String(s) => crate::Node::String(s.into_owned()),
+            Node::CData(s) => crate::Node::CData(s.into_owned()),
+            Node::Comment(s) => crate::Node::Comment(s.into_owned()),
+            Node::ProcessingInstruction(pi) => crate::Node::ProcessingInstruction(pi),
+            Node::DocType(s) => crate::Node::DocType(s.into_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn into_owned() {
+        let text = String::from("punks");
+        let doc = Document {
+            declaration: Default::default(),
+            prolog: vec![Node::Comment(Cow::Borrowed("license"))],
+            root: ElementData {
+                namespace: Some(Cow::Borrowed("x")),
+                name: Cow::Borrowed("cat"),
+                attributes: vec![Attribute {
+                    key: Cow::Borrowed("name"),
+                    value: Cow::Owned("bishop".to_string()),
+                }],
+                nodes: vec![Node::String(Cow::Borrowed(text.as_str()))],
+            },
+            epilog: vec![],
+        };
+        assert_eq!(doc.root().fullname(), "x:cat");
+        assert_eq!(doc.root().attribute("name"), Some("bishop"));
+        let owned = doc.into_owned();
+        drop(text);
+        assert_eq!(owned.root.fullname(), "x:cat");
+        assert_eq!(owned.root.attributes.map().get("name").unwrap(), "bishop");
+        assert_eq!(
+            owned.prolog,
+            vec![crate::Node::Comment("license".to_string())]
+        );
+        assert_eq!(
+            owned.root.nodes,
+            vec![crate::Node::String("punks".to_string())]
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use crate::chars::is_name;
+use crate::error::{Result, XErr};
+use crate::{ElementData, Node, Symbol};
+
+/// Builds an `ElementData` with chained calls. Names are checked as they are given, and the first
+/// problem, including one in a child, is returned by `build`.
+///
+/// ```
+/// use xdoc::ElementBuilder;
+///
+/// let cats = ElementBuilder::new("cats")
+///     .child(ElementBuilder::new("cat").attr("name", "bones"))
+///     .child(ElementBuilder::new("cat").attr("name", "bishop").text("punks"))
+///     .comment(" more to come ")
+///     .build()
+///     .unwrap();
+/// assert_eq!(cats.children_elements().count(), 2);
+/// assert_eq!(cats.inner_text(), "punks");
+///
+/// assert!(ElementBuilder::new("1cat").build().is_err());
+/// ```
+#[derive(Debug)]
+pub struct ElementBuilder {
+    element: ElementData,
+    error: Option<XErr>,
+}
+
+impl ElementBuilder {
+    /// Begins an element named `name`, which may have a namespace prefix, e.g. `"x:cat"`.
+    pub fn new(name: &str) -> Self {
+        let mut builder = ElementBuilder {
+            element: ElementData::default(),
+            error: None,
+        };
+        match split_name(name) {
+            Ok((namespace, local)) => {
+                builder.element.namespace = namespace.map(Symbol::from);
+                builder.element.name = local.into();
+            }
+            Err(e) => builder.error = Some(e),
+        }
+        builder
+    }
+
+    pub fn attr<S: Into<String>>(mut self, name: &str, value: S) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        match self.check_attribute(name) {
+            Ok(()) => {
+                self.element
+                    .attributes
+                    .mut_map()
+                    .insert(name.into(), value.into());
+            }
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+
+    pub fn child(mut self, child: ElementBuilder) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        match child.build() {
+            Ok(data) => self.element.nodes.push(Node::Element(data)),
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+
+    /// Adds any kind of node, for example a processing instruction.
+    pub fn node(mut self, node: Node) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        match node.check() {
+            Ok(()) => self.element.nodes.push(node),
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+
+    pub fn text<S: Into<String>>(self, text: S) -> Self {
+        self.node(Node::String(text.into()))
+    }
+
+    pub fn cdata<S: Into<String>>(self, text: S) -> Self {
+        self.node(Node::CData(text.into()))
+    }
+
+    pub fn comment<S: Into<String>>(self, comment: S) -> Self {
+        self.node(Node::Comment(comment.into()))
+    }
+
+    fn check_attribute(&self, name: &str) -> Result<()> {
+        if let Err(e) = split_name(name) {
+            return wrap!(e);
+        }
+        if self.element.attributes.map().contains_key(name) {
+            return raise!("Duplicate attribute '{}'.", name);
+        }
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<ElementData> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.element),
+        }
+    }
+}
+
+/// Splits a qualified name into its namespace prefix, if any, and its local name, and checks that
+/// both are valid.
+pub(crate) fn split_name(name: &str) -> Result<(Option<&str>, &str)> {
+    let (namespace, local) = match name.split_once(':') {
+        Some((namespace, local)) => (Some(namespace), local),
+        None => (None, name),
+    };
+    let is_valid = |s: &str| is_name(s) && !s.contains(':');
+    if !is_valid(local) || !namespace.is_none_or(is_valid) {
+        return raise!("Invalid name '{}'.", name);
+    }
+    Ok((namespace, local))
+}
+
+/// Builds an `ElementData` from a declarative description, returning `xdoc::error::Result`.
+///
+/// An element is its name, then optionally its attributes in parentheses, then optionally its
+/// children in braces. Names that are not Rust identifiers, for example those with a `-` or a
+/// namespace prefix, are written as expressions in brackets: `["x:cat"]`. Children are separated
+/// by commas, and are elements, string literals for text, or `@text(expr)`, `@cdata(expr)`,
+/// `@comment(expr)` and `@node(expr)`.
+///
+/// ```
+/// use xdoc::xml;
+///
+/// let bishop = "bishop";
+/// let cats = xml! {
+///     cats("xmlns:x" = "urn:cats") {
+///         cat(name = "bones"),
+///         cat(name = bishop, age = 3.to_string()) { "punks" },
+///         ["x:cat"] { @comment(" unnamed "), @cdata("<meow>") },
+///     }
+/// }
+/// .unwrap();
+/// assert_eq!(cats.children_elements().count(), 3);
+/// assert_eq!(cats.first_child_named("x:cat").unwrap().text(), "<meow>");
+/// ```
+#[macro_export]
+macro_rules! xml {
+    ($($element:tt)+) => {
+        $crate::__xml_element!($($element)+).build()
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __xml_element {
+    ($name:ident $($rest:tt)*) => {
+        $crate::__xml_element!(@attrs $crate::ElementBuilder::new(stringify!($name)); $($rest)*)
+    };
+    ([$name:expr] $($rest:tt)*) => {
+        $crate::__xml_element!(@attrs $crate::ElementBuilder::new($name); $($rest)*)
+    };
+    (@attrs $builder:expr; ($($key:tt = $value:expr),* $(,)?) $($rest:tt)*) => {
+        $crate::__xml_element!(
+            @children $builder$(.attr($crate::__xml_name!($key), $value))*; $($rest)*
+        )
+    };
+    (@attrs $builder:expr; $($rest:tt)*) => {
+        $crate::__xml_element!(@children $builder; $($rest)*)
+    };
+    (@children $builder:expr; {$($children:tt)*}) => {
+        $crate::__xml_children!($builder; $($children)*)
+    };
+    (@children $builder:expr;) => {
+        $builder
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __xml_children {
+    ($builder:expr;) => {
+        $builder
+    };
+    ($builder:expr; $text:literal $(, $($rest:tt)*)?) => {
+        $crate::__xml_children!($builder.text($text); $($($rest)*)?)
+    };
+    ($builder:expr; @text($text:expr) $(, $($rest:tt)*)?) => {
+        $crate::__xml_children!($builder.text($text); $($($rest)*)?)
+    };
+    ($builder:expr; @cdata($text:expr) $(, $($rest:tt)*)?) => {
+        $crate::__xml_children!($builder.cdata($text); $($($rest)*)?)
+    };
+    ($builder:expr; @comment($comment:expr) $(, $($rest:tt)*)?) => {
+        $crate::__xml_children!($builder.comment($comment); $($($rest)*)?)
+    };
+    ($builder:expr; @node($node:expr) $(, $($rest:tt)*)?) => {
+        $crate::__xml_children!($builder.node($node); $($($rest)*)?)
+    };
+    ($builder:expr; $name:ident $(($($attrs:tt)*))? $({$($children:tt)*})? $(, $($rest:tt)*)?) => {
+        $crate::__xml_children!(
+            $builder.child($crate::__xml_element!(
+                $name $(($($attrs)*))? $({$($children)*})?
+            ));
+            $($($rest)*)?
+        )
+    };
+    ($builder:expr; [$name:expr] $(($($attrs:tt)*))? $({$($children:tt)*})? $(, $($rest:tt)*)?) => {
+        $crate::__xml_children!(
+            $builder.child($crate::__xml_element!(
+                [$name] $(($($attrs)*))? $({$($children)*})?
+            ));
+            $($($rest)*)?
+        )
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __xml_name {
+    ($name:ident) => {
+        stringify!($name)
+    };
+    ($name:literal) => {
+        $name
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map, OrdMap, PIData};
+
+    #[test]
+    fn builder_and_macro_agree() {
+        let pi = Node::ProcessingInstruction(PIData {
+            target: "cat".to_string(),
+            instructions: OrdMap::from(map! { "a".to_string() => "1".to_string() }),
+        });
+        let built = ElementBuilder::new("x:cats")
+            .attr("xml:space", "preserve")
+            .child(ElementBuilder::new("cat-1").attr("name", "bones"))
+            .text("text")
+            .cdata("cdata")
+            .comment("comment")
+            .node(pi.clone())
+            .build()
+            .unwrap();
+        let from_macro = xml! {
+            ["x:cats"]("xml:space" = "preserve") {
+                ["cat-1"](name = "bones",),
+                "text",
+                @cdata("cdata"),
+                @comment(String::from("comment")),
+                @node(pi),
+            }
+        }
+        .unwrap();
+        assert_eq!(built, from_macro);
+        assert_eq!(built.namespace.as_deref(), Some("x"));
+        assert_eq!(built.name, "cats");
+        assert_eq!(built.nodes.len(), 5);
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        assert!(xml! { ["cat dog"] }.is_err());
+        assert!(xml! { ["x:"] }.is_err());
+        assert!(xml! { ["x:y:z"] }.is_err());
+        assert!(xml! { cats { cat("1" = "x") } }.is_err());
+        assert!(xml! { cats(a = "1", a = "2") }.is_err());
+        assert!(xml! { cats { @comment("--") } }.is_err());
+        assert!(xml! { cats { cats { cats { [""] } } } }.is_err());
+    }
+}
@@ -0,0 +1,645 @@
+//! Canonical XML: a byte-exact form of a document, or of one of its subtrees, for signing and
+//! hashing. Both Canonical XML 1.1 (`Method::Inclusive`) and Exclusive XML Canonicalization 1.0
+//! (`Method::Exclusive`) are supported, with or without comments.
+//!
+//! Attributes are sorted by namespace URI and local name, namespace declarations are written only
+//! where they are needed, and text is escaped as the specifications require. Unlike
+//! `Format::Canonical`, this resolves namespace prefixes, so every prefix used must be declared.
+//!
+//! The canonical form is taken of the document as xdoc holds it. Anything the parser did not keep
+//! cannot be recovered: DTD attribute defaults and entity declarations are not applied, and the
+//! data of a processing instruction is written as its sorted pseudo-attributes. To keep whitespace
+//! between elements, parse with `ezxml::ParseOpts::with_preserve_whitespace`.
+//!
+//! ```
+//! use xdoc::c14n::{canonicalize, C14nOpts};
+//! use xdoc::{xml, Document};
+//!
+//! let root = xml! {
+//!     ["x:cats"]("xmlns:x" = "urn:x", name = "all", "x:kind" = "house") {
+//!         ["x:cat"](name = "bones"),
+//!     }
+//! };
+//! let doc = Document::from_root(root.unwrap());
+//! assert_eq!(
+//!     canonicalize(&doc, &C14nOpts::default()).unwrap(),
+//!     r#"<x:cats xmlns:x="urn:x" name="all" x:kind="house"><x:cat name="bones"></x:cat></x:cats>"#
+//! );
+//! ```
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::arena::{self, NodeId};
+use crate::error::Result;
+use crate::escape::{escape_attribute, escape_text};
+use crate::xpath::XML_NAMESPACE;
+use crate::{Document, Format, WriteOpts};
+
+/// Which canonicalization algorithm to use.
+#[derive(Debug, Clone, Copy, Eq, PartialOrd, PartialEq, Hash, Default)]
+pub enum Method {
+    /// Canonical XML 1.1. A subtree gets every namespace in scope where it is, and inherits the
+    /// `xml:lang`, `xml:space` and `xml:base` of its ancestors.
+    #[default]
+    Inclusive,
+    /// Exclusive XML Canonicalization 1.0. A namespace is only declared on the elements that use
+    /// it, so a subtree is canonicalized the same wherever it is placed.
+    Exclusive,
+}
+
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
+pub struct C14nOpts {
+    pub method: Method,
+    pub with_comments: bool,
+    /// The InclusiveNamespaces PrefixList of Exclusive XML Canonicalization: the prefixes that are
+    /// declared as `Method::Inclusive` would declare them, with `#default` for the default
+    /// namespace. It is ignored by `Method::Inclusive`.
+    pub inclusive_prefixes: Vec<String>,
+}
+
+impl C14nOpts {
+    /// The identifier of the algorithm, as used by XML Signature.
+    pub fn algorithm(&self) -> &'static str {
+        match (self.method, self.with_comments) {
+            (Method::Inclusive, false) => "http://www.w3.org/2006/12/xml-c14n11",
+            (Method::Inclusive, true) => "http://www.w3.org/2006/12/xml-c14n11#WithComments",
+            (Method::Exclusive, false) => "http://www.w3.org/2001/10/xml-exc-c14n#",
+            (Method::Exclusive, true) => "http://www.w3.org/2001/10/xml-exc-c14n#WithComments",
+        }
+    }
+}
+
+/// Returns the canonical form of the whole document.
+pub fn canonicalize(doc: &Document, opts: &C14nOpts) -> Result<String> {
+    let arena = arena::Document::from(doc);
+    canonicalize_node(&arena, arena.document_node(), opts)
+}
+
+/// Returns the canonical form of the node and its subtree.
+pub fn canonicalize_node(doc: &arena::Document, id: NodeId, opts: &C14nOpts) -> Result<String> {
+    let mut bytes = Vec::new();
+    write(doc, id, &mut bytes, opts)?;
+    match String::from_utf8(bytes) {
+        Ok(s) => Ok(s),
+        Err(e) => wrap!(e),
+    }
+}
+
+/// Writes the canonical form of the node and its subtree, which is UTF-8. The whole document is
+/// written by passing `doc.document_node()`.
+pub fn write<W>(doc: &arena::Document, id: NodeId, writer: &mut W, opts: &C14nOpts) -> Result<()>
+where
+    W: Write,
+{
+    let mut canonicalizer = Canonicalizer { doc, opts, writer };
+    match doc.node(id) {
+        arena::Node::Document => canonicalizer.document(id),
+        arena::Node::Element(_) => {
+            // the namespaces and xml attributes that the subtree gets from outside of it
+            let mut ancestors = doc.ancestors(id).collect::<Vec<_>>();
+            ancestors.reverse();
+            let mut scope = Namespaces::new();
+            for &ancestor in &ancestors {
+                if let Some(element) = doc.element(ancestor) {
+                    scope = enter(&scope, element);
+                }
+            }
+            let inherited = match opts.method {
+                Method::Inclusive => inherited_attributes(doc, &ancestors),
+                Method::Exclusive => Vec::new(),
+            };
+            canonicalizer.element(id, &scope, &Namespaces::new(), &inherited)
+        }
+        _ => canonicalizer.leaf(id),
+    }
+}
+
+/// Namespace URIs by prefix, with "" for the default namespace. An empty URI undeclares the
+/// default namespace.
+type Namespaces = HashMap<String, String>;
+
+/// Returns the namespaces in scope on the element, given those in scope on its parent.
+fn enter(parent: &Namespaces, element: &arena::ElementData) -> Namespaces {
+    let mut scope = parent.clone();
+    for (name, uri) in element.attributes.map() {
+        if name.as_str() == "xmlns" {
+            scope.insert(String::new(), uri.clone());
+        } else if let Some(prefix) = name.strip_prefix("xmlns:") {
+            // XML 1.1 can undeclare a prefix
+            if uri.is_empty() {
+                scope.remove(prefix);
+            } else {
+                scope.insert(prefix.to_string(), uri.clone());
+            }
+        }
+    }
+    scope
+}
+
+/// The `xml:lang`, `xml:space` and `xml:base` attributes that the apex of a subtree inherits from
+/// its ancestors, which are given outermost first. The `xml:base` values are joined.
+fn inherited_attributes(doc: &arena::Document, ancestors: &[NodeId]) -> Vec<(String, String)> {
+    let mut lang = None;
+    let mut space = None;
+    let mut base: Option<String> = None;
+    for element in ancestors.iter().filter_map(|&id| doc.element(id)) {
+        let attributes = element.attributes.map();
+        if let Some(value) = attributes.get("xml:lang") {
+            lang = Some(value.clone());
+        }
+        if let Some(value) = attributes.get("xml:space") {
+            space = Some(value.clone());
+        }
+        if let Some(value) = attributes.get("xml:base") {
+            base = Some(match base {
+                Some(base) => join_uri(&base, value),
+                None => value.clone(),
+            });
+        }
+    }
+    let mut inherited = Vec::new();
+    for (name, value) in [("xml:base", base), ("xml:lang", lang), ("xml:space", space)] {
+        if let Some(value) = value {
+            inherited.push((name.to_string(), value));
+        }
+    }
+    inherited
+}
+
+struct Canonicalizer<'a, W: Write> {
+    doc: &'a arena::Document,
+    opts: &'a C14nOpts,
+    writer: &'a mut W,
+}
+
+impl<W: Write> Canonicalizer<'_, W> {
+    fn out(&mut self, s: &str) -> Result<()> {
+        if let Err(e) = self.writer.write_all(s.as_bytes()) {
+            return wrap!(e);
+        }
+        Ok(())
+    }
+
+    fn is_written(&self, id: NodeId) -> bool {
+        match self.doc.node(id) {
+            arena::Node::Comment(_) => self.opts.with_comments,
+            arena::Node::DocType(_) | arena::Node::Document => false,
+            _ => true,
+        }
+    }
+
+    /// The nodes outside of the root element are separated from it by '\n'.
+    fn document(&mut self, id: NodeId) -> Result<()> {
+        let root = self.doc.root();
+        let mut is_prolog = true;
+        for child in self.doc.children(id) {
+            if Some(child) == root {
+                self.element(child, &Namespaces::new(), &Namespaces::new(), &[])?;
+                is_prolog = false;
+            } else if !self.is_written(child) {
+                continue;
+            } else if is_prolog {
+                self.leaf(child)?;
+                self.out("\n")?;
+            } else {
+                self.out("\n")?;
+                self.leaf(child)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a node that is not an element or the document node.
+    fn leaf(&mut self, id: NodeId) -> Result<()> {
+        match self.doc.node(id) {
+            arena::Node::String(s) | arena::Node::CData(s) => self.out(&escape_text(s)),
+            arena::Node::Comment(s) if self.opts.with_comments => {
+                self.out(&format!("<!--{}-->", s))
+            }
+            arena::Node::ProcessingInstruction(_) => {
+                let opts = WriteOpts {
+                    format: Format::Canonical,
+                    ..WriteOpts::default()
+                };
+                self.doc.to_node(id).write(self.writer, &opts, 0)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the element, given the namespaces in scope on its parent, those declared by the
+    /// nearest written ancestor, and the attributes it inherits if it is the apex of a subtree.
+    fn element(
+        &mut self,
+        id: NodeId,
+        parent_scope: &Namespaces,
+        rendered: &Namespaces,
+        inherited: &[(String, String)],
+    ) -> Result<()> {
+        let element = match self.doc.element(id) {
+            Some(element) => element,
+            None => return raise!("Expected an element."),
+        };
+        let scope = enter(parent_scope, element);
+        let attributes = element
+            .attributes
+            .map()
+            .iter()
+            .filter(|(name, _)| name.as_str() != "xmlns" && !name.starts_with("xmlns:"))
+            .collect::<Vec<_>>();
+
+        // the prefixes whose declarations may be written
+        let mut prefixes = match self.opts.method {
+            Method::Inclusive => scope.keys().cloned().chain(Some(String::new())).collect(),
+            Method::Exclusive => {
+                let mut used = vec![element.namespace.as_deref().unwrap_or("").to_string()];
+                for (name, _) in &attributes {
+                    if let Some((prefix, _)) = name.split_once(':') {
+                        used.push(prefix.to_string());
+                    }
+                }
+                for prefix in &self.opts.inclusive_prefixes {
+                    match prefix.as_str() {
+                        "#default" => used.push(String::new()),
+                        prefix => used.push(prefix.to_string()),
+                    }
+                }
+                used
+            }
+        };
+        prefixes.sort();
+        prefixes.dedup();
+        let mut declarations = Vec::new();
+        let mut next_rendered = rendered.clone();
+        for prefix in prefixes {
+            if prefix == "xml" {
+                continue;
+            }
+            let uri = scope.get(&prefix).map(String::as_str).unwrap_or("");
+            let current = rendered.get(&prefix).map(String::as_str).unwrap_or("");
+            // only the default namespace can be undeclared
+            if (uri.is_empty() && !prefix.is_empty()) || uri == current {
+                continue;
+            }
+            let name = match prefix.as_str() {
+                "" => "xmlns".to_string(),
+                prefix => format!("xmlns:{}", prefix),
+            };
+            declarations.push((name, uri.to_string()));
+            next_rendered.insert(prefix, uri.to_string());
+        }
+
+        // attributes are sorted by namespace URI, with none first, and then by local name
+        let mut sorted = Vec::new();
+        for (name, value) in &attributes {
+            let (uri, local) = match name.split_once(':') {
+                Some((prefix, local)) => (namespace_uri(&scope, prefix)?, local),
+                None => ("", name.as_str()),
+            };
+            sorted.push(((uri, local), name.as_str(), value.as_str()));
+        }
+        for (name, value) in inherited {
+            if !element.attributes.map().contains_key(name.as_str()) {
+                sorted.push(((XML_NAMESPACE, &name[4..]), name.as_str(), value.as_str()));
+            }
+        }
+        sorted.sort();
+
+        let name = match &element.namespace {
+            Some(prefix) => {
+                namespace_uri(&scope, prefix)?;
+                format!("{}:{}", prefix, element.name)
+            }
+            None => element.name.to_string(),
+        };
+        self.out(&format!("<{}", name))?;
+        for (name, value) in declarations {
+            self.out(&format!(" {}=\"{}\"", name, escape_attribute(&value, '"')))?;
+        }
+        for (_, name, value) in sorted {
+            self.out(&format!(" {}=\"{}\"", name, escape_attribute(value, '"')))?;
+        }
+        self.out(">")?;
+        for child in self.doc.children(id) {
+            match self.doc.node(child) {
+                arena::Node::Element(_) => self.element(child, &scope, &next_rendered, &[])?,
+                _ => self.leaf(child)?,
+            }
+        }
+        self.out(&format!("</{}>", name))
+    }
+}
+
+/// Returns the namespace URI bound to the prefix, or an error if it is not declared.
+fn namespace_uri<'a>(scope: &'a Namespaces, prefix: &str) -> Result<&'a str> {
+    if prefix == "xml" {
+        return Ok(XML_NAMESPACE);
+    }
+    match scope.get(prefix) {
+        Some(uri) if !prefix.is_empty() => Ok(uri.as_str()),
+        _ => raise!("The namespace prefix '{}' is not declared.", prefix),
+    }
+}
+
+/// The parts of a URI reference, as in RFC 3986.
+#[derive(Debug, Default)]
+struct Uri<'a> {
+    scheme: Option<&'a str>,
+    authority: Option<&'a str>,
+    path: &'a str,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+}
+
+impl<'a> Uri<'a> {
+    fn parse(s: &'a str) -> Self {
+        let mut uri = Uri::default();
+        let mut rest = s;
+        if let Some((before, fragment)) = rest.split_once('#') {
+            uri.fragment = Some(fragment);
+            rest = before;
+        }
+        if let Some((before, query)) = rest.split_once('?') {
+            uri.query = Some(query);
+            rest = before;
+        }
+        if let Some((scheme, after)) = rest.split_once(':') {
+            let is_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+            if is_scheme && !scheme.contains('/') {
+                uri.scheme = Some(scheme);
+                rest = after;
+            }
+        }
+        if let Some(after) = rest.strip_prefix("//") {
+            let end = after.find('/').unwrap_or(after.len());
+            uri.authority = Some(&after[..end]);
+            rest = &after[end..];
+        }
+        uri.path = rest;
+        uri
+    }
+}
+
+/// Resolves the reference against the base URI as RFC 3986 section 5.2 does.
+fn join_uri(base: &str, reference: &str) -> String {
+    let base = Uri::parse(base);
+    let reference = Uri::parse(reference);
+    let (scheme, authority, path, query) = if reference.scheme.is_some() {
+        (
+            reference.scheme,
+            reference.authority,
+            remove_dot_segments(reference.path),
+            reference.query,
+        )
+    } else if reference.authority.is_some() {
+        (
+            base.scheme,
+            reference.authority,
+            remove_dot_segments(reference.path),
+            reference.query,
+        )
+    } else if reference.path.is_empty() {
+        (
+            base.scheme,
+            base.authority,
+            base.path.to_string(),
+            reference.query.or(base.query),
+        )
+    } else if reference.path.starts_with('/') {
+        (
+            base.scheme,
+            base.authority,
+            remove_dot_segments(reference.path),
+            reference.query,
+        )
+    } else {
+        let merged = if base.authority.is_some() && base.path.is_empty() {
+            format!("/{}", reference.path)
+        } else {
+            match base.path.rfind('/') {
+                Some(i) => format!("{}{}", &base.path[..=i], reference.path),
+                None => reference.path.to_string(),
+            }
+        };
+        (
+            base.scheme,
+            base.authority,
+            remove_dot_segments(&merged),
+            reference.query,
+        )
+    };
+    let mut uri = String::new();
+    if let Some(scheme) = scheme {
+        uri.push_str(scheme);
+        uri.push(':');
+    }
+    if let Some(authority) = authority {
+        uri.push_str("//");
+        uri.push_str(authority);
+    }
+    uri.push_str(&path);
+    if let Some(query) = query {
+        uri.push('?');
+        uri.push_str(query);
+    }
+    if let Some(fragment) = reference.fragment {
+        uri.push('#');
+        uri.push_str(fragment);
+    }
+    uri
+}
+
+/// Removes the `.` and `..` segments from a path, as RFC 3986 section 5.2.4 does.
+fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output: Vec<&str> = Vec::new();
+    while !input.is_empty() {
+        if let Some(rest) = input
+            .strip_prefix("../")
+            .or_else(|| input.strip_prefix("./"))
+        {
+            input = rest;
+        } else if input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") || input == "/.." {
+            input = if input == "/.." { "/" } else { &input[3..] };
+            output.pop();
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            // move the first segment, with its leading '/' if any, to the output
+            let start = usize::from(input.starts_with('/'));
+            let end = input[start..].find('/').map_or(input.len(), |i| i + start);
+            output.push(&input[..end]);
+            input = &input[end..];
+        }
+    }
+    output.concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{xml, Node};
+
+    fn arena(root: Result<crate::ElementData>) -> arena::Document {
+        arena::Document::from(&Document::from_root(root.unwrap()))
+    }
+
+    fn find(doc: &arena::Document, name: &str) -> NodeId {
+        doc.descendants(doc.document_node())
+            .find(|&id| doc.element(id).is_some_and(|e| e.name.as_str() == name))
+            .unwrap()
+    }
+
+    fn exclusive() -> C14nOpts {
+        C14nOpts {
+            method: Method::Exclusive,
+            ..C14nOpts::default()
+        }
+    }
+
+    #[test]
+    fn escaping_and_empty_elements() {
+        let root = xml! {
+            a(b = "\"1\"\t<2>\r\n'") { "x < y & z >\r", @cdata("<&>"), b }
+        };
+        let doc = Document::from_root(root.unwrap());
+        assert_eq!(
+            canonicalize(&doc, &C14nOpts::default()).unwrap(),
+            "<a b=\"&quot;1&quot;&#x9;&lt;2>&#xD;&#xA;'\">\
+             x &lt; y &amp; z &gt;&#xD;&lt;&amp;&gt;<b></b></a>"
+        );
+    }
+
+    #[test]
+    fn comments_and_top_level_nodes() {
+        let mut doc = Document::from_root(xml! { a { @comment(" in ") } }.unwrap());
+        doc.prolog.push(Node::DocType("a".to_string()));
+        doc.prolog.push(Node::Comment(" before ".to_string()));
+        doc.epilog.push(Node::Comment(" after ".to_string()));
+        assert_eq!(canonicalize(&doc, &C14nOpts::default()).unwrap(), "<a></a>");
+        let opts = C14nOpts {
+            with_comments: true,
+            ..C14nOpts::default()
+        };
+        assert_eq!(
+            canonicalize(&doc, &opts).unwrap(),
+            "<!-- before -->\n<a><!-- in --></a>\n<!-- after -->"
+        );
+    }
+
+    #[test]
+    fn superfluous_declarations_are_dropped() {
+        let root = xml! {
+            a("xmlns" = "urn:a", "xmlns:x" = "urn:x") {
+                b("xmlns" = "urn:a", "xmlns:x" = "urn:x") {
+                    c("xmlns" = "") { d("xmlns" = "") }
+                }
+            }
+        };
+        let doc = arena(root);
+        let expected = "<a xmlns=\"urn:a\" xmlns:x=\"urn:x\"><b><c xmlns=\"\"><d></d></c></b></a>";
+        let id = doc.document_node();
+        assert_eq!(
+            canonicalize_node(&doc, id, &C14nOpts::default()).unwrap(),
+            expected
+        );
+        // x is never used, so exclusive canonicalization leaves it out
+        assert_eq!(
+            canonicalize_node(&doc, id, &exclusive()).unwrap(),
+            "<a xmlns=\"urn:a\"><b><c xmlns=\"\"><d></d></c></b></a>"
+        );
+    }
+
+    #[test]
+    fn subtrees() {
+        let root = xml! {
+            ["x:a"]("xmlns:x" = "urn:x", "xmlns:y" = "urn:y", "xml:lang" = "en", "xml:base" = "http://example.org/a/") {
+                b("xml:base" = "b/c.xml", "xml:id" = "b") {
+                    ["x:c"]("y:k" = "v", "xml:space" = "preserve") { "text" }
+                }
+            }
+        };
+        let doc = arena(root);
+        let c = find(&doc, "c");
+        assert_eq!(
+            canonicalize_node(&doc, c, &C14nOpts::default()).unwrap(),
+            "<x:c xmlns:x=\"urn:x\" xmlns:y=\"urn:y\" xml:base=\"http://example.org/a/b/c.xml\" \
+             xml:lang=\"en\" xml:space=\"preserve\" y:k=\"v\">text</x:c>"
+        );
+        assert_eq!(
+            canonicalize_node(&doc, c, &exclusive()).unwrap(),
+            "<x:c xmlns:x=\"urn:x\" xmlns:y=\"urn:y\" xml:space=\"preserve\" y:k=\"v\">text</x:c>"
+        );
+        let b = find(&doc, "b");
+        let opts = C14nOpts {
+            inclusive_prefixes: vec!["y".to_string()],
+            ..exclusive()
+        };
+        assert_eq!(
+            canonicalize_node(&doc, b, &opts).unwrap(),
+            "<b xmlns:y=\"urn:y\" xml:base=\"b/c.xml\" xml:id=\"b\">\
+             <x:c xmlns:x=\"urn:x\" xml:space=\"preserve\" y:k=\"v\">text</x:c></b>"
+        );
+        let text = doc.first_child(c).unwrap();
+        assert_eq!(canonicalize_node(&doc, text, &exclusive()).unwrap(), "text");
+    }
+
+    #[test]
+    fn undeclared_prefix() {
+        let doc = Document::from_root(xml! { ["x:a"] }.unwrap());
+        assert!(canonicalize(&doc, &C14nOpts::default()).is_err());
+        let doc = Document::from_root(xml! { a("x:b" = "1") }.unwrap());
+        assert!(canonicalize(&doc, &C14nOpts::default()).is_err());
+    }
+
+    #[test]
+    fn algorithms() {
+        assert_eq!(
+            exclusive().algorithm(),
+            "http://www.w3.org/2001/10/xml-exc-c14n#"
+        );
+        let opts = C14nOpts {
+            with_comments: true,
+            ..C14nOpts::default()
+        };
+        assert_eq!(
+            opts.algorithm(),
+            "http://www.w3.org/2006/12/xml-c14n11#WithComments"
+        );
+    }
+
+    #[test]
+    fn uri_references() {
+        // from RFC 3986 section 5.4
+        let base = "http://a/b/c/d;p?q";
+        for (reference, expected) in &[
+            ("g:h", "g:h"),
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y#s", "http://a/b/c/g?y#s"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../../../g", "http://a/g"),
+            ("/./g", "http://a/g"),
+            ("g/../h", "http://a/b/c/h"),
+        ] {
+            assert_eq!(&join_uri(base, reference), expected, "{}", reference);
+        }
+        assert_eq!(join_uri("a/b/", "../c"), "a/c");
+    }
+}
@@ -0,0 +1,72 @@
+/// Returns `true` if `s` matches the XML `Name` production, which is the same in XML 1.0 and 1.1.
+pub(crate) fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if is_name_start_char(c) => chars.all(is_name_char),
+        _ => false,
+    }
+}
+
+pub(crate) fn is_name_start_char(c: char) -> bool {
+    // https://www.w3.org/TR/2008/REC-xml-20081126/#NT-NameStartChar
+    matches!(c,
+        'A'..='Z'
+        | 'a'..='z'
+        | ':'
+        | '_'
+        | '\u{C0}'..='\u{D6}'
+        | '\u{D8}'..='\u{F6}'
+        | '\u{F8}'..='\u{2FF}'
+        | '\u{370}'..='\u{37D}'
+        | '\u{37F}'..='\u{1FFF}'
+        | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}'
+        | '\u{2C00}'..='\u{2FEF}'
+        | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}'
+        | '\u{FDF0}'..='\u{FFFD}'
+        | '\u{10000}'..='\u{EFFFF}'
+    )
+}
+
+pub(crate) fn is_name_char(c: char) -> bool {
+    // https://www.w3.org/TR/2008/REC-xml-20081126/#NT-NameChar
+    is_name_start_char(c)
+        || matches!(c,
+            '0'..='9' | '-' | '.' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}'
+        )
+}
+
+/// Returns `true` if `c` matches the XML 1.0 `Char` production, i.e. it may appear in a document.
+#[cfg(feature = "serde")]
+pub(crate) fn is_char(c: char) -> bool {
+    // https://www.w3.org/TR/2008/REC-xml-20081126/#NT-Char
+    matches!(c,
+        '\t' | '\n' | '\r' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert!(is_name("cat"));
+        assert!(is_name("xs:élément-1.b"));
+        assert!(!is_name(""));
+        assert!(!is_name("1cat"));
+        assert!(!is_name("-cat"));
+        assert!(!is_name("cat dog"));
+        assert!(!is_name("cat>"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn chars() {
+        assert!("a\t\r\n é\u{10FFFF}".chars().all(is_char));
+        assert!(!is_char('\0'));
+        assert!(!is_char('\u{1B}'));
+        assert!(!is_char('\u{FFFE}'));
+    }
+}
@@ -0,0 +1,788 @@
+//! A structural diff between two documents, as a list of edits that each name the node or
+//! attribute they apply to with a path like `/cats/cat[2]/@name`.
+//!
+//! Children are aligned by a longest common subsequence of their kinds and names, so an element
+//! that is in both documents is compared attribute by attribute and child by child, and an element
+//! that is only in one of them is inserted or removed whole. Attribute order is never significant,
+//! since `OrdMap` does not keep it.
+//!
+//! ```
+//! use xdoc::diff::{Diff, Edit};
+//! use xdoc::{xml, Document};
+//!
+//! let old = Document::from_root(
+//!     xml! { cats { cat(name = "bones"), cat(name = "bishop") } }.unwrap(),
+//! );
+//! let new = Document::from_root(
+//!     xml! { cats { cat(name = "bones"), cat(name = "jack") { "punks" } } }.unwrap(),
+//! );
+//! let diff = Diff::new(&old, &new);
+//! assert_eq!(
+//!     diff.edits[0],
+//!     Edit::ChangeAttribute {
+//!         path: "/cats/cat[2]/@name".to_string(),
+//!         old: "bishop".to_string(),
+//!         new: "jack".to_string(),
+//!     }
+//! );
+//! assert_eq!(
+//!     diff.to_string(),
+//!     "~ /cats/cat[2]/@name: \"bishop\" -> \"jack\"\n+ /cats/cat[2]/text(): punks\n"
+//! );
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::escape::is_whitespace;
+use crate::{Declaration, Document, ElementData, Format, Namespaces, Node, WriteOpts};
+
+/// What the diff, and `semantic::Semantic`, leave out of the comparison. By default nothing is.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub struct DiffOpts {
+    /// Leaves out text nodes that are only whitespace.
+    pub ignore_whitespace_text: bool,
+    pub ignore_comments: bool,
+    /// Compares element and attribute names by namespace URI and local name rather than by
+    /// prefix, and leaves out the `xmlns` attributes that bind the prefixes.
+    pub ignore_prefixes: bool,
+}
+
+/// One difference between the old and the new document. The path of an inserted node or attribute
+/// is where it is in the new document, and otherwise it is where it is in the old one.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Edit {
+    Insert {
+        path: String,
+        node: Node,
+    },
+    Remove {
+        path: String,
+        node: Node,
+    },
+    /// Text, CDATA, a comment, a processing instruction or a DOCTYPE whose content changed.
+    Replace {
+        path: String,
+        old: Node,
+        new: Node,
+    },
+    InsertAttribute {
+        path: String,
+        value: String,
+    },
+    RemoveAttribute {
+        path: String,
+        value: String,
+    },
+    ChangeAttribute {
+        path: String,
+        old: String,
+        new: String,
+    },
+    ChangeDeclaration {
+        old: Declaration,
+        new: Declaration,
+    },
+}
+
+/// The edits that turn one document into another, in document order. `Display` writes a report
+/// with one line for each edit.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub struct Diff {
+    pub edits: Vec<Edit>,
+}
+
+impl Diff {
+    pub fn new(old: &Document, new: &Document) -> Self {
+        Diff::new_opts(old, new, &DiffOpts::default())
+    }
+
+    pub fn new_opts(old: &Document, new: &Document, opts: &DiffOpts) -> Self {
+        let mut differ = Differ {
+            opts: *opts,
+            edits: Vec::new(),
+        };
+        if old.declaration != new.declaration {
+            differ.edits.push(Edit::ChangeDeclaration {
+                old: old.declaration.clone(),
+                new: new.declaration.clone(),
+            });
+        }
+        let scope = Namespaces::default();
+        differ.children(
+            &Parent::new("", document_children(old), &scope),
+            &Parent::new("", document_children(new), &scope),
+        );
+        Diff {
+            edits: differ.edits,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for edit in &self.edits {
+            writeln!(f, "{}", edit)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Insert { path, node } => write!(f, "+ {}: {}", path, summary(node)),
+            Edit::Remove { path, node } => write!(f, "- {}: {}", path, summary(node)),
+            Edit::Replace { path, old, new } => {
+                write!(f, "~ {}: {} -> {}", path, summary(old), summary(new))
+            }
+            Edit::InsertAttribute { path, value } => write!(f, "+ {}: {:?}", path, value),
+            Edit::RemoveAttribute { path, value } => write!(f, "- {}: {:?}", path, value),
+            Edit::ChangeAttribute { path, old, new } => {
+                write!(f, "~ {}: {:?} -> {:?}", path, old, new)
+            }
+            Edit::ChangeDeclaration { old, new } => {
+                write!(f, "~ declaration: {:?} -> {:?}", old, new)
+            }
+        }
+    }
+}
+
+/// The node as compact XML.
+fn summary(node: &Node) -> String {
+    let opts = WriteOpts {
+        format: Format::Compact,
+        ..WriteOpts::default()
+    };
+    let mut bytes = Vec::new();
+    match node.write(&mut bytes, &opts, 0) {
+        Ok(()) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(_) => format!("{:?}", node),
+    }
+}
+
+/// Returns the namespace URI for the prefix, or the prefix itself if it is not bound, so that
+/// unbound prefixes still compare by name.
+fn resolve(scope: &Namespaces, prefix: &str) -> String {
+    match scope.uri(Some(prefix)) {
+        Some(uri) => uri.to_string(),
+        None => format!("{}:", prefix),
+    }
+}
+
+impl DiffOpts {
+    pub(crate) fn is_ignored(&self, node: &Node) -> bool {
+        match node {
+            Node::Comment(_) => self.ignore_comments,
+            Node::String(s) => self.ignore_whitespace_text && is_whitespace(s),
+            _ => false,
+        }
+    }
+
+    fn is_ignored_attribute(&self, name: &str) -> bool {
+        self.ignore_prefixes && (name == "xmlns" || name.starts_with("xmlns:"))
+    }
+
+    fn key(&self, child: Child<'_>, scope: &Namespaces) -> Key {
+        match child {
+            // an element's own namespace declarations are in scope for its name
+            Child::Element(element) => Key::Element(self.name(
+                element.namespace.as_deref(),
+                &element.name,
+                &scope.enter(element),
+                true,
+            )),
+            Child::Other(Node::String(_)) | Child::Other(Node::CData(_)) => Key::Text,
+            Child::Other(Node::Comment(_)) => Key::Comment,
+            Child::Other(Node::ProcessingInstruction(pi)) => {
+                Key::ProcessingInstruction(pi.target.clone())
+            }
+            Child::Other(Node::DocType(_)) => Key::DocType,
+            Child::Other(Node::Element(element)) => self.key(Child::Element(element), scope),
+        }
+    }
+
+    /// The name that elements and attributes are compared by.
+    pub(crate) fn name(
+        &self,
+        prefix: Option<&str>,
+        local: &str,
+        scope: &Namespaces,
+        is_element: bool,
+    ) -> String {
+        if !self.ignore_prefixes {
+            return match prefix {
+                Some(prefix) => format!("{}:{}", prefix, local),
+                None => local.to_string(),
+            };
+        }
+        // an unprefixed attribute is in no namespace, not in the default one
+        let uri = match prefix {
+            Some(prefix) => resolve(scope, prefix),
+            None if is_element => scope.uri(None).unwrap_or_default().to_string(),
+            None => String::new(),
+        };
+        format!("{{{}}}{}", uri, local)
+    }
+
+    /// The element's attributes, leaving out those that are ignored, by the name they are compared
+    /// by, with the name they have and their value. `scope` is the element's own.
+    pub(crate) fn attributes(
+        &self,
+        element: &ElementData,
+        scope: &Namespaces,
+    ) -> Vec<(String, (String, String))> {
+        let mut attributes = element
+            .attributes
+            .map()
+            .iter()
+            .filter(|(name, _)| !self.is_ignored_attribute(name))
+            .map(|(name, value)| {
+                let (prefix, local) = match name.split_once(':') {
+                    Some((prefix, local)) => (Some(prefix), local),
+                    None => (None, name.as_str()),
+                };
+                let key = self.name(prefix, local, scope, false);
+                (key, (name.to_string(), value.clone()))
+            })
+            .collect::<Vec<_>>();
+        attributes.sort();
+        attributes
+    }
+}
+
+/// A borrowed child of an element or of the document node. The root element is not held in a
+/// `Node`, so elements are borrowed on their own.
+#[derive(Clone, Copy)]
+enum Child<'a> {
+    Element(&'a ElementData),
+    Other(&'a Node),
+}
+
+impl<'a> From<&'a Node> for Child<'a> {
+    fn from(node: &'a Node) -> Self {
+        match node {
+            Node::Element(element) => Child::Element(element),
+            node => Child::Other(node),
+        }
+    }
+}
+
+impl Child<'_> {
+    fn to_node(self) -> Node {
+        match self {
+            Child::Element(element) => Node::Element(element.clone()),
+            Child::Other(node) => node.clone(),
+        }
+    }
+
+    /// The XPath node test that selects the child.
+    fn test(self) -> String {
+        match self {
+            Child::Element(element) | Child::Other(Node::Element(element)) => element.fullname(),
+            Child::Other(Node::String(_)) | Child::Other(Node::CData(_)) => "text()".to_string(),
+            Child::Other(Node::Comment(_)) => "comment()".to_string(),
+            Child::Other(Node::ProcessingInstruction(pi)) => {
+                format!("processing-instruction('{}')", pi.target)
+            }
+            Child::Other(Node::DocType(_)) => "doctype()".to_string(),
+        }
+    }
+}
+
+/// The document node's children.
+fn document_children(doc: &Document) -> Vec<Child<'_>> {
+    doc.prolog
+        .iter()
+        .map(Child::from)
+        .chain(Some(Child::Element(&doc.root)))
+        .chain(doc.epilog.iter().map(Child::from))
+        .collect()
+}
+
+/// The children of an element or of the document node, with what is needed to name them.
+struct Parent<'a> {
+    nodes: Vec<Child<'a>>,
+    /// The path of each child: an XPath location path with a position only where there is more
+    /// than one node with the same node test among the siblings.
+    paths: Vec<String>,
+    scope: &'a Namespaces,
+}
+
+impl<'a> Parent<'a> {
+    fn new(path: &str, nodes: Vec<Child<'a>>, scope: &'a Namespaces) -> Self {
+        let tests = nodes.iter().map(|node| node.test()).collect::<Vec<_>>();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for test in &tests {
+            *counts.entry(test).or_default() += 1;
+        }
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        let paths = tests
+            .iter()
+            .map(|test| match counts[test.as_str()] {
+                1 => format!("{}/{}", path, test),
+                _ => {
+                    let position = positions.entry(test).or_default();
+                    *position += 1;
+                    format!("{}/{}[{}]", path, test, position)
+                }
+            })
+            .collect();
+        Parent {
+            nodes,
+            paths,
+            scope,
+        }
+    }
+}
+
+/// What a node is matched on, which is its kind, and for an element or a processing instruction,
+/// its name. Text and CDATA are one kind.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+enum Key {
+    Element(String),
+    Text,
+    Comment,
+    ProcessingInstruction(String),
+    DocType,
+}
+
+struct Differ {
+    opts: DiffOpts,
+    edits: Vec<Edit>,
+}
+
+impl Differ {
+    fn children(&mut self, old: &Parent<'_>, new: &Parent<'_>) {
+        let old_indexes = self.kept(&old.nodes);
+        let new_indexes = self.kept(&new.nodes);
+        // equal keys get equal numbers, so that aligning the children compares numbers
+        let mut numbers: HashMap<Key, usize> = HashMap::new();
+        let mut number = |key: Key| {
+            let next = numbers.len();
+            *numbers.entry(key).or_insert(next)
+        };
+        let old_keys = old_indexes
+            .iter()
+            .map(|&i| number(self.opts.key(old.nodes[i], old.scope)))
+            .collect::<Vec<_>>();
+        let new_keys = new_indexes
+            .iter()
+            .map(|&i| number(self.opts.key(new.nodes[i], new.scope)))
+            .collect::<Vec<_>>();
+        let pairs = longest_common_subsequence(&old_keys, &new_keys);
+
+        let (mut i, mut j) = (0, 0);
+        for (matched_i, matched_j) in pairs
+            .into_iter()
+            .chain(Some((old_keys.len(), new_keys.len())))
+        {
+            // whatever is between the matches is only in one document
+            for &index in &old_indexes[i..matched_i] {
+                self.edits.push(Edit::Remove {
+                    path: old.paths[index].clone(),
+                    node: old.nodes[index].to_node(),
+                });
+            }
+            for &index in &new_indexes[j..matched_j] {
+                self.edits.push(Edit::Insert {
+                    path: new.paths[index].clone(),
+                    node: new.nodes[index].to_node(),
+                });
+            }
+            if matched_i < old_keys.len() {
+                self.node((old, old_indexes[matched_i]), (new, new_indexes[matched_j]));
+            }
+            i = matched_i + 1;
+            j = matched_j + 1;
+        }
+    }
+
+    /// The indexes of the nodes that are compared.
+    fn kept(&self, nodes: &[Child<'_>]) -> Vec<usize> {
+        (0..nodes.len())
+            .filter(|&i| match nodes[i] {
+                Child::Element(_) => true,
+                Child::Other(node) => !self.opts.is_ignored(node),
+            })
+            .collect()
+    }
+
+    fn node(&mut self, old: (&Parent<'_>, usize), new: (&Parent<'_>, usize)) {
+        let (old_path, new_path) = (&old.0.paths[old.1], &new.0.paths[new.1]);
+        match (old.0.nodes[old.1], new.0.nodes[new.1]) {
+            (Child::Element(old_element), Child::Element(new_element)) => {
+                let old_scope = old.0.scope.enter(old_element);
+                let new_scope = new.0.scope.enter(new_element);
+                self.attributes(
+                    (old_path, old_element, &old_scope),
+                    (new_path, new_element, &new_scope),
+                );
+                self.children(
+                    &Parent::new(
+                        old_path,
+                        old_element.nodes.iter().map(Child::from).collect(),
+                        &old_scope,
+                    ),
+                    &Parent::new(
+                        new_path,
+                        new_element.nodes.iter().map(Child::from).collect(),
+                        &new_scope,
+                    ),
+                );
+            }
+            (Child::Other(old_node), Child::Other(new_node)) if old_node != new_node => {
+                self.edits.push(Edit::Replace {
+                    path: old_path.clone(),
+                    old: old_node.clone(),
+                    new: new_node.clone(),
+                })
+            }
+            _ => {}
+        }
+    }
+
+    fn attributes(
+        &mut self,
+        old: (&str, &ElementData, &Namespaces),
+        new: (&str, &ElementData, &Namespaces),
+    ) {
+        let old_attributes = self.opts.attributes(old.1, old.2);
+        let new_attributes = self.opts.attributes(new.1, new.2);
+        let new_map = new_attributes.iter().cloned().collect::<HashMap<_, _>>();
+        let old_map = old_attributes.iter().cloned().collect::<HashMap<_, _>>();
+        let mut edits = Vec::new();
+        for (key, (name, value)) in &old_attributes {
+            match new_map.get(key) {
+                None => edits.push(Edit::RemoveAttribute {
+                    path: format!("{}/@{}", old.0, name),
+                    value: value.clone(),
+                }),
+                Some((_, new_value)) if new_value != value => edits.push(Edit::ChangeAttribute {
+                    path: format!("{}/@{}", old.0, name),
+                    old: value.clone(),
+                    new: new_value.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (key, (name, value)) in &new_attributes {
+            if !old_map.contains_key(key) {
+                edits.push(Edit::InsertAttribute {
+                    path: format!("{}/@{}", new.0, name),
+                    value: value.clone(),
+                });
+            }
+        }
+        self.edits.extend(edits);
+    }
+}
+
+/// Returns the index pairs of a longest common subsequence of `a` and `b`, in order. This is
+/// Myers' linear space algorithm, which takes time proportional to the length of the inputs times
+/// the number of elements that are only in one of them.
+fn longest_common_subsequence<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    subsequence_into(a, b, (0, 0), &mut pairs);
+    pairs
+}
+
+/// Pushes the pairs of a longest common subsequence of `a` and `b`, which start at `offset` in the
+/// whole inputs.
+fn subsequence_into<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    offset: (usize, usize),
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    pairs.extend((0..prefix).map(|k| (offset.0 + k, offset.1 + k)));
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let (a0, b0) = (offset.0 + prefix, offset.1 + prefix);
+    let suffix = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+    // with the prefix and suffix gone, either one side is empty or there are at least two edits,
+    // and then both halves around the middle snake are smaller than the whole
+    if !a.is_empty() && !b.is_empty() {
+        let ((x, y), (u, v)) = middle_snake(a, b);
+        subsequence_into(&a[..x], &b[..y], (a0, b0), pairs);
+        pairs.extend((0..u - x).map(|k| (a0 + x + k, b0 + y + k)));
+        subsequence_into(&a[u..], &b[v..], (a0 + u, b0 + v), pairs);
+    }
+    pairs.extend((0..suffix).map(|k| (a0 + a.len() + k, b0 + b.len() + k)));
+}
+
+/// Returns the start and end of the run of equal elements in the middle of a shortest edit script
+/// from `a` to `b`, found by searching forward from the start and backward from the end at once.
+#[allow(clippy::many_single_char_names)]
+fn middle_snake<T: PartialEq>(a: &[T], b: &[T]) -> ((usize, usize), (usize, usize)) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let max = (n + m + 1) / 2 + 1;
+    // forward[k] is the furthest x reached on the diagonal x - y = k, and backward[k] is the same
+    // counting from the ends of `a` and `b`
+    let mut forward = vec![0isize; 2 * max as usize + 1];
+    let mut backward = vec![0isize; 2 * max as usize + 1];
+    let at = |k: isize| (k + max) as usize;
+    for d in 0..max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let start = (x, x - k);
+            while x < n && x - k < m && a[x as usize] == b[(x - k) as usize] {
+                x += 1;
+            }
+            forward[at(k)] = x;
+            if delta % 2 != 0 && (delta - k).abs() < d && x + backward[at(delta - k)] >= n {
+                let (x0, y0) = start;
+                return ((x0 as usize, y0 as usize), (x as usize, (x - k) as usize));
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let start = (x, x - k);
+            while x < n && x - k < m && a[(n - x - 1) as usize] == b[(m - (x - k) - 1) as usize] {
+                x += 1;
+            }
+            backward[at(k)] = x;
+            if delta % 2 == 0 && (delta - k).abs() <= d && x + forward[at(delta - k)] >= n {
+                let (x0, y0) = start;
+                return (
+                    ((n - x) as usize, (m - (x - k)) as usize),
+                    ((n - x0) as usize, (m - y0) as usize),
+                );
+            }
+        }
+    }
+    unreachable!("the forward and backward searches always meet")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{xml, Encoding};
+
+    fn doc(root: crate::error::Result<ElementData>) -> Document {
+        Document::from_root(root.unwrap())
+    }
+
+    fn report(old: &Document, new: &Document, opts: &DiffOpts) -> Vec<String> {
+        Diff::new_opts(old, new, opts)
+            .edits
+            .iter()
+            .map(|edit| edit.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn equal_documents_have_no_edits() {
+        let cats = doc(xml! { cats { cat(name = "bones") { "big" } } });
+        assert!(Diff::new(&cats, &cats.clone()).is_empty());
+        assert_eq!(Diff::new(&cats, &cats).to_string(), "");
+    }
+
+    #[test]
+    fn inserted_removed_and_changed_nodes() {
+        let old = doc(xml! {
+            cats {
+                cat(name = "bones") { "big" },
+                cat(name = "bishop", age = "3"),
+                @comment(" old "),
+                dog,
+            }
+        });
+        let new = doc(xml! {
+            cats {
+                kitten,
+                cat(name = "bones") { @cdata("small") },
+                cat(name = "bishop", color = "black"),
+                @comment(" new "),
+            }
+        });
+        assert_eq!(
+            report(&old, &new, &DiffOpts::default()),
+            vec![
+                "+ /cats/kitten: <kitten/>",
+                "~ /cats/cat[1]/text(): big -> <![CDATA[small]]>",
+                "- /cats/cat[2]/@age: \"3\"",
+                "+ /cats/cat[2]/@color: \"black\"",
+                "~ /cats/comment(): <!-- old --> -> <!-- new -->",
+                "- /cats/dog: <dog/>",
+            ]
+        );
+    }
+
+    #[test]
+    fn renamed_elements_are_removed_and_inserted() {
+        let old = doc(xml! { cats { cat { "a" } } });
+        let new = doc(xml! { cats { dog { "a" } } });
+        let diff = Diff::new(&old, &new);
+        assert_eq!(
+            diff.edits,
+            vec![
+                Edit::Remove {
+                    path: "/cats/cat".to_string(),
+                    node: Node::Element(xml! { cat { "a" } }.unwrap()),
+                },
+                Edit::Insert {
+                    path: "/cats/dog".to_string(),
+                    node: Node::Element(xml! { dog { "a" } }.unwrap()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn prolog_epilog_and_declaration() {
+        let old = doc(xml! { cats });
+        let mut new = old.clone();
+        new.declaration.encoding = Encoding::Utf8;
+        new.prolog.push(Node::Comment(" hi ".to_string()));
+        new.epilog.push(Node::Comment(" bye ".to_string()));
+        assert_eq!(
+            report(&old, &new, &DiffOpts::default()),
+            vec![
+                format!(
+                    "~ declaration: {:?} -> {:?}",
+                    old.declaration, new.declaration
+                ),
+                "+ /comment()[1]: <!-- hi -->".to_string(),
+                "+ /comment()[2]: <!-- bye -->".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn options_ignore_whitespace_comments_and_prefixes() {
+        let old = doc(xml! {
+            ["a:cats"]("xmlns:a" = "urn:cats", "a:kind" = "house") {
+                "\n  ",
+                ["a:cat"] { @comment(" bones ") },
+            }
+        });
+        let new = doc(xml! {
+            ["b:cats"]("xmlns:b" = "urn:cats", "b:kind" = "house") {
+                ["b:cat"],
+                "\n",
+            }
+        });
+        assert_eq!(report(&old, &new, &DiffOpts::default()).len(), 2);
+        let opts = DiffOpts {
+            ignore_whitespace_text: true,
+            ignore_comments: true,
+            ignore_prefixes: true,
+        };
+        assert_eq!(Diff::new_opts(&old, &new, &opts).to_string(), "");
+
+        // the same prefix bound to another namespace is a different name
+        let other = doc(xml! { ["a:cats"]("xmlns:a" = "urn:dogs", "a:kind" = "house") });
+        assert_eq!(
+            report(&old, &other, &opts),
+            vec![
+                "- /a:cats: <a:cats xmlns:a=\"urn:cats\" a:kind=\"house\">\n  <a:cat><!-- bones --></a:cat></a:cats>",
+                "+ /a:cats: <a:cats xmlns:a=\"urn:dogs\" a:kind=\"house\"/>",
+            ]
+        );
+    }
+
+    #[test]
+    fn subsequence() {
+        let pairs = longest_common_subsequence(&[1, 2, 3, 4, 5], &[0, 2, 4, 5, 6]);
+        assert_eq!(pairs, vec![(1, 1), (3, 2), (4, 3)]);
+        assert!(longest_common_subsequence::<u8>(&[], &[1]).is_empty());
+    }
+
+    #[test]
+    fn subsequences_are_common_and_longest() {
+        // the length of a longest common subsequence, from the whole table
+        fn length(a: &[u8], b: &[u8]) -> usize {
+            let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+            for i in (0..a.len()).rev() {
+                for j in (0..b.len()).rev() {
+                    lengths[i][j] = if a[i] == b[j] {
+                        lengths[i + 1][j + 1] + 1
+                    } else {
+                        lengths[i + 1][j].max(lengths[i][j + 1])
+                    };
+                }
+            }
+            lengths[0][0]
+        }
+        let mut seed = 7u32;
+        let mut next = |limit: u32| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((seed >> 16) % limit) as u8
+        };
+        for _ in 0..500 {
+            let a = (0..next(20)).map(|_| next(4)).collect::<Vec<_>>();
+            let b = (0..next(20)).map(|_| next(4)).collect::<Vec<_>>();
+            let pairs = longest_common_subsequence(&a, &b);
+            assert_eq!(pairs.len(), length(&a, &b), "{:?} {:?}", a, b);
+            for (k, &(i, j)) in pairs.iter().enumerate() {
+                assert_eq!(a[i], b[j]);
+                if k > 0 {
+                    assert!(pairs[k - 1].0 < i && pairs[k - 1].1 < j);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn many_children() {
+        let mut cats = xml! { cats }.unwrap();
+        for i in 0..8000 {
+            let cat = ElementData {
+                name: format!("cat{}", i).as_str().into(),
+                ..ElementData::default()
+            };
+            cats.nodes.push(Node::Element(cat));
+        }
+        let old = Document::from_root(cats);
+        assert!(Diff::new(&old, &old).is_empty());
+        let mut new = old.clone();
+        new.root.nodes.remove(10);
+        new.root.nodes[3999]
+            .as_element_mut()
+            .unwrap()
+            .attributes
+            .mut_map()
+            .insert("name".into(), "bones".to_string());
+        new.root
+            .nodes
+            .insert(7000, Node::Element(xml! { dog }.unwrap()));
+        assert_eq!(
+            report(&old, &new, &DiffOpts::default()),
+            vec![
+                "- /cats/cat10: <cat10/>",
+                "+ /cats/cat4000/@name: \"bones\"",
+                "+ /cats/dog: <dog/>",
+            ]
+        );
+    }
+}
@@ -2,43 +2,33 @@ use std::default::Default;
 use std::io::{Cursor, Write};
 
 use crate::error::Result;
-use crate::ElementData;
+use crate::{ElementData, Node};
 
-#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
     serde(rename_all = "snake_case")
 )]
 pub enum Version {
+    #[default]
     None,
     One,
     OneDotOne,
 }
 
-impl Default for Version {
-    fn default() -> Self {
-        Version::None
-    }
-}
-
-#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
     serde(rename_all = "snake_case")
 )]
 pub enum Encoding {
+    #[default]
     None,
     Utf8,
 }
 
-impl Default for Encoding {
-    fn default() -> Self {
-        Encoding::None
-    }
-}
-
 #[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
 #[cfg_attr(
     feature = "serde",
@@ -58,19 +48,83 @@ pub struct Declaration {
 )]
 pub struct Document {
     pub declaration: Declaration,
+    /// The comments, processing instructions and DOCTYPE between the declaration and the root, in
+    /// document order.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub prolog: Vec<Node>,
     pub root: ElementData,
+    /// The comments and processing instructions after the root, in document order.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub epilog: Vec<Node>,
+}
+
+impl Declaration {
+    /// Returns `true` if there is nothing to declare, in which case no declaration is written.
+    pub fn is_empty(&self) -> bool {
+        self.version == Version::None && self.encoding == Encoding::None
+    }
+
+    pub(crate) fn write<W>(&self, writer: &mut W, opts: &WriteOpts) -> Result<()>
+    where
+        W: Write,
+    {
+        let q = opts.quote_char();
+        if let Err(e) = write!(writer, "<?xml ") {
+            return wrap!(e);
+        }
+        let mut need_space = true;
+        match self.version {
+            Version::None => need_space = false,
+            Version::One => {
+                if let Err(e) = write!(writer, "version={}1.0{}", q, q) {
+                    return wrap!(e);
+                }
+            }
+            Version::OneDotOne => {
+                if let Err(e) = write!(writer, "version={}1.1{}", q, q) {
+                    return wrap!(e);
+                }
+            }
+        }
+
+        match self.encoding {
+            Encoding::None => {}
+            Encoding::Utf8 => {
+                if need_space {
+                    if let Err(e) = write!(writer, " ") {
+                        return wrap!(e);
+                    }
+                }
+                if let Err(e) = write!(writer, "encoding={}UTF-8{}", q, q) {
+                    return wrap!(e);
+                }
+            }
+        }
+        if let Err(e) = write!(writer, "?>") {
+            return wrap!(e);
+        }
+        Ok(())
+    }
 }
 
 impl Default for Document {
     fn default() -> Self {
         Document {
             declaration: Declaration::default(),
+            prolog: Vec::new(),
             root: ElementData {
                 namespace: None,
-                name: "root".to_string(),
+                name: "root".into(),
                 attributes: Default::default(),
                 nodes: vec![],
             },
+            epilog: Vec::new(),
         }
     }
 }
@@ -88,31 +142,105 @@ impl Default for Indent {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
 pub enum Newline {
     None,
-    Newline,
+    /// `\n`
+    #[default]
+    Unix,
+    /// `\r\n`
     Windows,
 }
 
-impl Default for Newline {
-    fn default() -> Self {
-        Newline::Newline
-    }
+/// How the writer lays out the nodes of a document.
+#[derive(Debug, Clone, Copy, Eq, PartialOrd, PartialEq, Hash, Default)]
+pub enum Format {
+    /// Elements that contain only elements, comments and processing instructions have their
+    /// children indented on lines of their own, and whitespace-only text between those children is
+    /// replaced by the indentation. Mixed content, i.e. anything containing text or CDATA, and
+    /// anything inside `xml:space="preserve"`, is written inline exactly as it is.
+    #[default]
+    Pretty,
+    /// Nothing is added: the document's text nodes, and the trailing newline if there is one, are
+    /// the only whitespace written.
+    Compact,
+    /// The layout of Canonical XML: no declaration or DOCTYPE, CDATA written as escaped text,
+    /// empty elements written as start-end tag pairs, double quotes, and a '\n' separating the
+    /// root element from the comments and processing instructions around it. The other options,
+    /// apart from `format`, are ignored. Namespaces are not resolved, see `c14n` for that.
+    Canonical,
+}
+
+/// The quote character written around attribute values.
+#[derive(Debug, Clone, Copy, Eq, PartialOrd, PartialEq, Hash, Default)]
+pub enum Quote {
+    /// `name="value"`
+    #[default]
+    Double,
+    /// `name='value'`
+    Single,
+}
+
+/// How an element without any child nodes is written.
+#[derive(Debug, Clone, Copy, Eq, PartialOrd, PartialEq, Hash, Default)]
+pub enum EmptyElement {
+    /// `<a/>`
+    #[default]
+    SelfClosing,
+    /// `<a></a>`
+    StartEnd,
 }
 
+/// Options that control how a document is formatted when it is written.
 #[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
 pub struct WriteOpts {
     pub indent: Indent,
     pub newline: Newline,
+    pub format: Format,
+    /// When a start tag that begins on a line of its own would be wider than this many chars,
+    /// counting its indentation, each of its attributes is written on a line of its own instead.
+    /// `None` never wraps attributes, and neither does `Newline::None`.
+    pub line_width: Option<usize>,
+    pub quote: Quote,
+    pub empty_element: EmptyElement,
+    /// Whether the document ends with a newline.
+    pub trailing_newline: bool,
 }
 
 impl WriteOpts {
-    fn newline_str(&self) -> &'static str {
+    pub(crate) fn newline_str(&self) -> &'static str {
         match self.newline {
             Newline::None => "",
-            Newline::Newline => "\n",
-            Newline::Windows => "\n\r",
+            Newline::Unix => "\n",
+            Newline::Windows => "\r\n",
+        }
+    }
+
+    pub(crate) fn quote_char(&self) -> char {
+        match (self.format, self.quote) {
+            (Format::Canonical, _) | (_, Quote::Double) => '"',
+            (_, Quote::Single) => '\'',
+        }
+    }
+
+    pub(crate) fn self_closing(&self) -> bool {
+        self.format != Format::Canonical && self.empty_element == EmptyElement::SelfClosing
+    }
+
+    /// Returns `true` if a start tag that is `width` chars wide, not counting its indentation,
+    /// should have its attributes wrapped.
+    pub(crate) fn wraps_attributes(&self, depth: usize, width: usize) -> bool {
+        if self.format != Format::Pretty || self.newline == Newline::None {
+            return false;
+        }
+        let indent_width = match self.indent {
+            Indent::None => 0,
+            Indent::Spaces(n) => depth * n,
+            Indent::Tab => depth,
+        };
+        match self.line_width {
+            None => false,
+            Some(line_width) => indent_width + width > line_width,
         }
     }
 
@@ -120,8 +248,7 @@ impl WriteOpts {
     where
         W: Write,
     {
-        let s = std::iter::repeat(s).take(num).collect::<String>();
-        if let Err(e) = write!(writer, "{}", s) {
+        if let Err(e) = write!(writer, "{}", s.repeat(num)) {
             return wrap!(e);
         }
         Ok(())
@@ -131,6 +258,9 @@ impl WriteOpts {
     where
         W: Write,
     {
+        if self.format != Format::Pretty {
+            return Ok(());
+        }
         match self.indent {
             Indent::None => {
                 return Ok(());
@@ -153,6 +283,9 @@ impl WriteOpts {
     where
         W: Write,
     {
+        if self.format != Format::Pretty {
+            return Ok(());
+        }
         if let Err(e) = write!(writer, "{}", self.newline_str()) {
             return wrap!(e);
         }
@@ -168,7 +301,9 @@ impl Document {
     pub fn from_root(root: ElementData) -> Self {
         Document {
             declaration: Default::default(),
+            prolog: Vec::new(),
             root,
+            epilog: Vec::new(),
         }
     }
 
@@ -187,40 +322,20 @@ impl Document {
     where
         W: Write,
     {
-        if self.declaration.encoding != Encoding::None || self.declaration.version != Version::None
-        {
-            if let Err(e) = write!(writer, "<?xml ") {
+        if opts.format == Format::Canonical {
+            return self.write_canonical(writer, opts);
+        }
+        if !self.declaration.is_empty() {
+            if let Err(e) = self.declaration.write(writer, opts) {
                 return wrap!(e);
             }
-            let mut need_space = true;
-            match self.declaration.version {
-                Version::None => need_space = false,
-                Version::One => {
-                    if let Err(e) = write!(writer, "version=\"1.0\"") {
-                        return wrap!(e);
-                    }
-                }
-                Version::OneDotOne => {
-                    if let Err(e) = write!(writer, "version=\"1.1\"") {
-                        return wrap!(e);
-                    }
-                }
+            if let Err(e) = opts.newline(writer) {
+                return wrap!(e);
             }
+        }
 
-            match self.declaration.encoding {
-                Encoding::None => {}
-                Encoding::Utf8 => {
-                    if need_space {
-                        if let Err(e) = write!(writer, " ") {
-                            return wrap!(e);
-                        }
-                    }
-                    if let Err(e) = write!(writer, "encoding=\"UTF-8\"") {
-                        return wrap!(e);
-                    }
-                }
-            }
-            if let Err(e) = write!(writer, "?>") {
+        for node in self.prolog.iter() {
+            if let Err(e) = node.write(writer, opts, 0) {
                 return wrap!(e);
             }
             if let Err(e) = opts.newline(writer) {
@@ -232,12 +347,57 @@ impl Document {
             return wrap!(e);
         }
 
+        for node in self.epilog.iter() {
+            if let Err(e) = opts.newline(writer) {
+                return wrap!(e);
+            }
+            if let Err(e) = node.write(writer, opts, 0) {
+                return wrap!(e);
+            }
+        }
+
+        if opts.trailing_newline {
+            if let Err(e) = write!(writer, "{}", opts.newline_str()) {
+                return wrap!(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Canonical XML drops the declaration and DOCTYPE, and separates each of the remaining
+    /// top-level nodes with a single '\n' regardless of `opts.newline`.
+    fn write_canonical<W>(&self, writer: &mut W, opts: &WriteOpts) -> Result<()>
+    where
+        W: Write,
+    {
+        for node in self.prolog.iter() {
+            if let Node::DocType(_) = node {
+                continue;
+            }
+            if let Err(e) = node.write(writer, opts, 0) {
+                return wrap!(e);
+            }
+            if let Err(e) = writeln!(writer) {
+                return wrap!(e);
+            }
+        }
+        if let Err(e) = self.root().write(writer, opts, 0) {
+            return wrap!(e);
+        }
+        for node in self.epilog.iter() {
+            if let Err(e) = writeln!(writer) {
+                return wrap!(e);
+            }
+            if let Err(e) = node.write(writer, opts, 0) {
+                return wrap!(e);
+            }
+        }
         Ok(())
     }
 
     pub fn to_string_opts(&self, opts: &WriteOpts) -> Result<String> {
         let mut c = Cursor::new(Vec::new());
-        if let Err(e) = self.write_opts(&mut c, &opts) {
+        if let Err(e) = self.write_opts(&mut c, opts) {
             return wrap!(e);
         }
         let data = c.into_inner();
@@ -248,12 +408,12 @@ impl Document {
     }
 }
 
-impl ToString for Document {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let opts = WriteOpts::default();
         match self.to_string_opts(&opts) {
-            Ok(s) => s,
-            Err(_) => "<error/>".to_string(),
+            Ok(s) => write!(f, "{}", s),
+            Err(_) => write!(f, "<error/>"),
         }
     }
 }
@@ -275,8 +435,9 @@ macro_rules! map (
 mod tests {
     use std::io::Cursor;
 
-    use crate::doc::{Declaration, Encoding, Version};
+    use crate::doc::{Declaration, Encoding, Format, Version, WriteOpts};
     use crate::*;
+    use crate::{EmptyElement, Indent, Newline, Quote};
 
     fn assert_ezfile(doc: &Document) {
         let root = doc.root();
@@ -285,7 +446,7 @@ mod tests {
         assert_eq!(root_data.namespace, None);
         assert_eq!(root_data.attributes.map().len(), 0);
         assert_eq!(root_data.nodes.len(), 2);
-        let bones_element = root_data.nodes.get(0).unwrap();
+        let bones_element = root_data.nodes.first().unwrap();
         if let Node::Element(bones) = bones_element {
             assert_eq!(bones.name, "cat");
             assert_eq!(bones.namespace, None);
@@ -305,7 +466,7 @@ mod tests {
             assert_eq!(name, "bishop");
             // assert text data
             assert_eq!(bishop.nodes.len(), 1);
-            if let Node::String(text) = bishop.nodes.get(0).unwrap() {
+            if let Node::String(text) = bishop.nodes.first().unwrap() {
                 assert_eq!(text, "punks");
             } else {
                 panic!("Expected to find a text node but it was not there.");
@@ -323,38 +484,20 @@ mod tests {
 "#;
 
     fn create_ezfile() -> Document {
-        let bones_data = ElementData {
-            namespace: None,
-            name: "cat".to_string(),
-            attributes: OrdMap::from(map! { "name".to_string() => "bones".to_string() }),
-            nodes: Vec::default(),
-        };
-
-        let bishop_data = ElementData {
-            namespace: None,
-            name: "cat".to_string(),
-            attributes: OrdMap::from(map! { "name".to_string() => "bishop".to_string() }),
-            nodes: vec![Node::String("punks".to_string())],
-        };
-
-        let bones_element = Node::Element(bones_data);
-        let bishop_element = Node::Element(bishop_data);
-
-        let cats_data = ElementData {
-            namespace: None,
-            name: "cats".to_string(),
-            attributes: Default::default(),
-            nodes: vec![bones_element, bishop_element],
+        let cats = xml! {
+            cats {
+                cat(name = "bones"),
+                cat(name = "bishop") { "punks" },
+            }
         };
-
-        // Document::from_root(Node::Element(cats_data))
-
         Document {
             declaration: Declaration {
                 version: Version::One,
                 encoding: Encoding::Utf8,
             },
-            root: cats_data,
+            prolog: Vec::new(),
+            root: cats.unwrap(),
+            epilog: Vec::new(),
         }
     }
 
@@ -368,16 +511,165 @@ mod tests {
     fn test_ezfile_to_string() {
         let doc = create_ezfile();
         let mut c = Cursor::new(Vec::new());
-        let result = doc.write(&mut c);
+        let opts = WriteOpts {
+            trailing_newline: true,
+            ..Default::default()
+        };
+        let result = doc.write_opts(&mut c, &opts);
         assert!(result.is_ok());
         let data = c.into_inner();
         let data_str = std::str::from_utf8(data.as_slice()).unwrap();
-        // TODO - remove this debugging write
-        let we = std::fs::write("/Users/mjb/Desktop/early.xml", data_str);
-        assert!(we.is_ok());
         assert_eq!(data_str, EZFILE_STR);
     }
 
+    fn element(name: &str, nodes: Vec<Node>) -> Node {
+        Node::Element(ElementData {
+            namespace: None,
+            name: name.into(),
+            attributes: Default::default(),
+            nodes,
+        })
+    }
+
+    fn text(s: &str) -> Node {
+        Node::String(s.to_string())
+    }
+
+    fn write_root(root: Node, format: Format) -> String {
+        let root = match root {
+            Node::Element(data) => data,
+            _ => panic!("the root must be an element"),
+        };
+        let opts = WriteOpts {
+            format,
+            ..Default::default()
+        };
+        Document::from_root(root).to_string_opts(&opts).unwrap()
+    }
+
+    #[test]
+    fn pretty_leaves_mixed_content_inline() {
+        let p = element(
+            "p",
+            vec![
+                text("Hello "),
+                element("b", vec![text("world"), element("i", vec![])]),
+                text("!"),
+            ],
+        );
+        let body = element(
+            "body",
+            vec![text("\n"), p, element("hr", vec![]), text(" ")],
+        );
+        assert_eq!(
+            write_root(body, Format::Pretty),
+            "<body>\n  <p>Hello <b>world<i/></b>!</p>\n  <hr/>\n</body>"
+        );
+    }
+
+    #[test]
+    fn pretty_keeps_whitespace_that_is_text() {
+        assert_eq!(
+            write_root(element("a", vec![text("  ")]), Format::Pretty),
+            "<a>  </a>"
+        );
+        let mut pre = ElementData {
+            namespace: None,
+            name: "pre".into(),
+            attributes: OrdMap::from(map! { "xml:space".to_string() => "preserve".to_string() }),
+            nodes: vec![element("b", vec![]), text("\n"), element("c", vec![])],
+        };
+        assert_eq!(
+            write_root(Node::Element(pre.clone()), Format::Pretty),
+            "<pre xml:space=\"preserve\"><b/>\n<c/></pre>"
+        );
+        pre.nodes = vec![Node::CData("x".to_string()), element("b", vec![])];
+        pre.attributes = Default::default();
+        assert_eq!(
+            write_root(Node::Element(pre), Format::Pretty),
+            "<pre><![CDATA[x]]><b/></pre>"
+        );
+    }
+
+    #[test]
+    fn compact_and_canonical() {
+        let mut doc = create_ezfile();
+        doc.prolog.push(Node::Comment(" cats ".to_string()));
+        doc.prolog.push(Node::DocType("cats".to_string()));
+        doc.epilog.push(Node::Comment(" end ".to_string()));
+        if let Node::Element(bishop) = &mut doc.root.nodes[1] {
+            bishop.nodes.push(Node::CData(" & co".to_string()));
+            bishop
+                .attributes
+                .mut_map()
+                .insert("b".into(), "\"1\"\t".to_string());
+        }
+        let compact = WriteOpts {
+            format: Format::Compact,
+            ..Default::default()
+        };
+        assert_eq!(
+            doc.to_string_opts(&compact).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><!-- cats --><!DOCTYPE cats><cats>\
+             <cat name=\"bones\"/><cat b=\"&quot;1&quot;&#x9;\" name=\"bishop\">\
+             punks<![CDATA[ & co]]></cat></cats><!-- end -->"
+        );
+        let canonical = WriteOpts {
+            format: Format::Canonical,
+            ..Default::default()
+        };
+        assert_eq!(
+            doc.to_string_opts(&canonical).unwrap(),
+            "<!-- cats -->\n<cats><cat name=\"bones\"></cat>\
+             <cat b=\"&quot;1&quot;&#x9;\" name=\"bishop\">punks &amp; co</cat></cats>\n\
+             <!-- end -->"
+        );
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        let root = element("a", vec![text("1 < 2 & 3 > 2\r\n")]);
+        assert_eq!(
+            write_root(root, Format::Compact),
+            "<a>1 &lt; 2 &amp; 3 &gt; 2&#xD;\n</a>"
+        );
+    }
+
+    #[test]
+    fn formatting_options() {
+        let mut doc = create_ezfile();
+        if let Node::Element(bones) = &mut doc.root.nodes[0] {
+            bones
+                .attributes
+                .mut_map()
+                .insert("says".into(), "'meow'".to_string());
+        }
+        let opts = WriteOpts {
+            indent: Indent::Tab,
+            newline: Newline::Windows,
+            line_width: Some(30),
+            quote: Quote::Single,
+            empty_element: EmptyElement::StartEnd,
+            ..Default::default()
+        };
+        assert_eq!(
+            doc.to_string_opts(&opts).unwrap(),
+            "<?xml version='1.0' encoding='UTF-8'?>\r\n<cats>\r\n\t<cat\r\n\t\tname='bones'\r\n\
+             \t\tsays='&apos;meow&apos;'></cat>\r\n\t<cat name='bishop'>punks</cat>\r\n</cats>"
+        );
+        // the width counts the indentation
+        let opts = WriteOpts {
+            line_width: Some(28),
+            trailing_newline: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            doc.to_string_opts(&opts).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<cats>\n  <cat\n    name=\"bones\"\n    \
+             says=\"'meow'\"/>\n  <cat name=\"bishop\">punks</cat>\n</cats>\n"
+        );
+    }
+
     // TODO - feature flagging is not working for serde
     // #[test]
     // #[cfg(feature = "serde")]
@@ -69,7 +69,7 @@ macro_rules! raise {
         line: line!() as u64,
         source: None,
     }));
-    ($fmt:expr, $($arg:expr),+) => ($Err($crate::error::XErr {
+    ($fmt:expr, $($arg:expr),+) => (Err($crate::error::XErr {
         message: format!($fmt, $($arg),+),
         file: file!().to_string(),
         line: line!() as u64,
@@ -0,0 +1,77 @@
+use std::borrow::Cow;
+
+/// Escapes character data so that a parser reads back exactly `s`. Carriage returns are written as
+/// character references because a parser would otherwise normalize them to '\n'.
+pub(crate) fn escape_text(s: &str) -> Cow<'_, str> {
+    escape(s, |c| match c {
+        '&' => Some("&amp;"),
+        '<' => Some("&lt;"),
+        '>' => Some("&gt;"),
+        '\r' => Some("&#xD;"),
+        _ => None,
+    })
+}
+
+/// Escapes an attribute value that is written between `quote`s so that a parser reads back exactly
+/// `s`. Tabs and line ends are written as character references so that they survive attribute
+/// value normalization.
+pub(crate) fn escape_attribute(s: &str, quote: char) -> Cow<'_, str> {
+    escape(s, |c| match c {
+        '&' => Some("&amp;"),
+        '<' => Some("&lt;"),
+        '"' if quote == '"' => Some("&quot;"),
+        '\'' if quote == '\'' => Some("&apos;"),
+        '\t' => Some("&#x9;"),
+        '\n' => Some("&#xA;"),
+        '\r' => Some("&#xD;"),
+        _ => None,
+    })
+}
+
+/// Returns `true` if `s` is made up only of XML whitespace.
+pub(crate) fn is_whitespace(s: &str) -> bool {
+    s.bytes().all(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
+}
+
+fn escape<F>(s: &str, replacement: F) -> Cow<'_, str>
+where
+    F: Fn(char) -> Option<&'static str>,
+{
+    // borrowed unless something had to be replaced
+    let mut owned: Option<String> = None;
+    let mut copied = 0;
+    for (i, c) in s.char_indices() {
+        if let Some(r) = replacement(c) {
+            let out = owned.get_or_insert_with(|| String::with_capacity(s.len() + 8));
+            out.push_str(&s[copied..i]);
+            out.push_str(r);
+            copied = i + c.len_utf8();
+        }
+    }
+    match owned {
+        None => Cow::Borrowed(s),
+        Some(mut out) => {
+            out.push_str(&s[copied..]);
+            Cow::Owned(out)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        assert!(matches!(escape_text("bones"), Cow::Borrowed("bones")));
+        assert_eq!(
+            escape_text("a < b && c > d\r\n"),
+            "a &lt; b &amp;&amp; c &gt; d&#xD;\n"
+        );
+        assert_eq!(
+            escape_attribute("\"1\"\t<2>\n'", '"'),
+            "&quot;1&quot;&#x9;&lt;2>&#xA;'"
+        );
+        assert_eq!(escape_attribute("\"1\"'", '\''), "\"1\"&apos;");
+    }
+}
@@ -5,22 +5,47 @@ extern crate serde;
 use std::hash::Hash;
 use std::io::Write;
 
+pub use builder::ElementBuilder;
 pub use doc::Document;
 pub use doc::{Declaration, Encoding, Version};
+pub use doc::{EmptyElement, Format, Indent, Newline, Quote, WriteOpts};
+pub use ds::Symbol;
+pub use navigation::{Descendants, Namespaces};
 pub use node::Node;
 pub use nodes::Nodes;
 pub use ord_map::OrdMap;
+pub use select::Select;
+pub use visit::{Fold, Visitor};
+pub use writer::XmlWriter;
 
-use crate::doc::WriteOpts;
 use crate::error::Result;
+use crate::escape::is_whitespace;
+use crate::writer::write_start_tag;
 
 #[macro_use]
 pub mod error;
 
+pub mod arena;
+pub mod borrowed;
+mod builder;
+pub mod c14n;
+mod chars;
+pub mod diff;
 mod doc;
+mod escape;
+mod mutation;
+mod navigation;
 mod node;
 mod nodes;
 mod ord_map;
+pub mod patch;
+mod select;
+pub mod semantic;
+#[cfg(feature = "serde")]
+pub mod ser;
+pub mod visit;
+mod writer;
+pub mod xpath;
 
 #[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
 #[cfg_attr(
@@ -44,17 +69,6 @@ pub struct PIData {
     pub instructions: OrdMap,
 }
 
-#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
-#[cfg_attr(
-    feature = "serde",
-    derive(Serialize, Deserialize),
-    serde(rename_all = "snake_case")
-)]
-struct Attribute {
-    key: String,
-    value: String,
-}
-
 #[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash, Default)]
 #[cfg_attr(
     feature = "serde",
@@ -62,8 +76,8 @@ struct Attribute {
     serde(rename_all = "snake_case")
 )]
 pub struct ElementData {
-    pub namespace: Option<String>,
-    pub name: String,
+    pub namespace: Option<Symbol>,
+    pub name: Symbol,
     pub attributes: OrdMap,
     pub nodes: Vec<Node>,
 }
@@ -92,111 +106,108 @@ impl ElementData {
                 return format!("{}:{}", ns, self.name);
             }
         }
-        self.name.clone()
+        self.name.to_string()
     }
 
     pub fn write<W>(&self, writer: &mut W, opts: &WriteOpts, depth: usize) -> Result<()>
+    where
+        W: Write,
+    {
+        self.write_element(writer, opts, depth, opts.format == Format::Pretty)
+    }
+
+    /// Writes the element, indenting its children only if `pretty` is `true` and doing so cannot
+    /// change the element's text.
+    pub(crate) fn write_element<W>(
+        &self,
+        writer: &mut W,
+        opts: &WriteOpts,
+        depth: usize,
+        pretty: bool,
+    ) -> Result<()>
     where
         W: Write,
     {
         if let Err(e) = self.check() {
             return wrap!(e);
         }
-        // Somewhat hacky, but if it's the root element then the caller needs to decide whether or
-        // not a newline precededs the element. For all other elements a newline should be OK.
-        if depth != 0 {
-            if let Err(e) = opts.newline(writer) {
-                return wrap!(e);
-            }
-        }
-        if let Err(e) = opts.indent(writer, depth) {
+        let fullname = self.fullname();
+        let attributes = self
+            .attributes
+            .map()
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<Vec<_>>();
+        let is_empty = self.nodes.is_empty();
+        if let Err(e) =
+            write_start_tag(writer, opts, &fullname, attributes, depth, pretty, is_empty)
+        {
             return wrap!(e);
         }
-        if let Err(e) = write!(writer, "<") {
-            return wrap!(e);
+        if is_empty && opts.self_closing() {
+            return Ok(());
         }
-        if let Some(ns) = &self.namespace {
-            if !ns.is_empty() {
-                if let Err(e) = write!(writer, "{}:", ns) {
+
+        if pretty && self.has_element_content() && !self.preserves_space() {
+            for node in self.nodes.iter() {
+                // the indentation takes the place of whitespace between the children
+                if let Node::String(s) = node {
+                    if is_whitespace(s) {
+                        continue;
+                    }
+                }
+                if let Err(e) = opts.newline(writer) {
                     return wrap!(e);
                 }
-            }
-        }
-        if let Err(e) = write!(writer, "{}", self.name) {
-            return wrap!(e);
-        }
-
-        let attribute_keys = self
-            .attributes
-            .map()
-            .keys()
-            .map(|k| k.as_str())
-            .collect::<Vec<&str>>();
-        for &k in attribute_keys.iter() {
-            if let Err(e) = write!(writer, " {}=\"", &k) {
-                return wrap!(e);
-            }
-            if let Some(val) = self.attributes.map().get(k) {
-                // TODO - escape string
-                if let Err(e) = write!(writer, "{}", val) {
+                if let Err(e) = opts.indent(writer, depth + 1) {
+                    return wrap!(e);
+                }
+                if let Err(e) = node.write_node(writer, opts, depth + 1, true) {
                     return wrap!(e);
                 }
             }
-            if let Err(e) = write!(writer, "\"") {
+            if let Err(e) = opts.newline(writer) {
                 return wrap!(e);
             }
-        }
-
-        if self.nodes.is_empty() {
-            if let Err(e) = write!(writer, "/>") {
+            if let Err(e) = opts.indent(writer, depth) {
                 return wrap!(e);
-            } else {
-                // if let Err(e) = opts.newline(writer) {
-                //     return wrap!(e);
-                // }
-                return Ok(());
             }
         } else {
-            // if let Err(e) = opts.indent(writer, depth) {
-            //     return wrap!(e);
-            // }
-            if let Err(e) = write!(writer, ">") {
-                return wrap!(e);
-            }
-        }
-        // if let Err(e) = opts.newline(writer) {
-        //     return wrap!(e);
-        // }
-
-        for node in self.nodes.iter() {
-            if let Err(e) = node.write(writer, opts, depth + 1) {
-                // TODO - this may explode with recursive wrapping
-                return wrap!(e);
-            }
-        }
-
-        // Closing Tag
-        if let Err(e) = write!(writer, "</") {
-            return wrap!(e);
-        }
-        if let Some(ns) = &self.namespace {
-            if !ns.is_empty() {
-                if let Err(e) = write!(writer, "{}:", ns) {
+            // everything below mixed content is written inline, since whitespace added anywhere
+            // in it would become part of the text
+            for node in self.nodes.iter() {
+                if let Err(e) = node.write_node(writer, opts, depth + 1, false) {
                     return wrap!(e);
                 }
             }
         }
-        if let Err(e) = write!(writer, "{}", self.name) {
-            return wrap!(e);
-        }
-        if let Err(e) = write!(writer, ">") {
-            return wrap!(e);
-        }
-        if let Err(e) = opts.newline(writer) {
+
+        if let Err(e) = write!(writer, "</{}>", fullname) {
             return wrap!(e);
         }
         Ok(())
     }
+
+    /// Returns `true` if the element has child elements, comments or processing instructions, and
+    /// its only text is whitespace between them.
+    fn has_element_content(&self) -> bool {
+        let mut has_markup = false;
+        for node in self.nodes.iter() {
+            match node {
+                Node::String(s) if is_whitespace(s) => {}
+                Node::String(_) | Node::CData(_) => return false,
+                _ => has_markup = true,
+            }
+        }
+        has_markup
+    }
+
+    fn preserves_space(&self) -> bool {
+        self.attributes
+            .map()
+            .get("xml:space")
+            .is_some_and(|v| v == "preserve")
+    }
 }
 
 #[cfg(test)]
@@ -210,7 +221,7 @@ mod tests {
         let mut doc = Document::new();
         doc.root = ElementData {
             namespace: None,
-            name: "root-element".to_string(),
+            name: "root-element".into(),
             attributes: Default::default(),
             nodes: vec![],
         };
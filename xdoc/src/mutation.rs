@@ -0,0 +1,247 @@
+use crate::builder::split_name;
+use crate::error::Result;
+use crate::{ElementData, Node, Symbol};
+
+/// Checked edits of an element and its children, the owned counterparts of the `arena::Document`
+/// operations. A child is given by its index in `nodes`. Each operation leaves the element as it
+/// was when it returns an error.
+impl ElementData {
+    /// Inserts `node` before the child at `index`, or after the last child if `index` is the number
+    /// of children. A DOCTYPE cannot be a child, and the node must be writable as XML.
+    pub fn insert_child(&mut self, index: usize, node: Node) -> Result<()> {
+        if index > self.nodes.len() {
+            return raise!(
+                "Cannot insert at {}, the element has {} children.",
+                index,
+                self.nodes.len()
+            );
+        }
+        if let Err(e) = check_child(&node) {
+            return wrap!(e);
+        }
+        self.nodes.insert(index, node);
+        Ok(())
+    }
+
+    /// Removes the child at `index` and returns it.
+    pub fn remove_child(&mut self, index: usize) -> Result<Node> {
+        if let Err(e) = self.check_index(index) {
+            return wrap!(e);
+        }
+        Ok(self.nodes.remove(index))
+    }
+
+    /// Puts `node` in the place of the child at `index` and returns that child.
+    pub fn replace_child(&mut self, index: usize, node: Node) -> Result<Node> {
+        if let Err(e) = self.check_index(index) {
+            return wrap!(e);
+        }
+        if let Err(e) = check_child(&node) {
+            return wrap!(e);
+        }
+        Ok(std::mem::replace(&mut self.nodes[index], node))
+    }
+
+    /// Puts `element` in the place of the child at `index` and moves the child into it, after any
+    /// children `element` already has.
+    pub fn wrap_child(&mut self, index: usize, mut element: ElementData) -> Result<()> {
+        if let Err(e) = self.check_index(index) {
+            return wrap!(e);
+        }
+        if let Err(e) = check_element(&element) {
+            return wrap!(e);
+        }
+        let child = std::mem::replace(&mut self.nodes[index], Node::String(String::new()));
+        element.nodes.push(child);
+        self.nodes[index] = Node::Element(element);
+        Ok(())
+    }
+
+    /// Puts the children of the child element at `index` in its place, and returns the element
+    /// without them.
+    pub fn unwrap_child(&mut self, index: usize) -> Result<ElementData> {
+        if let Err(e) = self.check_index(index) {
+            return wrap!(e);
+        }
+        let mut element = match self.nodes.remove(index) {
+            Node::Element(element) => element,
+            node => {
+                self.nodes.insert(index, node);
+                return raise!("Only an element can be unwrapped.");
+            }
+        };
+        let children = std::mem::take(&mut element.nodes);
+        self.nodes.splice(index..index, children);
+        Ok(element)
+    }
+
+    /// Changes the element's name, which may have a namespace prefix, e.g. `"x:cat"`.
+    pub fn rename(&mut self, name: &str) -> Result<()> {
+        let (namespace, local) = match split_name(name) {
+            Ok(split) => split,
+            Err(e) => return wrap!(e),
+        };
+        self.namespace = namespace.map(Symbol::from);
+        self.name = local.into();
+        Ok(())
+    }
+
+    /// Sets the attribute with the qualified name `name`, returning its old value.
+    pub fn set_attribute(&mut self, name: &str, value: &str) -> Result<Option<String>> {
+        if let Err(e) = split_name(name) {
+            return wrap!(e);
+        }
+        Ok(self
+            .attributes
+            .mut_map()
+            .insert(name.into(), value.to_string()))
+    }
+
+    /// Removes the attribute with the qualified name `name`, returning its value.
+    pub fn remove_attribute(&mut self, name: &str) -> Option<String> {
+        self.attributes.mut_map().remove(name)
+    }
+
+    /// Joins each run of adjacent text nodes below the element into one, and removes empty text
+    /// nodes. CDATA sections are left as they are.
+    pub fn merge_text(&mut self) {
+        let mut merged: Vec<Node> = Vec::with_capacity(self.nodes.len());
+        for mut node in self.nodes.drain(..) {
+            match (&mut node, merged.last_mut()) {
+                (Node::String(text), _) if text.is_empty() => continue,
+                (Node::String(text), Some(Node::String(previous))) => {
+                    previous.push_str(text);
+                    continue;
+                }
+                (Node::Element(element), _) => element.merge_text(),
+                _ => {}
+            }
+            merged.push(node);
+        }
+        self.nodes = merged;
+    }
+
+    fn check_index(&self, index: usize) -> Result<()> {
+        if index >= self.nodes.len() {
+            return raise!(
+                "There is no child at {}, the element has {} children.",
+                index,
+                self.nodes.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Returns an error if the node, or anything below it, cannot be the child of an element.
+fn check_child(node: &Node) -> Result<()> {
+    match node {
+        Node::DocType(_) => raise!("A DOCTYPE cannot be the child of an element."),
+        Node::Element(element) => check_element(element),
+        node => node.check(),
+    }
+}
+
+fn check_element(element: &ElementData) -> Result<()> {
+    if let Err(e) = element.check() {
+        return wrap!(e);
+    }
+    for node in &element.nodes {
+        if let Err(e) = check_child(node) {
+            return wrap!(e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{xml, ElementData, Node};
+
+    fn cats() -> ElementData {
+        xml! { cats { cat(name = "bones"), "a", cat(name = "bishop") } }.unwrap()
+    }
+
+    fn names(element: &ElementData) -> Vec<String> {
+        element
+            .nodes
+            .iter()
+            .map(|node| match node {
+                Node::Element(e) => e.fullname(),
+                Node::String(s) => format!("'{}'", s),
+                _ => "#".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn insert_remove_replace() {
+        let mut cats = cats();
+        cats.insert_child(3, Node::Comment(" end ".to_string()))
+            .unwrap();
+        cats.insert_child(0, Node::String("b".to_string())).unwrap();
+        assert_eq!(names(&cats), vec!["'b'", "cat", "'a'", "cat", "#"]);
+        assert!(cats.insert_child(6, Node::String("c".to_string())).is_err());
+        assert!(cats
+            .insert_child(0, Node::DocType("cats".to_string()))
+            .is_err());
+        assert!(cats
+            .insert_child(0, Node::Comment("a--b".to_string()))
+            .is_err());
+        assert_eq!(
+            cats.remove_child(4).unwrap(),
+            Node::Comment(" end ".to_string())
+        );
+        assert!(cats.remove_child(4).is_err());
+        let old = cats.replace_child(0, Node::CData("c".to_string())).unwrap();
+        assert_eq!(old, Node::String("b".to_string()));
+        assert!(cats
+            .replace_child(9, Node::String("c".to_string()))
+            .is_err());
+        assert_eq!(names(&cats), vec!["#", "cat", "'a'", "cat"]);
+    }
+
+    #[test]
+    fn wrap_and_unwrap() {
+        let mut cats = cats();
+        let wrapper = xml! { ["x:pair"] { "first" } }.unwrap();
+        cats.wrap_child(1, wrapper).unwrap();
+        assert_eq!(names(&cats), vec!["cat", "x:pair", "cat"]);
+        assert_eq!(
+            names(cats.nodes[1].as_element().unwrap()),
+            vec!["'first'", "'a'"]
+        );
+        assert!(cats.unwrap_child(0).is_ok());
+        let pair = cats.unwrap_child(0).unwrap();
+        assert_eq!(pair.fullname(), "x:pair");
+        assert_eq!(names(&cats), vec!["'first'", "'a'", "cat"]);
+        assert!(cats.unwrap_child(0).is_err());
+        assert_eq!(names(&cats), vec!["'first'", "'a'", "cat"]);
+        assert!(cats.wrap_child(0, ElementData::default()).is_err());
+        let mut bad = xml! { pair }.unwrap();
+        bad.nodes.push(Node::Comment("a--b".to_string()));
+        assert!(cats.wrap_child(0, bad.clone()).is_err());
+        assert!(cats.insert_child(0, Node::Element(bad)).is_err());
+        assert!(cats.wrap_child(3, pair).is_err());
+    }
+
+    #[test]
+    fn rename_attributes_and_text() {
+        let mut cats = cats();
+        cats.rename("x:kitties").unwrap();
+        assert_eq!(cats.fullname(), "x:kitties");
+        assert!(cats.rename("a b").is_err());
+        assert_eq!(cats.fullname(), "x:kitties");
+        assert_eq!(cats.set_attribute("x:size", "3").unwrap(), None);
+        assert_eq!(cats.set_attribute("x:size", "4").unwrap().unwrap(), "3");
+        assert!(cats.set_attribute("1size", "4").is_err());
+        assert_eq!(cats.remove_attribute("x:size").unwrap(), "4");
+        assert_eq!(cats.remove_attribute("x:size"), None);
+
+        let mut text =
+            xml! { a { "", "b", "c", b { "d", "", "e" }, "f", @cdata("g"), "h" } }.unwrap();
+        text.merge_text();
+        let expected = xml! { a { "bc", b { "de" }, "f", @cdata("g"), "h" } }.unwrap();
+        assert_eq!(text, expected);
+    }
+}
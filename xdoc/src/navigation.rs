@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::slice;
+
+use crate::xpath::XML_NAMESPACE;
+use crate::{ElementData, Node};
+
+impl ElementData {
+    /// Returns `true` if the element's qualified name is `name`. A name with a prefix, like
+    /// `"x:cat"`, only matches an element with that namespace prefix, and a name without one only
+    /// matches an element without one. To match by namespace URI instead, see `is_named_ns`.
+    pub fn is_named(&self, name: &str) -> bool {
+        match (name.split_once(':'), &self.namespace) {
+            (Some((ns, local)), Some(namespace)) => namespace == ns && self.name == local,
+            (None, None) => self.name == name,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the element is in the namespace `uri`, or in no namespace for `None`, and
+    /// its local name is `local`. The element's prefix is resolved through its own `xmlns`
+    /// attributes and then `namespaces`, the declarations in scope where the element is, so the
+    /// prefix it happens to use does not matter. An element with an undeclared prefix never matches.
+    pub fn is_named_ns(&self, uri: Option<&str>, local: &str, namespaces: &Namespaces) -> bool {
+        if self.name != local {
+            return false;
+        }
+        let namespaces = namespaces.enter(self);
+        match (
+            self.namespace.as_deref(),
+            namespaces.uri(self.namespace.as_deref()),
+        ) {
+            (Some(_), None) => false,
+            (_, element_uri) => element_uri == uri,
+        }
+    }
+
+    /// Returns the first child element named `local` in the namespace `uri`, see `is_named_ns`.
+    /// `namespaces` are the declarations in scope where this element is.
+    pub fn first_child_named_ns(
+        &self,
+        uri: Option<&str>,
+        local: &str,
+        namespaces: &Namespaces,
+    ) -> Option<&ElementData> {
+        let namespaces = namespaces.enter(self);
+        self.children_elements()
+            .find(|e| e.is_named_ns(uri, local, &namespaces))
+    }
+
+    /// Returns the child elements named `local` in the namespace `uri`, see `is_named_ns`.
+    /// `namespaces` are the declarations in scope where this element is.
+    pub fn elements_named_ns<'a>(
+        &'a self,
+        uri: Option<&'a str>,
+        local: &'a str,
+        namespaces: &Namespaces,
+    ) -> impl Iterator<Item = &'a ElementData> {
+        let namespaces = namespaces.enter(self);
+        self.children_elements()
+            .filter(move |e| e.is_named_ns(uri, local, &namespaces))
+    }
+
+    /// Returns the value of the attribute with the qualified name `name`.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.map().get(name).map(|v| v.as_str())
+    }
+
+    /// Returns the element's children that are elements.
+    pub fn children_elements(&self) -> impl Iterator<Item = &ElementData> {
+        self.nodes.iter().filter_map(Node::as_element)
+    }
+
+    /// Returns the first child element named `name`, see `is_named`.
+    pub fn first_child_named(&self, name: &str) -> Option<&ElementData> {
+        self.children_elements().find(|e| e.is_named(name))
+    }
+
+    /// Returns the child elements named `name`, see `is_named`.
+    pub fn elements_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a ElementData> {
+        self.children_elements().filter(move |e| e.is_named(name))
+    }
+
+    /// Returns every node below the element, in document order.
+    pub fn descendants(&self) -> Descendants<'_> {
+        Descendants {
+            stack: vec![self.nodes.iter()],
+        }
+    }
+
+    /// Returns the text and CDATA of the element's children, concatenated.
+    pub fn text(&self) -> String {
+        self.nodes.iter().filter_map(Node::as_text).collect()
+    }
+
+    /// Returns the text and CDATA of every node below the element, concatenated in document order.
+    pub fn inner_text(&self) -> String {
+        self.descendants().filter_map(Node::as_text).collect()
+    }
+}
+
+impl Node {
+    pub fn as_element(&self) -> Option<&ElementData> {
+        match self {
+            Node::Element(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn as_element_mut(&mut self) -> Option<&mut ElementData> {
+        match self {
+            Node::Element(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Returns the text of a text or CDATA node.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Node::String(s) | Node::CData(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns every node below the node, in document order. Only elements have any.
+    pub fn descendants(&self) -> Descendants<'_> {
+        match self {
+            Node::Element(data) => data.descendants(),
+            _ => Descendants { stack: Vec::new() },
+        }
+    }
+}
+
+/// The namespace URIs that prefixes are bound to at some point in a document, by the `xmlns`
+/// attributes of the elements around it. The `xml` prefix is always bound.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Namespaces {
+    /// The URI of each declared prefix, with "" for the default namespace. An empty URI undeclares.
+    uris: HashMap<String, String>,
+}
+
+impl Namespaces {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the namespaces in scope inside `element`: these ones, updated with the element's own
+    /// declarations.
+    pub fn enter(&self, element: &ElementData) -> Namespaces {
+        let mut namespaces = self.clone();
+        for (name, uri) in element.attributes.map() {
+            if name.as_str() == "xmlns" {
+                namespaces.uris.insert(String::new(), uri.clone());
+            } else if let Some(prefix) = name.strip_prefix("xmlns:") {
+                namespaces.uris.insert(prefix.to_string(), uri.clone());
+            }
+        }
+        namespaces
+    }
+
+    /// Returns the URI bound to `prefix`, or the default namespace for `None`. Returns `None` if the
+    /// prefix is not declared, or for `None` if there is no default namespace.
+    pub fn uri(&self, prefix: Option<&str>) -> Option<&str> {
+        if prefix == Some("xml") {
+            return Some(XML_NAMESPACE);
+        }
+        self.uris
+            .get(prefix.unwrap_or(""))
+            .map(String::as_str)
+            .filter(|uri| !uri.is_empty())
+    }
+}
+
+/// An iterator over the nodes below an element, each node followed by its own descendants.
+#[derive(Debug, Clone)]
+pub struct Descendants<'a> {
+    /// The remaining siblings at each level, from the outermost to the current one.
+    stack: Vec<slice::Iter<'a, Node>>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let siblings = self.stack.last_mut()?;
+            match siblings.next() {
+                Some(node) => {
+                    if let Node::Element(data) = node {
+                        self.stack.push(data.nodes.iter());
+                    }
+                    return Some(node);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{map, xml, ElementData, Namespaces, Node, OrdMap};
+
+    fn element(namespace: Option<&str>, name: &str, nodes: Vec<Node>) -> ElementData {
+        ElementData {
+            namespace: namespace.map(|ns| ns.into()),
+            name: name.into(),
+            attributes: Default::default(),
+            nodes,
+        }
+    }
+
+    fn cats() -> ElementData {
+        let mut bones = element(None, "cat", vec![Node::String("bones".to_string())]);
+        bones.attributes = OrdMap::from(map! { "x:age".to_string() => "3".to_string() });
+        let bishop = element(
+            Some("x"),
+            "cat",
+            vec![
+                Node::String("bishop ".to_string()),
+                Node::Element(element(None, "b", vec![Node::CData("& co".to_string())])),
+                Node::Comment("punks".to_string()),
+            ],
+        );
+        element(
+            None,
+            "cats",
+            vec![
+                Node::Element(bones),
+                Node::String("!".to_string()),
+                Node::Element(bishop),
+            ],
+        )
+    }
+
+    #[test]
+    fn names_and_attributes() {
+        let cats = cats();
+        assert_eq!(cats.children_elements().count(), 2);
+        let bones = cats.first_child_named("cat").unwrap();
+        assert_eq!(bones.attribute("x:age"), Some("3"));
+        assert_eq!(bones.attribute("age"), None);
+        let bishop = cats.first_child_named("x:cat").unwrap();
+        assert!(bishop.is_named("x:cat"));
+        assert!(!bishop.is_named("cat"));
+        assert!(!bishop.is_named("y:cat"));
+        assert_eq!(cats.elements_named("cat").count(), 1);
+        assert!(cats.first_child_named("dog").is_none());
+    }
+
+    #[test]
+    fn descendants_and_text() {
+        let cats = cats();
+        let kinds = cats
+            .descendants()
+            .map(|n| match n {
+                Node::Element(e) => e.fullname(),
+                Node::String(s) | Node::CData(s) => s.clone(),
+                _ => "#".to_string(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec!["cat", "bones", "!", "x:cat", "bishop ", "b", "& co", "#"]
+        );
+        assert_eq!(cats.text(), "!");
+        assert_eq!(cats.inner_text(), "bones!bishop & co");
+        assert_eq!(Node::String("x".to_string()).descendants().count(), 0);
+    }
+
+    #[test]
+    fn names_with_namespaces() {
+        let cats = xml! {
+            cats("xmlns:x" = "urn:a", "xmlns" = "urn:d") {
+                ["x:cat"] { "bones" },
+                ["y:cat"]("xmlns:y" = "urn:a") { "bishop" },
+                cat { "jack" },
+                cat("xmlns" = "") { "punks" },
+                ["z:cat"] { "lost" },
+            }
+        }
+        .unwrap();
+        let outside = Namespaces::new();
+        let names = |uri: Option<&str>| {
+            cats.elements_named_ns(uri, "cat", &outside)
+                .map(|e| e.text())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(Some("urn:a")), vec!["bones", "bishop"]);
+        assert_eq!(names(Some("urn:d")), vec!["jack"]);
+        assert_eq!(names(None), vec!["punks"]);
+        assert!(cats.is_named_ns(Some("urn:d"), "cats", &outside));
+        assert!(!cats.is_named_ns(None, "cats", &outside));
+        let inside = outside.enter(&cats);
+        assert_eq!(inside.uri(Some("x")), Some("urn:a"));
+        assert_eq!(inside.uri(Some("z")), None);
+        assert_eq!(
+            inside.uri(Some("xml")),
+            Some("http://www.w3.org/XML/1998/namespace")
+        );
+        let bishop = cats.first_child_named_ns(Some("urn:a"), "cat", &outside);
+        assert_eq!(bishop.unwrap().text(), "bones");
+    }
+}
@@ -1,7 +1,9 @@
 use std::io::Write;
 
-use crate::doc::WriteOpts;
+use crate::chars::is_name;
+use crate::doc::{Format, WriteOpts};
 use crate::error::Result;
+use crate::escape::escape_text;
 
 #[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash)]
 #[cfg_attr(
@@ -40,27 +42,253 @@ impl Node {
     where
         W: Write,
     {
+        self.write_node(writer, opts, depth, opts.format == Format::Pretty)
+    }
+
+    /// Writes the node. The caller writes any newline and indentation that precede it, and
+    /// `pretty` is passed on to elements, see `ElementData::write_element`.
+    pub(crate) fn write_node<W>(
+        &self,
+        writer: &mut W,
+        opts: &WriteOpts,
+        depth: usize,
+        pretty: bool,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        if let Err(e) = self.check() {
+            return wrap!(e);
+        }
         match self {
-            Node::Element(data) => data.write(writer, opts, depth),
+            Node::Element(data) => data.write_element(writer, opts, depth, pretty),
             Node::String(s) => {
-                // TODO - escape string
-                if let Err(e) = write!(writer, "{}", s) {
+                if let Err(e) = write!(writer, "{}", escape_text(s)) {
                     return wrap!(e);
                 }
                 Ok(())
             }
-            Node::CData(_) => {
-                Ok(()) /*TODO - implement*/
+            // canonical xml has no cdata sections, only the text they hold
+            Node::CData(s) if opts.format == Format::Canonical => {
+                if let Err(e) = write!(writer, "{}", escape_text(s)) {
+                    return wrap!(e);
+                }
+                Ok(())
             }
-            Node::Comment(_) => {
-                Ok(()) /*TODO - implement*/
+            Node::CData(s) => {
+                if let Err(e) = write!(writer, "<![CDATA[{}]]>", s) {
+                    return wrap!(e);
+                }
+                Ok(())
+            }
+            Node::Comment(s) => {
+                if let Err(e) = write!(writer, "<!--{}-->", s) {
+                    return wrap!(e);
+                }
+                Ok(())
             }
-            Node::ProcessingInstruction(_) => {
-                Ok(()) /*TODO - implement*/
+            Node::ProcessingInstruction(pi) => {
+                if let Err(e) = write!(writer, "<?{}", pi.target) {
+                    return wrap!(e);
+                }
+                // sorted so that the output does not depend on the order of the hash map
+                let mut keys = pi.instructions.map().keys().collect::<Vec<_>>();
+                keys.sort();
+                for key in keys {
+                    let value = &pi.instructions.map()[key];
+                    // values cannot be escaped, so the other quote is used if need be
+                    let quote = match opts.quote_char() {
+                        '"' if value.contains('"') => '\'',
+                        '\'' if value.contains('\'') => '"',
+                        quote => quote,
+                    };
+                    if let Err(e) = write!(writer, " {}={}{}{}", key, quote, value, quote) {
+                        return wrap!(e);
+                    }
+                }
+                if let Err(e) = write!(writer, "?>") {
+                    return wrap!(e);
+                }
+                Ok(())
             }
-            Node::DocType(_) => {
-                Ok(()) /*TODO - implement*/
+            Node::DocType(s) => {
+                if let Err(e) = write!(writer, "<!DOCTYPE {}>", s) {
+                    return wrap!(e);
+                }
+                Ok(())
             }
         }
     }
+
+    /// Returns an error if the node cannot be written as well-formed XML.
+    pub(crate) fn check(&self) -> Result<()> {
+        match self {
+            Node::Element(_) | Node::String(_) => {}
+            Node::CData(s) => {
+                if s.contains("]]>") {
+                    return raise!("CDATA must not contain ']]>'.");
+                }
+            }
+            Node::Comment(s) => {
+                if s.contains("--") || s.ends_with('-') {
+                    return raise!("A comment must not contain '--' or end with '-'.");
+                }
+            }
+            Node::ProcessingInstruction(pi) => {
+                if pi.target.is_empty() {
+                    return raise!("Empty processing instruction target.");
+                }
+                if !is_name(&pi.target) {
+                    return raise!(
+                        "The processing instruction target '{}' is not an XML name.",
+                        pi.target
+                    );
+                }
+                if pi.target.eq_ignore_ascii_case("xml") {
+                    return raise!(
+                        "The processing instruction target '{}' is reserved.",
+                        pi.target
+                    );
+                }
+                for (key, value) in pi.instructions.map().iter() {
+                    if key.is_empty() {
+                        return raise!("Empty processing instruction key encountered.");
+                    }
+                    if !is_name(key) {
+                        return raise!(
+                            "The processing instruction key '{}' is not an XML name.",
+                            key
+                        );
+                    }
+                    if value.contains("?>") {
+                        return raise!(
+                            "The processing instruction value for '{}' contains '?>'.",
+                            key
+                        );
+                    }
+                    if value.contains('"') && value.contains('\'') {
+                        return raise!(
+                            "The processing instruction value for '{}' contains both quote characters.",
+                            key
+                        );
+                    }
+                }
+            }
+            Node::DocType(s) => {
+                if s.is_empty() {
+                    return raise!("Empty DOCTYPE.");
+                }
+                check_doc_type(s)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns an error unless the DOCTYPE content starts with the root element's name and can be
+/// written between `<!DOCTYPE ` and `>` without the declaration ending early or running on past
+/// the `>`. Quotes and the internal subset are tracked as the parser tracks them.
+fn check_doc_type(s: &str) -> Result<()> {
+    let name_end = s
+        .find(|c: char| c == '[' || c.is_whitespace())
+        .unwrap_or(s.len());
+    if !is_name(&s[..name_end]) {
+        return raise!("The DOCTYPE '{}' does not start with an XML name.", s);
+    }
+    let mut quote = None;
+    let mut in_subset = false;
+    for c in s[name_end..].chars() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '[') => in_subset = true,
+            (None, ']') => in_subset = false,
+            (None, '>') if !in_subset => {
+                return raise!("The DOCTYPE '{}' contains a '>' that would end it.", s);
+            }
+            _ => {}
+        }
+    }
+    if quote.is_some() || in_subset {
+        return raise!(
+            "The DOCTYPE '{}' has an unclosed quote or internal subset.",
+            s
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map, ElementData, OrdMap, PIData};
+
+    fn write(node: &Node) -> Result<String> {
+        let mut data = Vec::new();
+        node.write(&mut data, &WriteOpts::default(), 0)?;
+        Ok(String::from_utf8(data).unwrap())
+    }
+
+    fn pi(target: &str, key: &str, value: &str) -> Node {
+        Node::ProcessingInstruction(PIData {
+            target: target.to_string(),
+            instructions: OrdMap::from(map! { key.to_string() => value.to_string() }),
+        })
+    }
+
+    #[test]
+    fn write_non_element_nodes() {
+        let cdata = Node::CData("<raw> & ]]".to_string());
+        assert_eq!(write(&cdata).unwrap(), "<![CDATA[<raw> & ]]]]>");
+        let comment = Node::Comment(" a - b ".to_string());
+        assert_eq!(write(&comment).unwrap(), "<!-- a - b -->");
+        let pi_node = pi("cat", "says", "\"meow\"");
+        assert_eq!(write(&pi_node).unwrap(), "<?cat says='\"meow\"'?>");
+        let doc_type = Node::DocType("cats".to_string());
+        assert_eq!(write(&doc_type).unwrap(), "<!DOCTYPE cats>");
+    }
+
+    #[test]
+    fn indented_like_elements() {
+        let root = ElementData {
+            namespace: None,
+            name: "cats".into(),
+            attributes: Default::default(),
+            nodes: vec![
+                Node::Comment(" bones ".to_string()),
+                pi("cat", "name", "bones"),
+            ],
+        };
+        let mut data = Vec::new();
+        root.write(&mut data, &WriteOpts::default(), 0).unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "<cats>\n  <!-- bones -->\n  <?cat name=\"bones\"?>\n</cats>"
+        );
+    }
+
+    #[test]
+    fn invalid_nodes_are_rejected() {
+        assert!(write(&Node::CData("a]]>b".to_string())).is_err());
+        assert!(write(&Node::Comment("a--b".to_string())).is_err());
+        assert!(write(&Node::Comment("a-".to_string())).is_err());
+        assert!(write(&pi("cat", "says", "?>")).is_err());
+        assert!(write(&pi("cat", "says", "'\"")).is_err());
+        assert!(write(&pi("XML", "version", "1.0")).is_err());
+        assert!(write(&pi("", "a", "b")).is_err());
+        assert!(write(&Node::DocType(String::new())).is_err());
+        assert!(write(&pi("a b", "c", "d")).is_err());
+        assert!(write(&pi("cat", "says meow", "d")).is_err());
+        assert!(write(&pi("cat", "a=b", "d")).is_err());
+        assert!(write(&Node::DocType("cats> <a".to_string())).is_err());
+        assert!(write(&Node::DocType("cats [ <!ENTITY x 'y'> ".to_string())).is_err());
+        assert!(write(&Node::DocType("cats SYSTEM \"a.dtd".to_string())).is_err());
+        assert!(write(&Node::DocType("1cats".to_string())).is_err());
+        let doc_type = Node::DocType("cats [ <!ENTITY x \"y>\"> ]".to_string());
+        assert_eq!(
+            write(&doc_type).unwrap(),
+            "<!DOCTYPE cats [ <!ENTITY x \"y>\"> ]>"
+        );
+    }
 }
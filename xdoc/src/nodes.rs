@@ -4,7 +4,7 @@ use std::hash::{Hash, Hasher};
 
 use crate::node::Node;
 
-#[derive(Debug, Eq)]
+#[derive(Debug, Eq, Default)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -22,12 +22,6 @@ impl Clone for Nodes {
     }
 }
 
-impl Default for Nodes {
-    fn default() -> Nodes {
-        Nodes(VecDeque::new())
-    }
-}
-
 impl PartialEq for Nodes {
     fn eq(&self, other: &Self) -> bool {
         if self.0.len() != other.0.len() {
@@ -3,17 +3,20 @@ use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 
-// TODO - extract key and value types
+use ds::Symbol;
+
+// TODO - extract the value type
+#[derive(Default)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct OrdMap(HashMap<String, String>);
+pub struct OrdMap(HashMap<Symbol, String>);
 
 impl OrdMap {
     pub fn new() -> Self {
         OrdMap(HashMap::new())
     }
 
-    pub fn from(inner: HashMap<String, String>) -> Self {
-        OrdMap(inner)
+    pub fn from<K: Into<Symbol>>(inner: HashMap<K, String>) -> Self {
+        OrdMap(inner.into_iter().map(|(k, v)| (k.into(), v)).collect())
     }
 }
 
@@ -27,12 +30,6 @@ impl Clone for OrdMap {
     }
 }
 
-impl Default for OrdMap {
-    fn default() -> Self {
-        Self(HashMap::new())
-    }
-}
-
 impl PartialEq for OrdMap {
     fn eq(&self, other: &Self) -> bool {
         if self.0.len() != other.0.len() {
@@ -54,11 +51,11 @@ impl PartialEq for OrdMap {
 impl Eq for OrdMap {}
 
 impl OrdMap {
-    pub fn map(&self) -> &HashMap<String, String> {
+    pub fn map(&self) -> &HashMap<Symbol, String> {
         &self.0
     }
 
-    pub fn mut_map(&mut self) -> &mut HashMap<String, String> {
+    pub fn mut_map(&mut self) -> &mut HashMap<Symbol, String> {
         &mut self.0
     }
 
@@ -133,9 +130,38 @@ impl fmt::Debug for OrdMap {
 
 impl Hash for OrdMap {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        for (k, v) in self.0.iter() {
+        // sorted so that equal maps hash the same whatever order the hash map iterates in
+        let mut entries = self.0.iter().collect::<Vec<_>>();
+        entries.sort();
+        for (k, v) in entries {
             k.hash(state);
             v.hash(state);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+
+    use super::*;
+
+    fn hash(map: &OrdMap) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        map.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn equal_maps_hash_the_same() {
+        let mut a = OrdMap::new();
+        let mut b = OrdMap(HashMap::with_capacity(100));
+        for i in 0..50 {
+            a.mut_map().insert(format!("k{}", i).into(), i.to_string());
+            b.mut_map()
+                .insert(format!("k{}", 49 - i).into(), (49 - i).to_string());
+        }
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+    }
+}
@@ -0,0 +1,720 @@
+//! Patches that add, remove and replace nodes and attributes, in the manner of RFC 5261 (An
+//! Extensible Markup Language (XML) Patch Operations Framework Utilizing XML Path Language (XPath)
+//! Selectors).
+//!
+//! Each operation has a selector, an XPath expression that must select exactly one node, which is
+//! evaluated against the document as the earlier operations left it. A patch is applied as a whole:
+//! if any operation fails, the document is left as it was.
+//!
+//! A patch can be built in Rust or read from a patch document like the following, where the
+//! namespaces declared on the root element bind the prefixes used in the selectors.
+//!
+//! ```xml
+//! <diff xmlns:x="urn:x">
+//!   <add sel="/cats" pos="prepend"><cat name="jack"/></add>
+//!   <add sel="/cats/cat[@name='bones']" type="@age">3</add>
+//!   <replace sel="/cats/cat[@name='bishop']/@name">rook</replace>
+//!   <remove sel="/cats/x:dog" ws="before"/>
+//! </diff>
+//! ```
+//!
+//! ```
+//! use xdoc::patch::{Operation, Patch, Position};
+//! use xdoc::{xml, Document, Node};
+//!
+//! let mut doc = Document::from_root(
+//!     xml! { cats { cat(name = "bones"), cat(name = "bishop") } }.unwrap(),
+//! );
+//! let patch = Patch {
+//!     operations: vec![
+//!         Operation::Add {
+//!             sel: "/cats/cat[1]".to_string(),
+//!             pos: Position::After,
+//!             nodes: vec![Node::Element(xml! { cat(name = "jack") }.unwrap())],
+//!         },
+//!         Operation::Replace {
+//!             sel: "/cats/cat[@name = 'bishop']/@name".to_string(),
+//!             nodes: vec![Node::String("rook".to_string())],
+//!         },
+//!     ],
+//!     ..Patch::default()
+//! };
+//! patch.apply(&mut doc).unwrap();
+//! let names = doc
+//!     .root
+//!     .children_elements()
+//!     .map(|cat| cat.attribute("name").unwrap())
+//!     .collect::<Vec<_>>();
+//! assert_eq!(names, vec!["bones", "jack", "rook"]);
+//! ```
+
+use std::collections::HashMap;
+
+use crate::arena::{self, NodeId};
+use crate::error::Result;
+use crate::escape::is_whitespace;
+use crate::xpath::{NodeRef, Value, XPath, XPathOpts};
+use crate::{Document, ElementData, Node, OrdMap};
+
+/// A list of operations that are applied in order.
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub struct Patch {
+    /// Binds the namespace prefixes used in the selectors to namespace URIs.
+    pub namespaces: HashMap<String, String>,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Operation {
+    /// Adds the nodes in or next to the selected node.
+    Add {
+        sel: String,
+        pos: Position,
+        nodes: Vec<Node>,
+    },
+    /// Adds an attribute, which must not already be there, to the selected element.
+    AddAttribute {
+        sel: String,
+        name: String,
+        value: String,
+    },
+    /// Puts the nodes in the place of the selected node. An element is replaced by exactly one
+    /// element, a comment by a comment and a processing instruction by a processing instruction.
+    /// The value of a selected attribute, or a selected text node, is replaced by the text of the
+    /// nodes.
+    Replace { sel: String, nodes: Vec<Node> },
+    /// Removes the selected node or attribute, and the whitespace next to it that `ws` asks for.
+    Remove { sel: String, ws: Whitespace },
+}
+
+/// Where `Operation::Add` puts the nodes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Position {
+    /// After the last child of the selected node.
+    #[default]
+    Append,
+    /// Before the first child of the selected node.
+    Prepend,
+    Before,
+    After,
+}
+
+/// The whitespace text that `Operation::Remove` removes along with the selected node.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Whitespace {
+    #[default]
+    None,
+    Before,
+    After,
+    Both,
+}
+
+impl Patch {
+    /// Applies the operations to the document in order. If one of them fails, the error says which
+    /// and the document is not changed.
+    pub fn apply(&self, doc: &mut Document) -> Result<()> {
+        let mut arena = arena::Document::from(&*doc);
+        self.apply_arena(&mut arena)?;
+        *doc = arena.to_document()?;
+        Ok(())
+    }
+
+    /// Applies the operations to the arena document in order. Unlike `apply`, the operations before
+    /// a failed one are not undone.
+    pub fn apply_arena(&self, doc: &mut arena::Document) -> Result<()> {
+        let opts = XPathOpts {
+            namespaces: self.namespaces.clone(),
+            ..XPathOpts::default()
+        };
+        for (i, operation) in self.operations.iter().enumerate() {
+            if let Err(e) = apply_operation(doc, operation, &opts) {
+                return wrap!(e, "Operation {} of the patch failed.", i + 1);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a patch document. The root element's name is not checked, its children are `add`,
+    /// `replace` and `remove` elements, and the namespaces it declares bind the prefixes used in
+    /// the selectors.
+    pub fn from_document(doc: &Document) -> Result<Patch> {
+        let mut patch = Patch::default();
+        for (name, uri) in doc.root.attributes.map() {
+            if let Some(prefix) = name.strip_prefix("xmlns:") {
+                patch.namespaces.insert(prefix.to_string(), uri.clone());
+            }
+        }
+        for node in &doc.root.nodes {
+            match node {
+                Node::Element(element) => patch.operations.push(read_operation(element)?),
+                Node::String(s) if is_whitespace(s) => {}
+                Node::Comment(_) | Node::ProcessingInstruction(_) => {}
+                _ => return raise!("A patch document can only contain operation elements."),
+            }
+        }
+        Ok(patch)
+    }
+
+    /// Writes the patch as a patch document with a `diff` root element.
+    pub fn to_document(&self) -> Document {
+        let mut attributes = OrdMap::new();
+        for (prefix, uri) in &self.namespaces {
+            attributes
+                .mut_map()
+                .insert(format!("xmlns:{}", prefix).into(), uri.clone());
+        }
+        Document::from_root(ElementData {
+            namespace: None,
+            name: "diff".into(),
+            attributes,
+            nodes: self
+                .operations
+                .iter()
+                .map(|operation| Node::Element(write_operation(operation)))
+                .collect(),
+        })
+    }
+}
+
+fn apply_operation(
+    doc: &mut arena::Document,
+    operation: &Operation,
+    opts: &XPathOpts,
+) -> Result<()> {
+    match operation {
+        Operation::Add { sel, pos, nodes } => {
+            let target = select_node(doc, sel, opts)?;
+            let ids = nodes
+                .iter()
+                .map(|node| doc.create_from(node))
+                .collect::<Vec<_>>();
+            match pos {
+                Position::Append => {
+                    for id in ids {
+                        doc.append(target, id)?;
+                    }
+                }
+                Position::Prepend => {
+                    let first = doc.first_child(target);
+                    for id in ids {
+                        match first {
+                            Some(first) => doc.insert_before(first, id)?,
+                            None => doc.append(target, id)?,
+                        }
+                    }
+                }
+                Position::Before => {
+                    for id in ids {
+                        doc.insert_before(target, id)?;
+                    }
+                }
+                Position::After => {
+                    // after the whole text node, if that is what is selected
+                    let mut previous = text_run(doc, target).pop().unwrap_or(target);
+                    for id in ids {
+                        doc.insert_after(previous, id)?;
+                        previous = id;
+                    }
+                }
+            }
+        }
+        Operation::AddAttribute { sel, name, value } => {
+            let target = select_node(doc, sel, opts)?;
+            let exists = match doc.element(target) {
+                Some(element) => element.attributes.map().contains_key(name.as_str()),
+                None => return raise!("'{}' does not select an element.", sel),
+            };
+            if exists {
+                return raise!("The element selected by '{}' already has '{}'.", sel, name);
+            }
+            doc.set_attribute(target, name, value)?;
+        }
+        Operation::Replace { sel, nodes } => match select(doc, sel, opts)? {
+            NodeRef::Attribute(id, name) => {
+                doc.set_attribute(id, &name, &text(nodes)?)?;
+            }
+            NodeRef::Node(id) => replace(doc, id, nodes)?,
+            NodeRef::Namespace(..) => return raise!("A namespace node cannot be replaced."),
+        },
+        Operation::Remove { sel, ws } => match select(doc, sel, opts)? {
+            NodeRef::Attribute(id, name) => {
+                doc.remove_attribute(id, &name)?;
+            }
+            NodeRef::Node(id) => remove(doc, id, *ws)?,
+            NodeRef::Namespace(..) => return raise!("A namespace node cannot be removed."),
+        },
+    }
+    Ok(())
+}
+
+/// Returns the one node that the selector selects.
+fn select(doc: &arena::Document, sel: &str, opts: &XPathOpts) -> Result<NodeRef> {
+    let xpath = match XPath::parse(sel) {
+        Ok(xpath) => xpath,
+        Err(e) => return wrap!(e, "Bad selector '{}'.", sel),
+    };
+    let value = match xpath.evaluate(doc, opts) {
+        Ok(value) => value,
+        Err(e) => return wrap!(e, "Selector '{}' failed.", sel),
+    };
+    match value {
+        Value::Nodes(mut nodes) if nodes.len() == 1 => Ok(nodes.remove(0)),
+        Value::Nodes(nodes) => raise!("'{}' selects {} nodes, not one.", sel, nodes.len()),
+        _ => raise!("'{}' does not select nodes.", sel),
+    }
+}
+
+/// Returns the one node, not an attribute or a namespace, that the selector selects.
+fn select_node(doc: &arena::Document, sel: &str, opts: &XPathOpts) -> Result<NodeId> {
+    match select(doc, sel, opts)? {
+        NodeRef::Node(id) => Ok(id),
+        _ => raise!("'{}' selects an attribute or a namespace, not a node.", sel),
+    }
+}
+
+/// The arena nodes that make up the XPath text node that starts at `id`, which is empty if `id` is
+/// not text.
+fn text_run(doc: &arena::Document, id: NodeId) -> Vec<NodeId> {
+    let is_text = |id: &NodeId| {
+        matches!(
+            doc.node(*id),
+            arena::Node::String(_) | arena::Node::CData(_)
+        )
+    };
+    if !is_text(&id) {
+        return Vec::new();
+    }
+    std::iter::once(id)
+        .chain(doc.following_siblings(id).take_while(is_text))
+        .collect()
+}
+
+/// The text of the nodes, which may only be text and CDATA.
+fn text(nodes: &[Node]) -> Result<String> {
+    let mut text = String::new();
+    for node in nodes {
+        match node {
+            Node::String(s) | Node::CData(s) => text.push_str(s),
+            _ => return raise!("An attribute or a text node can only be replaced by text."),
+        }
+    }
+    Ok(text)
+}
+
+fn replace(doc: &mut arena::Document, id: NodeId, nodes: &[Node]) -> Result<()> {
+    let kept = nodes
+        .iter()
+        .filter(|node| !matches!(node, Node::String(s) if is_whitespace(s)))
+        .collect::<Vec<_>>();
+    let node = match doc.node(id) {
+        arena::Node::Document => return raise!("The document node cannot be replaced."),
+        arena::Node::String(_) | arena::Node::CData(_) => {
+            text(nodes)?;
+            let run = text_run(doc, id);
+            for node in nodes {
+                let new = doc.create_from(node);
+                doc.insert_before(id, new)?;
+            }
+            for old in run {
+                doc.remove(old)?;
+            }
+            return Ok(());
+        }
+        arena::Node::Element(_) => match kept.as_slice() {
+            [node @ Node::Element(_)] => *node,
+            _ => return raise!("An element can only be replaced by one element."),
+        },
+        arena::Node::Comment(_) => match kept.as_slice() {
+            [node @ Node::Comment(_)] => *node,
+            _ => return raise!("A comment can only be replaced by one comment."),
+        },
+        arena::Node::ProcessingInstruction(_) => match kept.as_slice() {
+            [node @ Node::ProcessingInstruction(_)] => *node,
+            _ => {
+                return raise!(
+                    "A processing instruction can only be replaced by one processing instruction."
+                )
+            }
+        },
+        arena::Node::DocType(_) => return raise!("A DOCTYPE cannot be selected."),
+    };
+    let new = doc.create_from(node);
+    doc.replace_with(id, new)
+}
+
+fn remove(doc: &mut arena::Document, id: NodeId, ws: Whitespace) -> Result<()> {
+    if id == doc.document_node() {
+        return raise!("The document node cannot be removed.");
+    }
+    let is_whitespace_text =
+        |id: &NodeId| matches!(doc.node(*id), arena::Node::String(s) if is_whitespace(s));
+    let mut removed = text_run(doc, id);
+    if removed.is_empty() {
+        removed.push(id);
+    }
+    if matches!(ws, Whitespace::Before | Whitespace::Both) {
+        let before = doc
+            .preceding_siblings(id)
+            .take_while(is_whitespace_text)
+            .collect::<Vec<_>>();
+        if before.is_empty() {
+            return raise!("There is no whitespace text before the node to remove.");
+        }
+        removed.extend(before);
+    }
+    if matches!(ws, Whitespace::After | Whitespace::Both) {
+        let last = text_run(doc, id).pop().unwrap_or(id);
+        let after = doc
+            .following_siblings(last)
+            .take_while(is_whitespace_text)
+            .collect::<Vec<_>>();
+        if after.is_empty() {
+            return raise!("There is no whitespace text after the node to remove.");
+        }
+        removed.extend(after);
+    }
+    for id in removed {
+        doc.remove(id)?;
+    }
+    Ok(())
+}
+
+fn read_operation(element: &ElementData) -> Result<Operation> {
+    let sel = match element.attribute("sel") {
+        Some(sel) => sel.to_string(),
+        None => {
+            return raise!(
+                "The '{}' operation has no 'sel' attribute.",
+                element.fullname()
+            )
+        }
+    };
+    let nodes = element.nodes.clone();
+    let operation = match element.fullname().as_str() {
+        "add" => match (element.attribute("type"), element.attribute("pos")) {
+            (Some(kind), _) => match kind.strip_prefix('@') {
+                Some(name) => Operation::AddAttribute {
+                    sel,
+                    name: name.to_string(),
+                    value: text(&nodes)?,
+                },
+                None => return raise!("Adding '{}' is not supported.", kind),
+            },
+            (None, pos) => Operation::Add {
+                sel,
+                pos: match pos {
+                    None => Position::Append,
+                    Some("prepend") => Position::Prepend,
+                    Some("before") => Position::Before,
+                    Some("after") => Position::After,
+                    Some(pos) => return raise!("Unknown position '{}'.", pos),
+                },
+                nodes,
+            },
+        },
+        "replace" => Operation::Replace { sel, nodes },
+        "remove" => Operation::Remove {
+            sel,
+            ws: match element.attribute("ws") {
+                None => Whitespace::None,
+                Some("before") => Whitespace::Before,
+                Some("after") => Whitespace::After,
+                Some("both") => Whitespace::Both,
+                Some(ws) => return raise!("Unknown whitespace directive '{}'.", ws),
+            },
+        },
+        name => return raise!("Unknown patch operation '{}'.", name),
+    };
+    Ok(operation)
+}
+
+fn write_operation(operation: &Operation) -> ElementData {
+    let mut attributes = HashMap::new();
+    let (name, sel, nodes) = match operation {
+        Operation::Add { sel, pos, nodes } => {
+            let pos = match pos {
+                Position::Append => None,
+                Position::Prepend => Some("prepend"),
+                Position::Before => Some("before"),
+                Position::After => Some("after"),
+            };
+            if let Some(pos) = pos {
+                attributes.insert("pos", pos.to_string());
+            }
+            ("add", sel, nodes.clone())
+        }
+        Operation::AddAttribute { sel, name, value } => {
+            attributes.insert("type", format!("@{}", name));
+            ("add", sel, vec![Node::String(value.clone())])
+        }
+        Operation::Replace { sel, nodes } => ("replace", sel, nodes.clone()),
+        Operation::Remove { sel, ws } => {
+            let ws = match ws {
+                Whitespace::None => None,
+                Whitespace::Before => Some("before"),
+                Whitespace::After => Some("after"),
+                Whitespace::Both => Some("both"),
+            };
+            if let Some(ws) = ws {
+                attributes.insert("ws", ws.to_string());
+            }
+            ("remove", sel, Vec::new())
+        }
+    };
+    attributes.insert("sel", sel.clone());
+    ElementData {
+        namespace: None,
+        name: name.into(),
+        attributes: OrdMap::from(attributes),
+        nodes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml;
+
+    fn cats() -> Document {
+        Document::from_root(
+            xml! {
+                cats("xmlns:x" = "urn:x") {
+                    "\n  ",
+                    cat(name = "bones") { "big", @cdata(" cat") },
+                    "\n  ",
+                    ["x:dog"](name = "rex"),
+                    "\n  ",
+                    @comment(" more cats "),
+                    "\n",
+                }
+            }
+            .unwrap(),
+        )
+    }
+
+    fn operation(operation: Operation) -> Patch {
+        Patch {
+            namespaces: vec![("d".to_string(), "urn:x".to_string())]
+                .into_iter()
+                .collect(),
+            operations: vec![operation],
+        }
+    }
+
+    fn patched(operation: Operation) -> Result<String> {
+        let mut doc = cats();
+        self::operation(operation).apply(&mut doc)?;
+        doc.to_string_opts(&crate::WriteOpts {
+            format: crate::Format::Compact,
+            ..crate::WriteOpts::default()
+        })
+    }
+
+    fn element(name: &str) -> Node {
+        Node::Element(ElementData {
+            name: name.into(),
+            ..ElementData::default()
+        })
+    }
+
+    #[test]
+    fn add_in_every_position() {
+        let add = |sel: &str, pos| Operation::Add {
+            sel: sel.to_string(),
+            pos,
+            nodes: vec![element("a"), element("b")],
+        };
+        let compact = |s: &str| {
+            format!(
+                "<cats xmlns:x=\"urn:x\">\n  {}\n  <x:dog name=\"rex\"/>\n  <!-- more cats -->\n</cats>",
+                s
+            )
+        };
+        assert_eq!(
+            patched(add("/cats/cat", Position::Append)).unwrap(),
+            compact("<cat name=\"bones\">big<![CDATA[ cat]]><a/><b/></cat>")
+        );
+        assert_eq!(
+            patched(add("/cats/cat", Position::Prepend)).unwrap(),
+            compact("<cat name=\"bones\"><a/><b/>big<![CDATA[ cat]]></cat>")
+        );
+        assert_eq!(
+            patched(add("/cats/cat", Position::Before)).unwrap(),
+            compact("<a/><b/><cat name=\"bones\">big<![CDATA[ cat]]></cat>")
+        );
+        assert_eq!(
+            patched(add("/cats/cat/text()", Position::After)).unwrap(),
+            compact("<cat name=\"bones\">big<![CDATA[ cat]]><a/><b/></cat>")
+        );
+        let err = patched(add("/cats/cat/text()", Position::Append));
+        assert!(err.is_err());
+        let err = patched(add("/cats/*", Position::Append)).unwrap_err();
+        assert!(format!("{}", err.source.unwrap()).contains("selects 2 nodes"));
+    }
+
+    #[test]
+    fn add_attribute() {
+        let add = |name: &str| Operation::AddAttribute {
+            sel: "/cats/d:dog".to_string(),
+            name: name.to_string(),
+            value: "3".to_string(),
+        };
+        assert!(patched(add("age"))
+            .unwrap()
+            .contains("<x:dog age=\"3\" name=\"rex\"/>"));
+        assert!(patched(add("name")).is_err());
+    }
+
+    #[test]
+    fn replace_nodes_and_values() {
+        let replace = |sel: &str, nodes: Vec<Node>| Operation::Replace {
+            sel: sel.to_string(),
+            nodes,
+        };
+        assert!(patched(replace(
+            "/cats/d:dog/@name",
+            vec![Node::String("max".to_string())]
+        ))
+        .unwrap()
+        .contains("<x:dog name=\"max\"/>"));
+        assert!(patched(replace(
+            "/cats/cat/text()",
+            vec![Node::String("small".to_string())]
+        ))
+        .unwrap()
+        .contains("<cat name=\"bones\">small</cat>"));
+        assert!(patched(replace(
+            "/cats/d:dog",
+            vec![Node::String("\n".to_string()), element("wolf")]
+        ))
+        .unwrap()
+        .contains("\n  <wolf/>\n"));
+        assert!(patched(replace(
+            "/cats/comment()",
+            vec![Node::Comment("cats".to_string())]
+        ))
+        .unwrap()
+        .contains("<!--cats-->"));
+        assert!(patched(replace("/cats/d:dog", vec![element("a"), element("b")])).is_err());
+        assert!(patched(replace("/cats/comment()", vec![element("a")])).is_err());
+        assert!(patched(replace("/cats/@name", vec![element("a")])).is_err());
+    }
+
+    #[test]
+    fn remove_with_whitespace() {
+        let remove = |sel: &str, ws| Operation::Remove {
+            sel: sel.to_string(),
+            ws,
+        };
+        assert_eq!(
+            patched(remove("/cats/cat", Whitespace::Both)).unwrap(),
+            "<cats xmlns:x=\"urn:x\"><x:dog name=\"rex\"/>\n  <!-- more cats -->\n</cats>"
+        );
+        assert_eq!(
+            patched(remove("/cats/comment()", Whitespace::Before)).unwrap(),
+            "<cats xmlns:x=\"urn:x\">\n  <cat name=\"bones\">big<![CDATA[ cat]]></cat>\n  <x:dog name=\"rex\"/>\n</cats>"
+        );
+        assert!(patched(remove("/cats/cat/text()", Whitespace::None))
+            .unwrap()
+            .contains("<cat name=\"bones\"/>"));
+        assert!(patched(remove("/cats/d:dog/@name", Whitespace::None))
+            .unwrap()
+            .contains("<x:dog/>"));
+        assert!(patched(remove("/cats/cat/text()", Whitespace::After)).is_err());
+        assert!(patched(remove("/cats", Whitespace::None)).is_err());
+    }
+
+    #[test]
+    fn failed_patch_leaves_document_unchanged() {
+        let mut doc = cats();
+        let patch = Patch {
+            operations: vec![
+                Operation::Remove {
+                    sel: "/cats/cat".to_string(),
+                    ws: Whitespace::None,
+                },
+                Operation::Remove {
+                    sel: "/cats/cat".to_string(),
+                    ws: Whitespace::None,
+                },
+            ],
+            ..Patch::default()
+        };
+        let err = patch.apply(&mut doc).unwrap_err();
+        assert!(err.message.contains("Operation 2"));
+        assert_eq!(doc, cats());
+    }
+
+    #[test]
+    fn patch_document_round_trip() {
+        let patch = Patch {
+            namespaces: vec![("x".to_string(), "urn:x".to_string())]
+                .into_iter()
+                .collect(),
+            operations: vec![
+                Operation::Add {
+                    sel: "/cats".to_string(),
+                    pos: Position::Prepend,
+                    nodes: vec![element("a")],
+                },
+                Operation::AddAttribute {
+                    sel: "/cats/x:dog".to_string(),
+                    name: "age".to_string(),
+                    value: "3".to_string(),
+                },
+                Operation::Replace {
+                    sel: "/cats/x:dog/@name".to_string(),
+                    nodes: vec![Node::String("max".to_string())],
+                },
+                Operation::Remove {
+                    sel: "/cats/cat".to_string(),
+                    ws: Whitespace::After,
+                },
+            ],
+        };
+        let doc = patch.to_document();
+        assert_eq!(
+            doc.to_string(),
+            r#"<diff xmlns:x="urn:x">
+  <add pos="prepend" sel="/cats">
+    <a/>
+  </add>
+  <add sel="/cats/x:dog" type="@age">3</add>
+  <replace sel="/cats/x:dog/@name">max</replace>
+  <remove sel="/cats/cat" ws="after"/>
+</diff>"#
+        );
+        assert_eq!(Patch::from_document(&doc).unwrap(), patch);
+    }
+
+    #[test]
+    fn bad_patch_documents() {
+        let read = |root: Result<ElementData>| Patch::from_document(&Document::from_root(root?));
+        assert!(read(xml! { diff { add } }).is_err());
+        assert!(read(xml! { diff { move(sel = "/a") } }).is_err());
+        assert!(read(xml! { diff { add(sel = "/a", pos = "under") } }).is_err());
+        assert!(read(xml! { diff { add(sel = "/a", type = "namespace::x") } }).is_err());
+        assert!(read(xml! { diff { remove(sel = "/a", ws = "around") } }).is_err());
+        assert!(read(xml! { diff { "text" } }).is_err());
+    }
+}
@@ -0,0 +1,711 @@
+use std::rc::Rc;
+
+use crate::error::Result;
+use crate::{Document, ElementData};
+
+impl Document {
+    /// Returns the elements that match a CSS selector, in document order.
+    ///
+    /// The supported selectors are type selectors (`cat`, `*`, and `x|cat` for the `x:` prefix),
+    /// attribute selectors (`[a]`, `[a="b"]`, `[a^="b"]` and `[a*="b"]`), the descendant, child
+    /// (`>`), next sibling (`+`) and subsequent sibling (`~`) combinators, `:nth-child(an+b)` and
+    /// `:not(...)`, and lists separated by commas. As in CSS, a type selector without a prefix
+    /// matches an element with any prefix, and `|cat` matches only an element without one.
+    ///
+    /// ```
+    /// use xdoc::{xml, Document};
+    ///
+    /// let doc = Document::from_root(
+    ///     xml! {
+    ///         cats {
+    ///             cat(name = "bones"),
+    ///             ["x:cat"](name = "bishop") { kitten(name = "punks") },
+    ///             cat(name = "jack"),
+    ///         }
+    ///     }
+    ///     .unwrap(),
+    /// );
+    /// let names = |selector: &str| {
+    ///     doc.select(selector)
+    ///         .unwrap()
+    ///         .map(|e| e.attribute("name").unwrap())
+    ///         .collect::<Vec<_>>()
+    /// };
+    /// assert_eq!(names("cats > cat"), vec!["bones", "bishop", "jack"]);
+    /// assert_eq!(names("x|cat ~ *, kitten"), vec!["punks", "jack"]);
+    /// assert_eq!(names("cat:nth-child(odd):not([name^='b'])"), vec!["jack"]);
+    /// ```
+    pub fn select(&self, selector: &str) -> Result<Select<'_>> {
+        Ok(Select {
+            selector: parse(selector)?,
+            chain: Vec::new(),
+            levels: vec![Level {
+                elements: Rc::new([&self.root]),
+                next: 0,
+            }],
+        })
+    }
+}
+
+impl ElementData {
+    /// Returns the elements below this one that match a CSS selector, in document order, see
+    /// `Document::select`. The element itself is never returned, but it can match the left side of
+    /// a combinator, so `cats > cat` selects the children of a `cats` element. Its siblings are not
+    /// known, so it never matches `:nth-child` or the left side of a sibling combinator.
+    pub fn select(&self, selector: &str) -> Result<Select<'_>> {
+        Ok(Select {
+            selector: parse(selector)?,
+            chain: vec![Entry {
+                element: self,
+                index: 0,
+                siblings: None,
+            }],
+            levels: vec![Level {
+                elements: self.children_elements().collect(),
+                next: 0,
+            }],
+        })
+    }
+}
+
+/// An iterator over the elements that match a CSS selector, see `Document::select`.
+#[derive(Debug, Clone)]
+pub struct Select<'a> {
+    selector: Vec<Complex>,
+    /// The element being visited and its ancestors, from the top down.
+    chain: Vec<Entry<'a>>,
+    /// The elements at each level of the chain that are still to be visited.
+    levels: Vec<Level<'a>>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry<'a> {
+    element: &'a ElementData,
+    // among the element children of its parent, from 0
+    index: usize,
+    // the element children of its parent, or None if they are not known
+    siblings: Option<Rc<[&'a ElementData]>>,
+}
+
+#[derive(Debug, Clone)]
+struct Level<'a> {
+    elements: Rc<[&'a ElementData]>,
+    next: usize,
+}
+
+impl<'a> Iterator for Select<'a> {
+    type Item = &'a ElementData;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let level = self.levels.last_mut()?;
+            if level.next == level.elements.len() {
+                // done with the children of the element at the end of the chain
+                self.levels.pop();
+                self.chain.pop();
+                continue;
+            }
+            let element = level.elements[level.next];
+            self.chain.push(Entry {
+                element,
+                index: level.next,
+                siblings: Some(level.elements.clone()),
+            });
+            level.next += 1;
+            self.levels.push(Level {
+                elements: element.children_elements().collect(),
+                next: 0,
+            });
+            let (entry, ancestors) = self.chain.split_last()?;
+            if self.selector.iter().any(|c| c.matches(ancestors, entry)) {
+                return Some(element);
+            }
+        }
+    }
+}
+
+/// Compound selectors joined by combinators, e.g. `a > b c`.
+#[derive(Debug, Clone, PartialEq)]
+struct Complex {
+    compounds: Vec<Compound>,
+    // combinators[i] is between compounds[i] and compounds[i + 1]
+    combinators: Vec<Combinator>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+    NextSibling,
+    SubsequentSibling,
+}
+
+/// Simple selectors that must all match one element, e.g. `cat[name]:nth-child(2)`.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Compound {
+    simple: Vec<Simple>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Simple {
+    // a prefix of None matches any prefix, Some("") matches no prefix, and a name of None is '*'
+    Type {
+        prefix: Option<String>,
+        name: Option<String>,
+    },
+    // the qualified attribute name
+    Attribute {
+        name: String,
+        test: Option<(AttributeOp, String)>,
+    },
+    NthChild {
+        a: i64,
+        b: i64,
+    },
+    Not(Vec<Complex>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttributeOp {
+    Equals,
+    StartsWith,
+    Contains,
+}
+
+impl Complex {
+    /// Returns `true` if the selector matches the element of `entry`, whose ancestors are
+    /// `ancestors`, from the top down.
+    fn matches(&self, ancestors: &[Entry<'_>], entry: &Entry<'_>) -> bool {
+        self.matches_from(self.compounds.len() - 1, ancestors, entry)
+    }
+
+    fn matches_from(&self, i: usize, ancestors: &[Entry<'_>], entry: &Entry<'_>) -> bool {
+        if !self.compounds[i].matches(ancestors, entry) {
+            return false;
+        }
+        if i == 0 {
+            return true;
+        }
+        match self.combinators[i - 1] {
+            Combinator::Child => match ancestors.split_last() {
+                Some((parent, rest)) => self.matches_from(i - 1, rest, parent),
+                None => false,
+            },
+            Combinator::Descendant => (0..ancestors.len())
+                .rev()
+                .any(|k| self.matches_from(i - 1, &ancestors[..k], &ancestors[k])),
+            Combinator::NextSibling => entry
+                .index
+                .checked_sub(1)
+                .and_then(|index| sibling(entry, index))
+                .is_some_and(|sibling| self.matches_from(i - 1, ancestors, &sibling)),
+            Combinator::SubsequentSibling => (0..entry.index)
+                .filter_map(|index| sibling(entry, index))
+                .any(|sibling| self.matches_from(i - 1, ancestors, &sibling)),
+        }
+    }
+}
+
+/// Returns the entry for the sibling at `index` of the element of `entry`.
+fn sibling<'a>(entry: &Entry<'a>, index: usize) -> Option<Entry<'a>> {
+    let siblings = entry.siblings.as_ref()?;
+    Some(Entry {
+        element: siblings.get(index)?,
+        index,
+        siblings: Some(siblings.clone()),
+    })
+}
+
+impl Compound {
+    fn matches(&self, ancestors: &[Entry<'_>], entry: &Entry<'_>) -> bool {
+        let element = entry.element;
+        self.simple.iter().all(|simple| match simple {
+            Simple::Type { prefix, name } => {
+                let prefix_matches = match prefix.as_deref() {
+                    None => true,
+                    Some("") => element.namespace.is_none(),
+                    Some(prefix) => element.namespace.as_deref() == Some(prefix),
+                };
+                prefix_matches && name.as_ref().is_none_or(|name| *name == *element.name)
+            }
+            Simple::Attribute { name, test } => match (element.attribute(name), test) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(value), Some((op, wanted))) => match op {
+                    AttributeOp::Equals => value == wanted,
+                    // an empty string matches nothing, as in CSS
+                    AttributeOp::StartsWith => !wanted.is_empty() && value.starts_with(wanted),
+                    AttributeOp::Contains => !wanted.is_empty() && value.contains(wanted.as_str()),
+                },
+            },
+            Simple::NthChild { .. } if entry.siblings.is_none() => false,
+            Simple::NthChild { a, b } => {
+                let position = entry.index as i64 + 1;
+                match a {
+                    0 => position == *b,
+                    a => (position - b) % a == 0 && (position - b) / a >= 0,
+                }
+            }
+            Simple::Not(selectors) => !selectors.iter().any(|c| c.matches(ancestors, entry)),
+        })
+    }
+}
+
+fn parse(selector: &str) -> Result<Vec<Complex>> {
+    let mut parser = Parser {
+        chars: selector.chars().collect(),
+        pos: 0,
+    };
+    let list = parser.list()?;
+    match parser.peek() {
+        None => Ok(list),
+        Some(c) => raise!("Unexpected '{}' in selector '{}'.", c, selector),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(found) => raise!("Expected '{}' but found '{}' in selector.", c, found),
+            None => raise!("Expected '{}' at the end of the selector.", c),
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    fn list(&mut self) -> Result<Vec<Complex>> {
+        let mut list = Vec::new();
+        loop {
+            self.skip_whitespace();
+            list.push(self.complex()?);
+            self.skip_whitespace();
+            if !self.eat(',') {
+                return Ok(list);
+            }
+        }
+    }
+
+    fn complex(&mut self) -> Result<Complex> {
+        let mut complex = Complex {
+            compounds: vec![self.compound()?],
+            combinators: Vec::new(),
+        };
+        loop {
+            let whitespace = self.skip_whitespace();
+            let combinator = match self.peek() {
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::SubsequentSibling,
+                None | Some(',') | Some(')') => return Ok(complex),
+                Some(_) if whitespace => Combinator::Descendant,
+                Some(c) => return raise!("Unexpected '{}' in selector.", c),
+            };
+            if combinator != Combinator::Descendant {
+                self.pos += 1;
+                self.skip_whitespace();
+            }
+            complex.combinators.push(combinator);
+            complex.compounds.push(self.compound()?);
+        }
+    }
+
+    fn compound(&mut self) -> Result<Compound> {
+        let mut compound = Compound::default();
+        if let Some(simple) = self.type_selector()? {
+            compound.simple.push(simple);
+        }
+        loop {
+            match self.peek() {
+                Some('[') => compound.simple.push(self.attribute()?),
+                Some(':') => compound.simple.push(self.pseudo_class()?),
+                _ => break,
+            }
+        }
+        if compound.simple.is_empty() {
+            return match self.peek() {
+                Some(c) => raise!("Expected a selector but found '{}'.", c),
+                None => raise!("Expected a selector at the end of the selector."),
+            };
+        }
+        Ok(compound)
+    }
+
+    fn type_selector(&mut self) -> Result<Option<Simple>> {
+        let first = match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                None
+            }
+            Some('|') => Some(String::new()),
+            _ => match self.identifier() {
+                Some(name) => Some(name),
+                None => return Ok(None),
+            },
+        };
+        if !self.eat('|') {
+            return Ok(Some(Simple::Type {
+                prefix: None,
+                name: first,
+            }));
+        }
+        // what came before the '|' was the prefix, where '*' is any prefix
+        let name = if self.eat('*') {
+            None
+        } else {
+            match self.identifier() {
+                Some(name) => Some(name),
+                None => return raise!("Expected a name after '|' in selector."),
+            }
+        };
+        Ok(Some(Simple::Type {
+            prefix: first,
+            name,
+        }))
+    }
+
+    fn attribute(&mut self) -> Result<Simple> {
+        self.expect('[')?;
+        self.skip_whitespace();
+        let mut name = match self.identifier() {
+            Some(name) => name,
+            None => return raise!("Expected an attribute name in selector."),
+        };
+        // a namespaced attribute, but not the start of '|='
+        if self.peek() == Some('|') && self.chars.get(self.pos + 1) != Some(&'=') {
+            self.pos += 1;
+            match self.identifier() {
+                Some(local) => name = format!("{}:{}", name, local),
+                None => return raise!("Expected an attribute name after '|' in selector."),
+            }
+        }
+        self.skip_whitespace();
+        let op = match self.peek() {
+            Some(']') => None,
+            Some('=') => Some(AttributeOp::Equals),
+            Some('^') => Some(AttributeOp::StartsWith),
+            Some('*') => Some(AttributeOp::Contains),
+            Some(c) => return raise!("Unsupported attribute operator '{}' in selector.", c),
+            None => return raise!("Unterminated attribute selector."),
+        };
+        let test = match op {
+            None => None,
+            Some(op) => {
+                if op != AttributeOp::Equals {
+                    self.pos += 1;
+                }
+                self.expect('=')?;
+                self.skip_whitespace();
+                let value = self.value()?;
+                self.skip_whitespace();
+                Some((op, value))
+            }
+        };
+        self.expect(']')?;
+        Ok(Simple::Attribute { name, test })
+    }
+
+    fn value(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(quote) if quote == '"' || quote == '\'' => {
+                self.pos += 1;
+                let mut value = String::new();
+                loop {
+                    match self.peek() {
+                        Some(c) if c == quote => {
+                            self.pos += 1;
+                            return Ok(value);
+                        }
+                        Some('\\') if self.pos + 1 < self.chars.len() => {
+                            value.push(self.chars[self.pos + 1]);
+                            self.pos += 2;
+                        }
+                        Some(c) => {
+                            value.push(c);
+                            self.pos += 1;
+                        }
+                        None => return raise!("Unterminated string in selector."),
+                    }
+                }
+            }
+            _ => match self.identifier() {
+                Some(value) => Ok(value),
+                None => raise!("Expected an attribute value in selector."),
+            },
+        }
+    }
+
+    fn pseudo_class(&mut self) -> Result<Simple> {
+        self.expect(':')?;
+        let name = self.identifier().unwrap_or_default();
+        match name.to_ascii_lowercase().as_str() {
+            "nth-child" => {
+                self.expect('(')?;
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c != ')') {
+                    self.pos += 1;
+                }
+                let argument = self.chars[start..self.pos].iter().collect::<String>();
+                self.expect(')')?;
+                let (a, b) = parse_nth(&argument)?;
+                Ok(Simple::NthChild { a, b })
+            }
+            "not" => {
+                self.expect('(')?;
+                let list = self.list()?;
+                self.expect(')')?;
+                Ok(Simple::Not(list))
+            }
+            _ => raise!("Unsupported pseudo-class ':{}' in selector.", name),
+        }
+    }
+
+    /// Reads a CSS identifier, with any character escaped by a backslash, e.g. `a\.b`. XML names
+    /// that are not CSS identifiers, like those with a `.`, need the escape.
+    fn identifier(&mut self) -> Option<String> {
+        let mut identifier = String::new();
+        while let Some(c) = self.peek() {
+            let start = identifier.is_empty() || identifier == "-";
+            if c == '\\' && self.pos + 1 < self.chars.len() {
+                identifier.push(self.chars[self.pos + 1]);
+                self.pos += 2;
+                continue;
+            }
+            let is_valid = c == '_'
+                || c == '-'
+                || c.is_alphabetic()
+                || !c.is_ascii()
+                || (!start && c.is_ascii_digit());
+            if !is_valid {
+                break;
+            }
+            identifier.push(c);
+            self.pos += 1;
+        }
+        match identifier.is_empty() {
+            true => None,
+            false => Some(identifier),
+        }
+    }
+}
+
+/// Parses the `an+b` argument of `:nth-child`, including `odd` and `even`.
+fn parse_nth(argument: &str) -> Result<(i64, i64)> {
+    let argument = argument
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let int = |s: &str| -> Result<i64> {
+        match s.trim_start_matches('+').parse() {
+            Ok(n) if !s.starts_with("+-") => Ok(n),
+            _ => raise!("Invalid :nth-child argument '{}'.", argument),
+        }
+    };
+    match argument.as_str() {
+        "odd" => return Ok((2, 1)),
+        "even" => return Ok((2, 0)),
+        _ => {}
+    }
+    match argument.split_once('n') {
+        Some((a, b)) => {
+            let a = match a {
+                "" | "+" => 1,
+                "-" => -1,
+                a => int(a)?,
+            };
+            let b = match b {
+                "" => 0,
+                b if b.starts_with('+') || b.starts_with('-') => int(b)?,
+                _ => return raise!("Invalid :nth-child argument '{}'.", argument),
+            };
+            Ok((a, b))
+        }
+        None => Ok((0, int(&argument)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{xml, Node};
+
+    fn cats() -> Document {
+        let root = xml! {
+            cats("xmlns:x" = "urn:x") {
+                cat(name = "bones", color = "black and white"),
+                ["x:cat"](name = "bishop", "x:age" = "7") {
+                    kitten(name = "punks"),
+                    kitten(name = "pip") { ["x:kitten"](name = "dot") },
+                },
+                "text between",
+                cat(name = "jack", color = "black"),
+                dog(name = "rex"),
+                ["first.name"](name = "dotted"),
+            }
+        };
+        Document::from_root(root.unwrap())
+    }
+
+    fn names(doc: &Document, selector: &str) -> Vec<String> {
+        doc.select(selector)
+            .unwrap()
+            .map(|e| e.attribute("name").unwrap_or(&e.name).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn types_and_namespaces() {
+        let doc = cats();
+        assert_eq!(names(&doc, "cats"), vec!["cats"]);
+        assert_eq!(names(&doc, "cat"), vec!["bones", "bishop", "jack"]);
+        assert_eq!(names(&doc, "|cat"), vec!["bones", "jack"]);
+        assert_eq!(names(&doc, "x|cat"), vec!["bishop"]);
+        assert_eq!(names(&doc, "x|*"), vec!["bishop", "dot"]);
+        assert_eq!(names(&doc, "*|kitten"), vec!["punks", "pip", "dot"]);
+        assert_eq!(names(&doc, "first\\.name"), vec!["dotted"]);
+        assert_eq!(doc.select("*").unwrap().count(), 9);
+    }
+
+    #[test]
+    fn attributes() {
+        let doc = cats();
+        assert_eq!(names(&doc, "[color]"), vec!["bones", "jack"]);
+        assert_eq!(names(&doc, "[color=black]"), vec!["jack"]);
+        assert_eq!(names(&doc, "[color ^= 'black']"), vec!["bones", "jack"]);
+        assert_eq!(names(&doc, "[color*=\"and\"]"), vec!["bones"]);
+        assert_eq!(names(&doc, "[x|age='7']"), vec!["bishop"]);
+        assert!(names(&doc, "[name^='']").is_empty());
+        assert!(names(&doc, "[name*='']").is_empty());
+    }
+
+    #[test]
+    fn combinators() {
+        let doc = cats();
+        assert_eq!(names(&doc, "cats kitten"), vec!["punks", "pip", "dot"]);
+        assert_eq!(names(&doc, "cats > kitten"), Vec::<String>::new());
+        assert_eq!(names(&doc, "cat>kitten"), vec!["punks", "pip"]);
+        assert_eq!(names(&doc, "cats > * > * > *"), vec!["dot"]);
+        // text between elements does not break adjacency
+        assert_eq!(names(&doc, "x|cat + cat"), vec!["jack"]);
+        assert_eq!(
+            names(&doc, "[name=bones] ~ *"),
+            vec!["bishop", "jack", "rex", "dotted"]
+        );
+        assert_eq!(names(&doc, "kitten + kitten kitten"), vec!["dot"]);
+        assert_eq!(
+            names(&doc, "dog, kitten"),
+            vec!["punks", "pip", "dot", "rex"]
+        );
+    }
+
+    #[test]
+    fn pseudo_classes() {
+        let doc = cats();
+        assert_eq!(names(&doc, "cats > :nth-child(2)"), vec!["bishop"]);
+        assert_eq!(
+            names(&doc, "cats > :nth-child(odd)"),
+            vec!["bones", "jack", "dotted"]
+        );
+        assert_eq!(
+            names(&doc, "cats > :nth-child(even)"),
+            vec!["bishop", "rex"]
+        );
+        assert_eq!(
+            names(&doc, "cats > :nth-child(-n + 2)"),
+            vec!["bones", "bishop"]
+        );
+        assert_eq!(names(&doc, "cats > :nth-child(3n+1)"), vec!["bones", "rex"]);
+        assert_eq!(names(&doc, "cat:not([color])"), vec!["bishop"]);
+        assert_eq!(names(&doc, "kitten:not(cat > *)"), vec!["dot"]);
+        assert_eq!(names(&doc, "cats > :not(cat, dog)"), vec!["dotted"]);
+    }
+
+    #[test]
+    fn select_below_an_element() {
+        let doc = cats();
+        let bishop = doc.root.first_child_named("x:cat").unwrap();
+        let found = bishop
+            .select("x|cat > kitten, cats *")
+            .unwrap()
+            .map(|e| e.attribute("name").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec!["punks", "pip"]);
+        assert_eq!(bishop.select(":nth-child(1)").unwrap().count(), 2);
+        // bishop is the second child of cats, but its siblings are not known from here
+        assert_eq!(bishop.select(":nth-child(1) > *").unwrap().count(), 0);
+        assert_eq!(
+            bishop
+                .select(":nth-child(2) > [name=punks]")
+                .unwrap()
+                .count(),
+            0
+        );
+        assert_eq!(bishop.select("cat + * > *").unwrap().count(), 0);
+        assert_eq!(
+            bishop
+                .select(":not(:nth-child(1)) > [name=punks]")
+                .unwrap()
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn many_siblings() {
+        let mut root = xml! { root { b } }.unwrap();
+        for _ in 0..4000 {
+            root.nodes.push(Node::Element(xml! { a }.unwrap()));
+        }
+        let doc = Document::from_root(root);
+        assert_eq!(doc.select("b ~ a").unwrap().count(), 4000);
+        assert_eq!(doc.select("a ~ b").unwrap().count(), 0);
+        assert_eq!(doc.select("a + a").unwrap().count(), 3999);
+        assert_eq!(doc.select("c ~ a").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn bad_selectors() {
+        for selector in &[
+            "",
+            "cat >",
+            "cat,",
+            "[name",
+            "[name~=x]",
+            "[name=",
+            ":hover",
+            ":nth-child(x)",
+            ":not(cat",
+            "cat)",
+            "x|",
+            "a b c >> d",
+        ] {
+            assert!(cats().select(selector).is_err(), "{}", selector);
+        }
+    }
+}
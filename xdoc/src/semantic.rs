@@ -0,0 +1,343 @@
+//! Comparing and hashing documents by what they hold rather than how they are written. The derived
+//! `PartialEq` of `Document` tells a pretty-printed document from a compact one, since the
+//! indentation is text. `Semantic` compares with `DiffOpts`, so it can leave out whitespace-only
+//! text, comments and the choice of namespace prefixes. Attribute order never matters.
+//!
+//! Two documents are equal as `Semantic` exactly when `Diff::new_opts` finds no edits between
+//! them, and equal documents hash the same. `Semantic::content_hash` is the same in every run and
+//! on every platform, so it can be stored as a cache key.
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use xdoc::diff::DiffOpts;
+//! use xdoc::semantic::Semantic;
+//! use xdoc::{xml, Document};
+//!
+//! let compact = Document::from_root(xml! { cats { cat(name = "bones") } }.unwrap());
+//! let pretty = Document::from_root(
+//!     xml! { cats { "\n  ", cat(name = "bones"), "\n", @comment(" cats ") } }.unwrap(),
+//! );
+//! assert_ne!(compact, pretty);
+//!
+//! let opts = DiffOpts {
+//!     ignore_whitespace_text: true,
+//!     ignore_comments: true,
+//!     ignore_prefixes: true,
+//! };
+//! assert_eq!(Semantic::new(&compact, &opts), Semantic::new(&pretty, &opts));
+//!
+//! let mut cache = HashMap::new();
+//! cache.insert(Semantic::new(&compact, &opts), "parsed");
+//! assert_eq!(cache.get(&Semantic::new(&pretty, &opts)), Some(&"parsed"));
+//! ```
+
+use std::hash::{Hash, Hasher};
+
+use crate::diff::DiffOpts;
+use crate::{Declaration, Document, ElementData, Encoding, Namespaces, Node, PIData, Version};
+
+/// A document that compares and hashes by its content, as `opts` sees it. Documents are only
+/// meant to be compared with the same options.
+#[derive(Debug, Clone, Copy)]
+pub struct Semantic<'a> {
+    doc: &'a Document,
+    opts: DiffOpts,
+}
+
+/// The parts of a document that are compared, in document order.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token<'a> {
+    Declaration(&'a Declaration),
+    /// The start of an element, by the name it is compared by.
+    Start(String),
+    /// An attribute of the element that was started last, by the name it is compared by. They come
+    /// in order of that name.
+    Attribute(String, &'a str),
+    End,
+    Text(&'a str),
+    CData(&'a str),
+    Comment(&'a str),
+    ProcessingInstruction(&'a PIData),
+    DocType(&'a str),
+}
+
+impl<'a> Semantic<'a> {
+    pub fn new(doc: &'a Document, opts: &DiffOpts) -> Self {
+        Semantic { doc, opts: *opts }
+    }
+
+    pub fn document(&self) -> &'a Document {
+        self.doc
+    }
+
+    /// A 64-bit FNV-1a hash of the content, which does not depend on the platform or the run.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv(FNV_OFFSET_BASIS);
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn tokens(&self) -> Vec<Token<'a>> {
+        let doc = self.doc;
+        let mut tokens = vec![Token::Declaration(&doc.declaration)];
+        let scope = Namespaces::default();
+        for node in &doc.prolog {
+            self.node(node, &scope, &mut tokens);
+        }
+        self.element(&doc.root, &scope, &mut tokens);
+        for node in &doc.epilog {
+            self.node(node, &scope, &mut tokens);
+        }
+        tokens
+    }
+
+    fn node(&self, node: &'a Node, scope: &Namespaces, tokens: &mut Vec<Token<'a>>) {
+        if self.opts.is_ignored(node) {
+            return;
+        }
+        tokens.push(match node {
+            Node::Element(element) => return self.element(element, scope, tokens),
+            Node::String(s) => Token::Text(s),
+            Node::CData(s) => Token::CData(s),
+            Node::Comment(s) => Token::Comment(s),
+            Node::ProcessingInstruction(pi) => Token::ProcessingInstruction(pi),
+            Node::DocType(s) => Token::DocType(s),
+        });
+    }
+
+    fn element(&self, element: &'a ElementData, scope: &Namespaces, tokens: &mut Vec<Token<'a>>) {
+        let scope = scope.enter(element);
+        let name = self
+            .opts
+            .name(element.namespace.as_deref(), &element.name, &scope, true);
+        tokens.push(Token::Start(name));
+        for (key, (name, _)) in self.opts.attributes(element, &scope) {
+            // borrow the value from the element rather than the copy
+            let value = element.attributes.map()[name.as_str()].as_str();
+            tokens.push(Token::Attribute(key, value));
+        }
+        for node in &element.nodes {
+            self.node(node, &scope, tokens);
+        }
+        tokens.push(Token::End);
+    }
+}
+
+impl PartialEq for Semantic<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.tokens() == other.tokens()
+    }
+}
+
+impl Eq for Semantic<'_> {}
+
+impl Hash for Semantic<'_> {
+    /// Only whole bytes are written to the hasher, with lengths as little-endian `u64`s, so the
+    /// hash does not depend on the platform.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let string = |state: &mut H, s: &str| {
+            state.write(&(s.len() as u64).to_le_bytes());
+            state.write(s.as_bytes());
+        };
+        for token in self.tokens() {
+            match token {
+                Token::Declaration(declaration) => {
+                    let version = match declaration.version {
+                        Version::None => 0,
+                        Version::One => 1,
+                        Version::OneDotOne => 2,
+                    };
+                    let encoding = match declaration.encoding {
+                        Encoding::None => 0,
+                        Encoding::Utf8 => 1,
+                    };
+                    state.write(&[0, version, encoding]);
+                }
+                Token::Start(name) => {
+                    state.write(&[1]);
+                    string(state, &name);
+                }
+                Token::Attribute(name, value) => {
+                    state.write(&[2]);
+                    string(state, &name);
+                    string(state, value);
+                }
+                Token::End => state.write(&[3]),
+                Token::Text(s) => {
+                    state.write(&[4]);
+                    string(state, s);
+                }
+                Token::CData(s) => {
+                    state.write(&[5]);
+                    string(state, s);
+                }
+                Token::Comment(s) => {
+                    state.write(&[6]);
+                    string(state, s);
+                }
+                Token::ProcessingInstruction(pi) => {
+                    state.write(&[7]);
+                    string(state, &pi.target);
+                    let mut instructions = pi.instructions.map().iter().collect::<Vec<_>>();
+                    instructions.sort();
+                    state.write(&(instructions.len() as u64).to_le_bytes());
+                    for (key, value) in instructions {
+                        string(state, key);
+                        string(state, value);
+                    }
+                }
+                Token::DocType(s) => {
+                    state.write(&[8]);
+                    string(state, s);
+                }
+            }
+        }
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// The 64-bit FNV-1a hash, which unlike `DefaultHasher` is fixed.
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::diff::Diff;
+    use crate::{xml, OrdMap};
+
+    fn doc(root: crate::error::Result<ElementData>) -> Document {
+        Document::from_root(root.unwrap())
+    }
+
+    fn all() -> DiffOpts {
+        DiffOpts {
+            ignore_whitespace_text: true,
+            ignore_comments: true,
+            ignore_prefixes: true,
+        }
+    }
+
+    fn hash(semantic: &Semantic<'_>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        semantic.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Checks that `Semantic` agrees with `Diff`, and that equal documents hash the same.
+    fn same(a: &Document, b: &Document, opts: &DiffOpts) -> bool {
+        let (a, b) = (Semantic::new(a, opts), Semantic::new(b, opts));
+        let equal = a == b;
+        assert_eq!(
+            equal,
+            Diff::new_opts(a.document(), b.document(), opts).is_empty()
+        );
+        if equal {
+            assert_eq!(hash(&a), hash(&b));
+            assert_eq!(a.content_hash(), b.content_hash());
+        }
+        equal
+    }
+
+    #[test]
+    fn whitespace_comments_and_prefixes() {
+        let a = doc(xml! {
+            ["a:cats"]("xmlns:a" = "urn:cats", kind = "house", "a:age" = "3") {
+                "\n  ",
+                ["a:cat"] { "bones" },
+                "\n  ",
+                @comment(" more "),
+            }
+        });
+        let b = doc(xml! {
+            cats("xmlns" = "urn:cats", "xmlns:b" = "urn:cats", "b:age" = "3", kind = "house") {
+                cat { "bones" },
+            }
+        });
+        let default = DiffOpts::default();
+        assert!(same(&a, &a, &default));
+        assert!(!same(&a, &b, &default));
+        assert!(same(&a, &b, &all()));
+        let whitespace = DiffOpts {
+            ignore_whitespace_text: true,
+            ..default
+        };
+        assert!(!same(&a, &b, &whitespace));
+        let comments = DiffOpts {
+            ignore_comments: false,
+            ..all()
+        };
+        assert!(!same(&a, &b, &comments));
+    }
+
+    #[test]
+    fn content_is_compared() {
+        let a = doc(xml! { cats { cat(name = "bones") { "big" } } });
+        let changes = vec![
+            doc(xml! { cats { cat(name = "bishop") { "big" } } }),
+            doc(xml! { cats { cat(name = "bones") { "small" } } }),
+            doc(xml! { cats { cat(name = "bones") { @cdata("big") } } }),
+            doc(xml! { cats { cat(name = "bones") { "b", "ig" } } }),
+            doc(xml! { cats { cat(name = "bones", age = "3") { "big" } } }),
+            doc(xml! { cats { dog(name = "bones") { "big" } } }),
+            doc(xml! { cats { cat(name = "bones") { "big" }, cat } }),
+            doc(xml! { cats { cat(name = "bones") } }),
+        ];
+        for b in &changes {
+            assert!(!same(&a, b, &all()), "{}", b);
+            assert_ne!(
+                Semantic::new(&a, &all()).content_hash(),
+                Semantic::new(b, &all()).content_hash(),
+                "{}",
+                b
+            );
+        }
+        let mut declared = a.clone();
+        declared.declaration.version = Version::One;
+        assert!(!same(&a, &declared, &all()));
+    }
+
+    #[test]
+    fn attribute_order() {
+        let mut ascending = HashMap::new();
+        let mut descending = HashMap::with_capacity(64);
+        for i in 0..32 {
+            ascending.insert(format!("a{}", i), i.to_string());
+            descending.insert(format!("a{}", 31 - i), (31 - i).to_string());
+        }
+        let element = |attributes| ElementData {
+            name: "cat".into(),
+            attributes: OrdMap::from(attributes),
+            ..ElementData::default()
+        };
+        let a = Document::from_root(element(ascending));
+        let b = Document::from_root(element(descending));
+        assert!(same(&a, &b, &DiffOpts::default()));
+    }
+
+    #[test]
+    fn content_hash_is_fixed() {
+        let cats = doc(xml! { cats { cat(name = "bones") { "big" } } });
+        assert_eq!(
+            Semantic::new(&cats, &DiffOpts::default()).content_hash(),
+            0x50c6_858f_afc5_69d9
+        );
+    }
+}
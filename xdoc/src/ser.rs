@@ -0,0 +1,907 @@
+//! A serde `Serializer` that turns any `T: Serialize` into a `Document` or XML text.
+//!
+//! - A struct is an element named after the struct, and each of its fields is a child element
+//!   named after the field, holding the field's value.
+//! - A field whose name starts with `@`, such as `#[serde(rename = "@id")]`, is an attribute
+//!   instead, and must hold a scalar, an `Option` of one, or a sequence of them, which is written
+//!   separated by spaces.
+//! - A field named `$value` is the content of the element itself: text for a scalar, and for a
+//!   struct or an enum variant, a child element named after the struct or the variant.
+//! - A sequence repeats the field's element once for each item. In `$value`, its items follow one
+//!   another, with scalars separated by spaces. An item cannot be `None` or a sequence, since
+//!   neither could be told apart when the XML is read back, so `Vec<Option<T>>` and `Vec<Vec<T>>`
+//!   are errors. For the same reason, a scalar item of an attribute or of `$value` cannot be empty
+//!   or hold whitespace, so `vec!["a b", "c"]` is an error there.
+//! - `None` leaves the field out. A unit variant is its name as text, and any other variant is an
+//!   element named after the variant.
+//! - A map is like a struct, with its keys as the names. `#[serde(flatten)]` works the same way.
+//!
+//! Names may have a namespace prefix, as in `#[serde(rename = "x:cat")]`, and are checked. Strings
+//! and chars must hold only characters that XML allows, so `"a\0b"` is an error.
+//!
+//! ```
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Cats {
+//!     #[serde(rename = "@xmlns:x")]
+//!     xmlns: &'static str,
+//!     #[serde(rename = "x:cat")]
+//!     cats: Vec<Cat>,
+//! }
+//!
+//! #[derive(Serialize)]
+//! struct Cat {
+//!     #[serde(rename = "@name")]
+//!     name: &'static str,
+//!     #[serde(rename = "@age", skip_serializing_if = "Option::is_none")]
+//!     age: Option<u8>,
+//!     #[serde(rename = "$value")]
+//!     description: &'static str,
+//! }
+//!
+//! let cats = Cats {
+//!     xmlns: "urn:cats",
+//!     cats: vec![
+//!         Cat { name: "bones", age: Some(3), description: "big" },
+//!         Cat { name: "bishop", age: None, description: "black" },
+//!     ],
+//! };
+//! assert_eq!(
+//!     xdoc::ser::to_string(&cats).unwrap(),
+//!     r#"<Cats xmlns:x="urn:cats">
+//!   <x:cat age="3" name="bones">big</x:cat>
+//!   <x:cat name="bishop">black</x:cat>
+//! </Cats>"#
+//! );
+//! ```
+
+use std::fmt::Display;
+
+use serde::ser::{self, Serialize};
+
+use crate::builder::split_name;
+use crate::chars::is_char;
+use crate::error::{Result, XErr};
+use crate::{Document, ElementData, Node, OrdMap};
+
+/// Serializes the value as a document whose root element is named after the value's struct, unit
+/// struct or enum variant.
+pub fn to_document<T>(value: &T) -> Result<Document>
+where
+    T: Serialize + ?Sized,
+{
+    let root = match value.serialize(ContentSerializer)? {
+        Content::Element(
+            element @ Element {
+                name: Some(name), ..
+            },
+        ) => element.build(name)?,
+        Content::Variant(name, content) => Element::with(*content)?.build(name)?,
+        _ => {
+            return raise!(
+                "Only a struct or an enum variant names the root element, use to_document_root."
+            )
+        }
+    };
+    Ok(Document::from_root(root))
+}
+
+/// Serializes the value as the content of a root element named `root`.
+pub fn to_document_root<T>(root: &str, value: &T) -> Result<Document>
+where
+    T: Serialize + ?Sized,
+{
+    let content = value.serialize(ContentSerializer)?;
+    Ok(Document::from_root(Element::with(content)?.build(root)?))
+}
+
+/// Serializes the value as `to_document` does and writes it with the default `WriteOpts`.
+pub fn to_string<T>(value: &T) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    Ok(to_document(value)?.to_string())
+}
+
+impl ser::Error for XErr {
+    fn custom<T: Display>(msg: T) -> Self {
+        XErr {
+            message: msg.to_string(),
+            file: file!().to_string(),
+            line: line!() as u64,
+            source: None,
+        }
+    }
+}
+
+/// What a value serializes to, before it is placed in an element.
+#[derive(Debug)]
+enum Content {
+    /// `None`, which is left out.
+    Nothing,
+    /// A scalar, or a unit variant.
+    Text(String),
+    /// A struct, a unit struct, a map or the unit value.
+    Element(Element),
+    Seq(Vec<Content>),
+    /// A variant with a value, by the name of the variant.
+    Variant(&'static str, Box<Content>),
+}
+
+impl Content {
+    /// Returns an error if the content cannot be an item of a sequence, which is the case when it
+    /// would be lost or merged with the items around it.
+    fn check_item(&self) -> Result<()> {
+        match self {
+            Content::Nothing => raise!("A sequence item cannot be None."),
+            Content::Seq(_) => raise!("A sequence item cannot be a sequence."),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if the content cannot be an item of a space-separated list, which is the
+    /// case when it would be split or dropped.
+    fn check_list_item(&self) -> Result<()> {
+        self.check_item()?;
+        match self {
+            Content::Text(s) if s.is_empty() || s.contains([' ', '\t', '\n', '\r']) => {
+                raise!(
+                    "A list item cannot be empty or hold whitespace, but {:?} does.",
+                    s
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// An element that is being built, with the name of its struct if it has one.
+#[derive(Debug, Default)]
+struct Element {
+    name: Option<&'static str>,
+    attributes: Vec<(String, String)>,
+    nodes: Vec<Node>,
+}
+
+impl Element {
+    /// An element that holds the content.
+    fn with(content: Content) -> Result<Self> {
+        let mut element = Element::default();
+        match content {
+            Content::Element(content) => element.merge(content),
+            content => element.inline(content)?,
+        }
+        Ok(element)
+    }
+
+    fn merge(&mut self, other: Element) {
+        self.attributes.extend(other.attributes);
+        self.nodes.extend(other.nodes);
+    }
+
+    /// Places the value of a struct field or a map entry.
+    fn field(&mut self, key: &str, content: Content) -> Result<()> {
+        if let Some(name) = key.strip_prefix('@') {
+            self.attribute(name, content)
+        } else if key == "$value" {
+            self.inline(content)
+        } else {
+            self.child(key, content)
+        }
+    }
+
+    fn attribute(&mut self, name: &str, content: Content) -> Result<()> {
+        let value = match content {
+            Content::Nothing => return Ok(()),
+            Content::Text(s) => s,
+            Content::Seq(items) => {
+                let mut values = Vec::new();
+                for item in items {
+                    item.check_list_item()?;
+                    match item {
+                        Content::Text(s) => values.push(s),
+                        _ => return raise!("The items of attribute '{}' must be scalars.", name),
+                    }
+                }
+                values.join(" ")
+            }
+            _ => return raise!("Attribute '{}' must be a scalar.", name),
+        };
+        if self.attributes.iter().any(|(other, _)| other == name) {
+            return raise!("Attribute '{}' is given more than once.", name);
+        }
+        self.attributes.push((name.to_string(), value));
+        Ok(())
+    }
+
+    /// Adds the content as the element's own text and children.
+    fn inline(&mut self, content: Content) -> Result<()> {
+        match content {
+            Content::Nothing => {}
+            Content::Text(s) => match self.nodes.last_mut() {
+                // scalars that follow one another are separated as a list
+                Some(Node::String(text)) => {
+                    text.push(' ');
+                    text.push_str(&s);
+                }
+                _ => self.nodes.push(Node::String(s)),
+            },
+            Content::Element(element) => match element.name {
+                Some(name) => self.nodes.push(Node::Element(element.build(name)?)),
+                None => self.merge(element),
+            },
+            Content::Seq(items) => {
+                for item in items {
+                    item.check_list_item()?;
+                    self.inline(item)?;
+                }
+            }
+            Content::Variant(name, content) => self.child(name, *content)?,
+        }
+        Ok(())
+    }
+
+    /// Adds the content as a child element named `name`, or one for each item of a sequence.
+    fn child(&mut self, name: &str, content: Content) -> Result<()> {
+        match content {
+            Content::Nothing => {}
+            Content::Seq(items) => {
+                for item in items {
+                    item.check_item()?;
+                    self.child(name, item)?;
+                }
+            }
+            content => {
+                let child = Element::with(content)?.build(name)?;
+                self.nodes.push(Node::Element(child));
+            }
+        }
+        Ok(())
+    }
+
+    fn build(self, name: &str) -> Result<ElementData> {
+        let (namespace, local) = split_name(name)?;
+        let mut attributes = OrdMap::new();
+        for (name, value) in self.attributes {
+            split_name(&name)?;
+            attributes.mut_map().insert(name.into(), value);
+        }
+        Ok(ElementData {
+            namespace: namespace.map(Into::into),
+            name: local.into(),
+            attributes,
+            nodes: self.nodes,
+        })
+    }
+}
+
+/// Serializes a value to `Content`.
+struct ContentSerializer;
+
+impl ContentSerializer {
+    fn text<T: Display>(value: T) -> Result<Content> {
+        let text = value.to_string();
+        if let Some(c) = text.chars().find(|&c| !is_char(c)) {
+            return raise!("The character {:?} cannot be written as XML.", c);
+        }
+        Ok(Content::Text(text))
+    }
+
+    /// Floats are written as XML Schema writes them, with `INF` rather than `inf`.
+    fn float(value: f64) -> Result<Content> {
+        match value {
+            f64::INFINITY => Self::text("INF"),
+            f64::NEG_INFINITY => Self::text("-INF"),
+            value => Self::text(value),
+        }
+    }
+}
+
+impl ser::Serializer for ContentSerializer {
+    type Ok = Content;
+    type Error = XErr;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = ElementSerializer;
+    type SerializeStruct = ElementSerializer;
+    type SerializeStructVariant = ElementSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Content> {
+        Self::float(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Content> {
+        Self::float(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Content> {
+        Self::text(v)
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<Content> {
+        raise!("Bytes cannot be serialized as XML.")
+    }
+
+    fn serialize_none(self) -> Result<Content> {
+        Ok(Content::Nothing)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Content>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Content> {
+        Ok(Content::Element(Element::default()))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Content> {
+        Ok(Content::Element(Element {
+            name: Some(name),
+            ..Element::default()
+        }))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Content> {
+        Self::text(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _: &'static str, value: &T) -> Result<Content>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Content>
+    where
+        T: Serialize + ?Sized,
+    {
+        Ok(Content::Variant(variant, Box::new(value.serialize(self)?)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer> {
+        Ok(SeqSerializer {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<ElementSerializer> {
+        Ok(ElementSerializer::default())
+    }
+
+    fn serialize_struct(self, name: &'static str, _: usize) -> Result<ElementSerializer> {
+        let mut serializer = ElementSerializer::default();
+        serializer.element.name = Some(name);
+        Ok(serializer)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<ElementSerializer> {
+        Ok(ElementSerializer {
+            variant: Some(variant),
+            ..ElementSerializer::default()
+        })
+    }
+}
+
+/// Collects the items of a sequence, a tuple, or a tuple variant.
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    items: Vec<Content>,
+}
+
+impl SeqSerializer {
+    fn push<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.items.push(value.serialize(ContentSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Content> {
+        let seq = Content::Seq(self.items);
+        Ok(match self.variant {
+            Some(variant) => Content::Variant(variant, Box::new(seq)),
+            None => seq,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Content;
+    type Error = XErr;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Content;
+    type Error = XErr;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Content;
+    type Error = XErr;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Content;
+    type Error = XErr;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+/// Builds the element of a struct, a struct variant, or a map.
+#[derive(Default)]
+struct ElementSerializer {
+    variant: Option<&'static str>,
+    element: Element,
+    /// The map key whose value comes next.
+    key: Option<String>,
+}
+
+impl ElementSerializer {
+    fn finish(self) -> Result<Content> {
+        let element = Content::Element(self.element);
+        Ok(match self.variant {
+            Some(variant) => Content::Variant(variant, Box::new(element)),
+            None => element,
+        })
+    }
+}
+
+impl ser::SerializeMap for ElementSerializer {
+    type Ok = Content;
+    type Error = XErr;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        match key.serialize(ContentSerializer)? {
+            Content::Text(key) => self.key = Some(key),
+            _ => return raise!("Map keys must be scalars."),
+        }
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = match self.key.take() {
+            Some(key) => key,
+            None => return raise!("A map value was given without a key."),
+        };
+        let content = value.serialize(ContentSerializer)?;
+        self.element.field(&key, content)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for ElementSerializer {
+    type Ok = Content;
+    type Error = XErr;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let content = value.serialize(ContentSerializer)?;
+        self.element.field(key, content)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for ElementSerializer {
+    type Ok = Content;
+    type Error = XErr;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let content = value.serialize(ContentSerializer)?;
+        self.element.field(key, content)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{xml, Format, WriteOpts};
+
+    fn compact(doc: &Document) -> String {
+        let opts = WriteOpts {
+            format: Format::Compact,
+            ..WriteOpts::default()
+        };
+        let mut bytes = Vec::new();
+        doc.root.write(&mut bytes, &opts, 0).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename = "cat")]
+    struct Cat {
+        #[serde(rename = "@name")]
+        name: String,
+        #[serde(rename = "@tags")]
+        tags: Vec<&'static str>,
+        age: Option<u8>,
+        color: Color,
+        toys: Vec<Toy>,
+        #[serde(rename = "$value")]
+        note: Option<&'static str>,
+    }
+
+    #[derive(Serialize)]
+    #[allow(dead_code)]
+    enum Color {
+        Black,
+        Grey,
+    }
+
+    #[derive(Serialize)]
+    enum Toy {
+        Ball(f64),
+        Mouse { squeaks: bool },
+        Box,
+    }
+
+    fn bones() -> Cat {
+        Cat {
+            name: "bones".to_string(),
+            tags: vec!["big", "old"],
+            age: Some(3),
+            color: Color::Black,
+            toys: vec![Toy::Ball(1.5), Toy::Mouse { squeaks: true }, Toy::Box],
+            note: Some("a good cat"),
+        }
+    }
+
+    #[test]
+    fn structs_fields_and_enums() {
+        let doc = to_document(&bones()).unwrap();
+        let expected = xml! {
+            cat(name = "bones", tags = "big old") {
+                age { "3" },
+                color { "Black" },
+                toys { Ball { "1.5" } },
+                toys { Mouse { squeaks { "true" } } },
+                toys { "Box" },
+                "a good cat",
+            }
+        };
+        assert_eq!(doc.root, expected.unwrap());
+
+        let cat = Cat {
+            age: None,
+            toys: Vec::new(),
+            note: None,
+            ..bones()
+        };
+        assert_eq!(
+            compact(&to_document(&cat).unwrap()),
+            r#"<cat name="bones" tags="big old"><color>Black</color></cat>"#
+        );
+    }
+
+    #[test]
+    fn value_content() {
+        #[derive(Serialize)]
+        struct Shelf {
+            #[serde(rename = "$value")]
+            items: Vec<Item>,
+        }
+
+        #[derive(Serialize)]
+        enum Item {
+            #[serde(rename = "x:book")]
+            Book {
+                title: &'static str,
+            },
+            Gap,
+        }
+
+        #[derive(Serialize)]
+        struct Numbers(#[serde(rename = "$value")] Vec<i32>);
+
+        let shelf = Shelf {
+            items: vec![Item::Book { title: "Emma" }, Item::Gap, Item::Gap],
+        };
+        assert_eq!(
+            compact(&to_document(&shelf).unwrap()),
+            "<Shelf><x:book><title>Emma</title></x:book>Gap Gap</Shelf>"
+        );
+        let numbers = Numbers(vec![1, -2, 3]);
+        assert_eq!(
+            compact(&to_document_root("numbers", &numbers).unwrap()),
+            "<numbers>1 -2 3</numbers>"
+        );
+    }
+
+    #[test]
+    fn maps_and_flattening() {
+        #[derive(Serialize)]
+        struct Wrapper {
+            #[serde(rename = "@id")]
+            id: u32,
+            #[serde(flatten)]
+            extra: BTreeMap<&'static str, f32>,
+        }
+
+        let mut extra = BTreeMap::new();
+        extra.insert("@weight", 4.5);
+        extra.insert("ratio", f32::INFINITY);
+        let wrapper = Wrapper { id: 7, extra };
+        assert_eq!(
+            compact(&to_document_root("w", &wrapper).unwrap()),
+            r#"<w id="7" weight="4.5"><ratio>INF</ratio></w>"#
+        );
+    }
+
+    #[test]
+    fn roots() {
+        assert!(to_document(&5).is_err());
+        assert!(to_document(&vec![bones()]).is_err());
+        assert_eq!(compact(&to_document_root("n", &5).unwrap()), "<n>5</n>");
+        assert_eq!(
+            compact(&to_document(&Toy::Mouse { squeaks: false }).unwrap()),
+            "<Mouse><squeaks>false</squeaks></Mouse>"
+        );
+        #[derive(Serialize)]
+        struct Empty;
+        assert_eq!(compact(&to_document(&Empty).unwrap()), "<Empty/>");
+        assert_eq!(compact(&to_document_root("e", &()).unwrap()), "<e/>");
+    }
+
+    #[test]
+    fn errors() {
+        #[derive(Serialize)]
+        struct BadAttribute {
+            #[serde(rename = "@toy")]
+            toy: Toy,
+        }
+
+        #[derive(Serialize)]
+        struct BadName {
+            #[serde(rename = "1cat")]
+            cat: u8,
+        }
+
+        let bad = BadAttribute {
+            toy: Toy::Ball(1.0),
+        };
+        assert!(to_document(&bad).is_err());
+        assert!(to_document(&BadName { cat: 1 }).is_err());
+        assert!(to_document_root("a", &b"bytes"[..]).is_ok());
+        let bytes = serde_bytes_like(&[1, 2]);
+        assert!(to_document_root("a", &bytes).is_err());
+    }
+
+    #[test]
+    fn sequences_that_would_not_round_trip() {
+        #[derive(Serialize)]
+        struct Ages {
+            age: Vec<Option<u8>>,
+        }
+
+        #[derive(Serialize)]
+        struct Grid {
+            row: Vec<Vec<u8>>,
+        }
+
+        #[derive(Serialize)]
+        struct Tags {
+            #[serde(rename = "@tags")]
+            tags: Vec<Option<&'static str>>,
+        }
+
+        assert!(to_document(&Ages { age: vec![Some(1)] }).is_ok());
+        assert!(to_document(&Ages {
+            age: vec![Some(1), None]
+        })
+        .is_err());
+        assert!(to_document(&Grid { row: vec![vec![1]] }).is_err());
+        assert!(to_document(&Tags { tags: vec![None] }).is_err());
+        assert!(to_document_root("a", &vec![vec![1, 2], vec![3]]).is_err());
+        assert!(to_document_root("a", &vec![Some(1), None]).is_err());
+        assert_eq!(
+            compact(&to_document_root("a", &vec![Some(1), Some(2)]).unwrap()),
+            "<a>1 2</a>"
+        );
+        assert!(to_document_root("a", &vec!["a b", "c"]).is_err());
+        assert!(to_document_root("a", &vec!["", "c"]).is_err());
+        assert!(to_document(&Tags {
+            tags: vec![Some("a\tb")]
+        })
+        .is_err());
+        assert!(to_document(&Tags {
+            tags: vec![Some("")]
+        })
+        .is_err());
+        assert_eq!(
+            compact(
+                &to_document(&Tags {
+                    tags: vec![Some("a")]
+                })
+                .unwrap()
+            ),
+            "<Tags tags=\"a\"/>"
+        );
+    }
+
+    #[test]
+    fn characters_that_are_not_xml() {
+        assert!(to_document_root("a", "a\0b").is_err());
+        assert!(to_document_root("a", &'\u{1B}').is_err());
+        assert!(to_document_root("a", &vec!["ok", "\u{FFFF}"]).is_err());
+        let mut map = BTreeMap::new();
+        map.insert("@name", "a\u{8}");
+        assert!(to_document_root("a", &map).is_err());
+        assert_eq!(
+            compact(&to_document_root("a", "tab\tand é").unwrap()),
+            "<a>tab\tand é</a>"
+        );
+    }
+
+    /// A value that serializes as bytes.
+    fn serde_bytes_like(bytes: &[u8]) -> impl Serialize + '_ {
+        struct Bytes<'a>(&'a [u8]);
+        impl Serialize for Bytes<'_> {
+            fn serialize<S: ser::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+        Bytes(bytes)
+    }
+}
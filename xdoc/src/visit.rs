@@ -0,0 +1,418 @@
+//! Traits for walking a tree, `Visitor`, and for rebuilding one, `Fold`. Every method has a
+//! default that recurses, so an implementation only overrides the hooks it cares about. To keep
+//! recursing from an overridden method, call the free function of the same name, e.g.
+//! `walk_element` from `Visitor::visit_element` or `fold_element` from `Fold::fold_element`, and
+//! to skip a subtree, don't.
+//!
+//! ```
+//! use xdoc::visit::{Fold, Visitor};
+//! use xdoc::{xml, Document, ElementData, Node};
+//!
+//! // counts the elements
+//! #[derive(Default)]
+//! struct Count(usize);
+//!
+//! impl Visitor for Count {
+//!     fn enter_element(&mut self, _: &ElementData) {
+//!         self.0 += 1;
+//!     }
+//! }
+//!
+//! // removes the comments
+//! struct StripComments;
+//!
+//! impl Fold for StripComments {
+//!     fn fold_comment(&mut self, _: String) -> Option<Node> {
+//!         None
+//!     }
+//! }
+//!
+//! let doc = Document::from_root(
+//!     xml! { cats { cat { @comment("bones") }, cat { "bishop", @comment("punks") } } }.unwrap(),
+//! );
+//! let mut count = Count::default();
+//! count.visit_document(&doc);
+//! assert_eq!(count.0, 3);
+//!
+//! let doc = StripComments.fold_document(doc);
+//! assert!(doc.root.descendants().all(|n| !matches!(n, Node::Comment(_))));
+//! assert_eq!(doc.root.inner_text(), "bishop");
+//! ```
+
+use crate::{Document, ElementData, Node, OrdMap, PIData};
+
+/// Walks a tree by reference. The `visit_*` methods are called for each node, and the default
+/// `visit_element` calls `enter_element`, then `visit_attribute` for each attribute in name order,
+/// then visits the children, then calls `exit_element`.
+pub trait Visitor {
+    fn visit_document(&mut self, doc: &Document) {
+        walk_document(self, doc)
+    }
+
+    fn visit_node(&mut self, node: &Node) {
+        walk_node(self, node)
+    }
+
+    fn visit_element(&mut self, element: &ElementData) {
+        walk_element(self, element)
+    }
+
+    /// Called before the element's attributes and children are visited.
+    fn enter_element(&mut self, _element: &ElementData) {}
+
+    /// Called after the element's children are visited.
+    fn exit_element(&mut self, _element: &ElementData) {}
+
+    fn visit_attribute(&mut self, _name: &str, _value: &str) {}
+
+    fn visit_text(&mut self, _text: &str) {}
+
+    fn visit_cdata(&mut self, _text: &str) {}
+
+    fn visit_comment(&mut self, _comment: &str) {}
+
+    fn visit_processing_instruction(&mut self, _pi: &PIData) {}
+
+    fn visit_doc_type(&mut self, _doc_type: &str) {}
+}
+
+/// Visits the prolog, the root and the epilog, in document order.
+pub fn walk_document<V: Visitor + ?Sized>(visitor: &mut V, doc: &Document) {
+    for node in &doc.prolog {
+        visitor.visit_node(node);
+    }
+    visitor.visit_element(&doc.root);
+    for node in &doc.epilog {
+        visitor.visit_node(node);
+    }
+}
+
+/// Calls the `visit_*` method for the kind of node.
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+    match node {
+        Node::Element(element) => visitor.visit_element(element),
+        Node::String(text) => visitor.visit_text(text),
+        Node::CData(text) => visitor.visit_cdata(text),
+        Node::Comment(comment) => visitor.visit_comment(comment),
+        Node::ProcessingInstruction(pi) => visitor.visit_processing_instruction(pi),
+        Node::DocType(doc_type) => visitor.visit_doc_type(doc_type),
+    }
+}
+
+pub fn walk_element<V: Visitor + ?Sized>(visitor: &mut V, element: &ElementData) {
+    visitor.enter_element(element);
+    // sorted so that the order does not depend on the order of the hash map
+    let mut names = element.attributes.map().keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        visitor.visit_attribute(name, &element.attributes.map()[name]);
+    }
+    for node in &element.nodes {
+        visitor.visit_node(node);
+    }
+    visitor.exit_element(element);
+}
+
+/// Rebuilds a tree by value. A node can be kept, changed, replaced by another kind of node, or
+/// removed by returning `None`. The root element can be changed but not removed.
+pub trait Fold {
+    fn fold_document(&mut self, doc: Document) -> Document {
+        fold_document(self, doc)
+    }
+
+    /// Folds each node of the prolog, the epilog or an element's children. Override it to add
+    /// nodes, or to change nodes depending on their neighbours.
+    fn fold_nodes(&mut self, nodes: Vec<Node>) -> Vec<Node> {
+        fold_nodes(self, nodes)
+    }
+
+    fn fold_node(&mut self, node: Node) -> Option<Node> {
+        fold_node(self, node)
+    }
+
+    fn fold_element(&mut self, element: ElementData) -> ElementData {
+        fold_element(self, element)
+    }
+
+    fn fold_attributes(&mut self, attributes: OrdMap) -> OrdMap {
+        attributes
+    }
+
+    fn fold_text(&mut self, text: String) -> Option<Node> {
+        Some(Node::String(text))
+    }
+
+    fn fold_cdata(&mut self, text: String) -> Option<Node> {
+        Some(Node::CData(text))
+    }
+
+    fn fold_comment(&mut self, comment: String) -> Option<Node> {
+        Some(Node::Comment(comment))
+    }
+
+    fn fold_processing_instruction(&mut self, pi: PIData) -> Option<Node> {
+        Some(Node::ProcessingInstruction(pi))
+    }
+
+    fn fold_doc_type(&mut self, doc_type: String) -> Option<Node> {
+        Some(Node::DocType(doc_type))
+    }
+}
+
+/// Folds the prolog, the root and the epilog, in document order.
+pub fn fold_document<F: Fold + ?Sized>(folder: &mut F, doc: Document) -> Document {
+    let Document {
+        declaration,
+        prolog,
+        root,
+        epilog,
+    } = doc;
+    let prolog = folder.fold_nodes(prolog);
+    let root = folder.fold_element(root);
+    let epilog = folder.fold_nodes(epilog);
+    Document {
+        declaration,
+        prolog,
+        root,
+        epilog,
+    }
+}
+
+pub fn fold_nodes<F: Fold + ?Sized>(folder: &mut F, nodes: Vec<Node>) -> Vec<Node> {
+    nodes
+        .into_iter()
+        .filter_map(|node| folder.fold_node(node))
+        .collect()
+}
+
+/// Calls the `fold_*` method for the kind of node.
+pub fn fold_node<F: Fold + ?Sized>(folder: &mut F, node: Node) -> Option<Node> {
+    match node {
+        Node::Element(element) => Some(Node::Element(folder.fold_element(element))),
+        Node::String(text) => folder.fold_text(text),
+        Node::CData(text) => folder.fold_cdata(text),
+        Node::Comment(comment) => folder.fold_comment(comment),
+        Node::ProcessingInstruction(pi) => folder.fold_processing_instruction(pi),
+        Node::DocType(doc_type) => folder.fold_doc_type(doc_type),
+    }
+}
+
+/// Folds the element's attributes and then its children.
+pub fn fold_element<F: Fold + ?Sized>(folder: &mut F, element: ElementData) -> ElementData {
+    let ElementData {
+        namespace,
+        name,
+        attributes,
+        nodes,
+    } = element;
+    let attributes = folder.fold_attributes(attributes);
+    let nodes = folder.fold_nodes(nodes);
+    ElementData {
+        namespace,
+        name,
+        attributes,
+        nodes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml;
+
+    fn cats() -> Document {
+        let root = xml! {
+            ["a:cats"]("xmlns:a" = "urn:cats", "a:kind" = "house") {
+                ["a:cat"](name = "bones") { "big", @cdata("<cat>") },
+                @comment(" between "),
+                cat(name = "bishop") { "punks" },
+            }
+        };
+        let mut doc = Document::from_root(root.unwrap());
+        doc.prolog.push(Node::DocType("cats".to_string()));
+        doc.epilog.push(Node::Comment(" end ".to_string()));
+        doc
+    }
+
+    /// Records each callback, indented by depth.
+    #[derive(Default)]
+    struct Trace {
+        depth: usize,
+        lines: Vec<String>,
+    }
+
+    impl Trace {
+        fn push(&mut self, line: String) {
+            self.lines
+                .push(format!("{}{}", "  ".repeat(self.depth), line));
+        }
+    }
+
+    impl Visitor for Trace {
+        fn enter_element(&mut self, element: &ElementData) {
+            self.push(format!("<{}>", element.fullname()));
+            self.depth += 1;
+        }
+
+        fn exit_element(&mut self, element: &ElementData) {
+            self.depth -= 1;
+            self.push(format!("</{}>", element.fullname()));
+        }
+
+        fn visit_attribute(&mut self, name: &str, value: &str) {
+            self.push(format!("@{}={}", name, value));
+        }
+
+        fn visit_text(&mut self, text: &str) {
+            self.push(format!("text {}", text));
+        }
+
+        fn visit_cdata(&mut self, text: &str) {
+            self.push(format!("cdata {}", text));
+        }
+
+        fn visit_comment(&mut self, comment: &str) {
+            self.push(format!("comment{}", comment));
+        }
+
+        fn visit_doc_type(&mut self, doc_type: &str) {
+            self.push(format!("doctype {}", doc_type));
+        }
+    }
+
+    #[test]
+    fn visitor_callbacks_are_in_document_order() {
+        let mut trace = Trace::default();
+        trace.visit_document(&cats());
+        assert_eq!(
+            trace.lines,
+            vec![
+                "doctype cats",
+                "<a:cats>",
+                "  @a:kind=house",
+                "  @xmlns:a=urn:cats",
+                "  <a:cat>",
+                "    @name=bones",
+                "    text big",
+                "    cdata <cat>",
+                "  </a:cat>",
+                "  comment between ",
+                "  <cat>",
+                "    @name=bishop",
+                "    text punks",
+                "  </cat>",
+                "</a:cats>",
+                "comment end ",
+            ]
+        );
+    }
+
+    #[test]
+    fn overriding_visit_element_skips_children() {
+        struct Shallow(Vec<String>);
+
+        impl Visitor for Shallow {
+            fn visit_element(&mut self, element: &ElementData) {
+                self.0.push(element.fullname());
+                if element.namespace.is_some() {
+                    walk_element(self, element);
+                }
+            }
+
+            fn visit_text(&mut self, text: &str) {
+                self.0.push(text.to_string());
+            }
+        }
+
+        let mut shallow = Shallow(Vec::new());
+        shallow.visit_document(&cats());
+        assert_eq!(shallow.0, vec!["a:cats", "a:cat", "big", "cat"]);
+    }
+
+    /// Moves elements and attributes from one namespace prefix to another.
+    struct RenamePrefix {
+        from: &'static str,
+        to: &'static str,
+    }
+
+    impl RenamePrefix {
+        fn rename(&self, name: &str) -> String {
+            let xmlns = format!("xmlns:{}", self.from);
+            match name.split_once(':') {
+                Some((prefix, local)) if prefix == self.from => format!("{}:{}", self.to, local),
+                _ if name == xmlns => format!("xmlns:{}", self.to),
+                _ => name.to_string(),
+            }
+        }
+    }
+
+    impl Fold for RenamePrefix {
+        fn fold_element(&mut self, element: ElementData) -> ElementData {
+            let mut element = fold_element(self, element);
+            if element.namespace.as_deref() == Some(self.from) {
+                element.namespace = Some(self.to.into());
+            }
+            element
+        }
+
+        fn fold_attributes(&mut self, attributes: OrdMap) -> OrdMap {
+            let mut renamed = OrdMap::new();
+            for (name, value) in attributes.map() {
+                renamed
+                    .mut_map()
+                    .insert(self.rename(name).into(), value.clone());
+            }
+            renamed
+        }
+    }
+
+    #[test]
+    fn fold_renames_a_prefix() {
+        let doc = RenamePrefix { from: "a", to: "b" }.fold_document(cats());
+        assert_eq!(doc.root.fullname(), "b:cats");
+        assert_eq!(doc.root.attribute("xmlns:b"), Some("urn:cats"));
+        assert_eq!(doc.root.attribute("b:kind"), Some("house"));
+        assert!(doc.root.first_child_named("b:cat").is_some());
+        assert!(doc.root.first_child_named("cat").is_some());
+    }
+
+    #[test]
+    fn fold_replaces_and_removes_nodes() {
+        // CDATA becomes text, and the comments and the DOCTYPE go
+        struct Simplify;
+
+        impl Fold for Simplify {
+            fn fold_cdata(&mut self, text: String) -> Option<Node> {
+                Some(Node::String(text))
+            }
+
+            fn fold_comment(&mut self, _: String) -> Option<Node> {
+                None
+            }
+
+            fn fold_doc_type(&mut self, _: String) -> Option<Node> {
+                None
+            }
+        }
+
+        let doc = Simplify.fold_document(cats());
+        assert!(doc.prolog.is_empty());
+        assert!(doc.epilog.is_empty());
+        assert_eq!(doc.root.nodes.len(), 2);
+        let bones = doc.root.first_child_named("a:cat").unwrap();
+        assert_eq!(
+            bones.nodes,
+            vec![
+                Node::String("big".to_string()),
+                Node::String("<cat>".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn default_fold_changes_nothing() {
+        struct Nothing;
+        impl Fold for Nothing {}
+        assert_eq!(Nothing.fold_document(cats()), cats());
+    }
+}
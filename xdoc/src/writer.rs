@@ -0,0 +1,587 @@
+use std::io::Write;
+
+use ds::Stack;
+
+use crate::chars::is_name;
+use crate::doc::{Format, WriteOpts};
+use crate::error::Result;
+use crate::escape::{escape_attribute, escape_text};
+use crate::{Declaration, Node, PIData};
+
+/// Writes a document one piece at a time, so that it never has to be held in memory as a
+/// `Document`. Names are checked, text and attribute values are escaped, and end tags must match
+/// the open start tags.
+///
+/// The output is laid out with the same `WriteOpts` as `Document::write_opts`, except that the
+/// writer cannot look ahead: an element's children are indented until text is written in it, and
+/// from then on its content is written inline.
+///
+/// ```
+/// use xdoc::XmlWriter;
+///
+/// let mut writer = XmlWriter::new(Vec::new());
+/// writer.start_element("cats").unwrap();
+/// writer.start_element("cat").unwrap();
+/// writer.attribute("name", "bones").unwrap();
+/// writer.end_element("cat").unwrap();
+/// writer.start_element("cat").unwrap();
+/// writer.text("bishop & co").unwrap();
+/// writer.end_element("cat").unwrap();
+/// writer.end_element("cats").unwrap();
+/// let xml = String::from_utf8(writer.finish().unwrap()).unwrap();
+/// assert_eq!(
+///     xml,
+///     "<cats>\n  <cat name=\"bones\"/>\n  <cat>bishop &amp; co</cat>\n</cats>"
+/// );
+/// ```
+pub struct XmlWriter<W: Write> {
+    writer: W,
+    opts: WriteOpts,
+    open: Stack<OpenElement>,
+    /// A start tag that has not been written yet because attributes may still be added to it.
+    start_tag: Option<StartTag>,
+    status: Status,
+    /// Nothing has been written to `writer` yet.
+    at_start: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialOrd, PartialEq, Hash)]
+enum Status {
+    /// Nothing has been written, so a declaration is still allowed.
+    Empty,
+    BeforeRoot,
+    InRoot,
+    AfterRoot,
+}
+
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq, Hash)]
+struct OpenElement {
+    name: String,
+    /// The element's content is written without adding any whitespace.
+    inline: bool,
+    /// At least one child has been written on a line of its own.
+    indented: bool,
+}
+
+struct StartTag {
+    name: String,
+    attributes: Vec<(String, String)>,
+    /// The start tag begins on a line of its own.
+    pretty: bool,
+}
+
+impl<W: Write> XmlWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self::new_opts(writer, &WriteOpts::default())
+    }
+
+    pub fn new_opts(writer: W, opts: &WriteOpts) -> Self {
+        XmlWriter {
+            writer,
+            opts: opts.clone(),
+            open: Stack::new(),
+            start_tag: None,
+            status: Status::Empty,
+            at_start: true,
+        }
+    }
+
+    /// Writes the XML declaration, which must come before anything else.
+    pub fn declaration(&mut self, declaration: &Declaration) -> Result<()> {
+        if self.status != Status::Empty {
+            return raise!("The declaration must be the first thing in the document.");
+        }
+        self.status = Status::BeforeRoot;
+        // canonical xml has no declaration
+        if self.opts.format == Format::Canonical || declaration.is_empty() {
+            return Ok(());
+        }
+        if let Err(e) = declaration.write(&mut self.writer, &self.opts) {
+            return wrap!(e);
+        }
+        self.at_start = false;
+        Ok(())
+    }
+
+    /// Writes `<!DOCTYPE doc_type>`, which must come before the root element.
+    pub fn doc_type(&mut self, doc_type: &str) -> Result<()> {
+        if self.status == Status::InRoot || self.status == Status::AfterRoot {
+            return raise!("The DOCTYPE must come before the root element.");
+        }
+        let node = Node::DocType(doc_type.to_string());
+        // canonical xml has no doctype
+        if self.opts.format == Format::Canonical {
+            if let Err(e) = node.check() {
+                return wrap!(e);
+            }
+            self.status = Status::BeforeRoot;
+            return Ok(());
+        }
+        self.write_node(&node)
+    }
+
+    /// Begins an element. Its attributes can be added until anything else is written.
+    pub fn start_element(&mut self, name: &str) -> Result<()> {
+        if !is_name(name) {
+            return raise!("Invalid element name '{}'.", name);
+        }
+        if self.status == Status::AfterRoot {
+            return raise!(
+                "The document can only have one root element, found '{}'.",
+                name
+            );
+        }
+        if let Err(e) = self.before_markup() {
+            return wrap!(e);
+        }
+        let pretty = self.opts.format == Format::Pretty && !self.is_inline();
+        self.start_tag = Some(StartTag {
+            name: name.to_string(),
+            attributes: Vec::new(),
+            pretty,
+        });
+        self.status = Status::InRoot;
+        Ok(())
+    }
+
+    /// Adds an attribute to the element that was just started.
+    pub fn attribute(&mut self, name: &str, value: &str) -> Result<()> {
+        if !is_name(name) {
+            return raise!("Invalid attribute name '{}'.", name);
+        }
+        let start_tag = match self.start_tag.as_mut() {
+            Some(start_tag) => start_tag,
+            None => return raise!("The attribute '{}' does not follow a start tag.", name),
+        };
+        if start_tag.attributes.iter().any(|(k, _)| k == name) {
+            return raise!("Duplicate attribute '{}'.", name);
+        }
+        start_tag
+            .attributes
+            .push((name.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    /// Ends the innermost open element, which must be named `name`.
+    pub fn end_element(&mut self, name: &str) -> Result<()> {
+        if let Some(start_tag) = &self.start_tag {
+            if start_tag.name != name {
+                return raise!(
+                    "Expected the end of '{}', found '{}'.",
+                    start_tag.name,
+                    name
+                );
+            }
+        }
+        if let Some(start_tag) = self.start_tag.take() {
+            let depth = self.open.len();
+            if let Err(e) = self.write_start_tag(&start_tag, depth, true) {
+                return wrap!(e);
+            }
+            if !self.opts.self_closing() {
+                if let Err(e) = write!(self.writer, "</{}>", name) {
+                    return wrap!(e);
+                }
+            }
+        } else {
+            match self.open.peek() {
+                Some(element) if element.name != name => {
+                    return raise!("Expected the end of '{}', found '{}'.", element.name, name)
+                }
+                Some(_) => {}
+                None => return raise!("Found the end of '{}' with no element open.", name),
+            }
+            let element = match self.open.pop() {
+                Some(element) => element,
+                None => return raise!("The open element disappeared."),
+            };
+            if element.indented && !element.inline {
+                if let Err(e) = self.opts.newline(&mut self.writer) {
+                    return wrap!(e);
+                }
+                if let Err(e) = self.opts.indent(&mut self.writer, self.open.len()) {
+                    return wrap!(e);
+                }
+            }
+            if let Err(e) = write!(self.writer, "</{}>", name) {
+                return wrap!(e);
+            }
+        }
+        if self.open.is_empty() {
+            self.status = Status::AfterRoot;
+        }
+        Ok(())
+    }
+
+    /// Writes escaped text in the open element. The element's content is inline from now on.
+    pub fn text(&mut self, text: &str) -> Result<()> {
+        if let Err(e) = self.before_text() {
+            return wrap!(e);
+        }
+        if let Err(e) = write!(self.writer, "{}", escape_text(text)) {
+            return wrap!(e);
+        }
+        Ok(())
+    }
+
+    /// Writes a CDATA section in the open element. The element's content is inline from now on.
+    pub fn cdata(&mut self, text: &str) -> Result<()> {
+        let node = Node::CData(text.to_string());
+        // checked before anything is written, so that a rejected call leaves the output as it was
+        if let Err(e) = node.check() {
+            return wrap!(e);
+        }
+        if let Err(e) = self.before_text() {
+            return wrap!(e);
+        }
+        let depth = self.open.len();
+        if let Err(e) = node.write_node(&mut self.writer, &self.opts, depth, false) {
+            return wrap!(e);
+        }
+        Ok(())
+    }
+
+    pub fn comment(&mut self, comment: &str) -> Result<()> {
+        self.write_node(&Node::Comment(comment.to_string()))
+    }
+
+    pub fn processing_instruction(&mut self, pi: &PIData) -> Result<()> {
+        self.write_node(&Node::ProcessingInstruction(pi.clone()))
+    }
+
+    /// Checks that the document is complete, and returns the underlying writer once it has been
+    /// flushed.
+    pub fn finish(mut self) -> Result<W> {
+        if let Some(start_tag) = &self.start_tag {
+            return raise!("The element '{}' was not closed.", start_tag.name);
+        }
+        if let Some(element) = self.open.peek() {
+            return raise!("The element '{}' was not closed.", element.name);
+        }
+        if self.status != Status::AfterRoot {
+            return raise!("The document has no root element.");
+        }
+        if self.opts.trailing_newline && self.opts.format != Format::Canonical {
+            if let Err(e) = write!(self.writer, "{}", self.opts.newline_str()) {
+                return wrap!(e);
+            }
+        }
+        if let Err(e) = self.writer.flush() {
+            return wrap!(e);
+        }
+        Ok(self.writer)
+    }
+
+    fn write_node(&mut self, node: &Node) -> Result<()> {
+        // checked before anything is written, so that a rejected call leaves the output as it was
+        if let Err(e) = node.check() {
+            return wrap!(e);
+        }
+        if let Err(e) = self.before_markup() {
+            return wrap!(e);
+        }
+        let depth = self.open.len();
+        let pretty = !self.is_inline();
+        if let Err(e) = node.write_node(&mut self.writer, &self.opts, depth, pretty) {
+            return wrap!(e);
+        }
+        if self.status == Status::Empty {
+            self.status = Status::BeforeRoot;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the open element's content is being written inline.
+    fn is_inline(&self) -> bool {
+        self.open.peek().is_some_and(|element| element.inline)
+    }
+
+    /// Writes the pending start tag, if any, and then the whitespace that precedes an element,
+    /// comment or processing instruction.
+    fn before_markup(&mut self) -> Result<()> {
+        if let Err(e) = self.flush_start_tag() {
+            return wrap!(e);
+        }
+        let depth = self.open.len();
+        match self.open.peek_mut() {
+            Some(element) if element.inline => return Ok(()),
+            Some(element) => element.indented = true,
+            // nothing separates the first node of the document from the start of the file
+            None if self.at_start => {
+                self.at_start = false;
+                return Ok(());
+            }
+            None if self.opts.format == Format::Canonical => {
+                if let Err(e) = writeln!(self.writer) {
+                    return wrap!(e);
+                }
+                return Ok(());
+            }
+            None => {}
+        }
+        if let Err(e) = self.opts.newline(&mut self.writer) {
+            return wrap!(e);
+        }
+        if let Err(e) = self.opts.indent(&mut self.writer, depth) {
+            return wrap!(e);
+        }
+        Ok(())
+    }
+
+    /// Writes the pending start tag, if any, and makes the open element's content inline.
+    fn before_text(&mut self) -> Result<()> {
+        if let Err(e) = self.flush_start_tag() {
+            return wrap!(e);
+        }
+        match self.open.peek_mut() {
+            Some(element) => element.inline = true,
+            None => return raise!("Text must be inside the root element."),
+        }
+        Ok(())
+    }
+
+    /// Writes the pending start tag, if any, as the start of an element that has content.
+    fn flush_start_tag(&mut self) -> Result<()> {
+        let start_tag = match self.start_tag.take() {
+            Some(start_tag) => start_tag,
+            None => return Ok(()),
+        };
+        let depth = self.open.len();
+        if let Err(e) = self.write_start_tag(&start_tag, depth, false) {
+            return wrap!(e);
+        }
+        let preserves_space = start_tag
+            .attributes
+            .iter()
+            .any(|(k, v)| k == "xml:space" && v == "preserve");
+        self.open.push(OpenElement {
+            name: start_tag.name,
+            inline: !start_tag.pretty || preserves_space,
+            indented: false,
+        });
+        Ok(())
+    }
+
+    fn write_start_tag(
+        &mut self,
+        start_tag: &StartTag,
+        depth: usize,
+        is_empty: bool,
+    ) -> Result<()> {
+        let attributes = start_tag
+            .attributes
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<Vec<_>>();
+        write_start_tag(
+            &mut self.writer,
+            &self.opts,
+            &start_tag.name,
+            attributes,
+            depth,
+            start_tag.pretty,
+            is_empty,
+        )
+    }
+}
+
+/// Writes `<name` and the attributes, followed by `/>` if the element is empty and written as a
+/// self-closing tag, otherwise `>`. Namespace declarations come first, then the other attributes,
+/// each sorted by name so that the output does not depend on the order they were given in.
+/// `pretty` is `true` if the start tag begins a line of its own, in which case its attributes may
+/// be wrapped.
+pub(crate) fn write_start_tag<W>(
+    writer: &mut W,
+    opts: &WriteOpts,
+    name: &str,
+    mut attributes: Vec<(&str, &str)>,
+    depth: usize,
+    pretty: bool,
+    is_empty: bool,
+) -> Result<()>
+where
+    W: Write,
+{
+    if let Err(e) = write!(writer, "<{}", name) {
+        return wrap!(e);
+    }
+    let quote = opts.quote_char();
+    attributes.sort_by_key(|(k, _)| (!is_namespace_declaration(k), *k));
+    let attributes = attributes
+        .into_iter()
+        .map(|(k, v)| (k, escape_attribute(v, quote)))
+        .collect::<Vec<_>>();
+    let is_self_closing = is_empty && opts.self_closing();
+
+    let wrap_attributes = pretty && !attributes.is_empty() && {
+        let width = attributes
+            .iter()
+            .map(|(k, v)| k.chars().count() + v.chars().count() + 4)
+            .sum::<usize>()
+            + name.chars().count()
+            + if is_self_closing { 3 } else { 2 };
+        opts.wraps_attributes(depth, width)
+    };
+    for (k, v) in attributes.iter() {
+        if wrap_attributes {
+            if let Err(e) = opts.newline(writer) {
+                return wrap!(e);
+            }
+            if let Err(e) = opts.indent(writer, depth + 1) {
+                return wrap!(e);
+            }
+        } else if let Err(e) = write!(writer, " ") {
+            return wrap!(e);
+        }
+        if let Err(e) = write!(writer, "{}={}{}{}", k, quote, v, quote) {
+            return wrap!(e);
+        }
+    }
+
+    let end = if is_self_closing { "/>" } else { ">" };
+    if let Err(e) = write!(writer, "{}", end) {
+        return wrap!(e);
+    }
+    Ok(())
+}
+
+fn is_namespace_declaration(key: &str) -> bool {
+    key == "xmlns" || key.starts_with("xmlns:")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map, Encoding, OrdMap, Version};
+
+    fn finish(writer: XmlWriter<Vec<u8>>) -> String {
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn same_output_as_document() {
+        let xml = "<?xml version=\"1.0\"?>\n<!-- cats -->\n<?cat a=\"1\"?>\n<!DOCTYPE cats>\n\
+                   <cats>\n  <cat name=\"bones\"/>\n  <cat>\n    <!-- bishop -->\n  </cat>\n  \
+                   <p>Hello <b>world</b><![CDATA[!]]><br/></p>\n</cats>\n<!-- end -->";
+        let mut w = XmlWriter::new(Vec::new());
+        w.declaration(&Declaration {
+            version: Version::One,
+            encoding: Encoding::None,
+        })
+        .unwrap();
+        w.comment(" cats ").unwrap();
+        w.processing_instruction(&PIData {
+            target: "cat".to_string(),
+            instructions: OrdMap::from(map! { "a".to_string() => "1".to_string() }),
+        })
+        .unwrap();
+        w.doc_type("cats").unwrap();
+        w.start_element("cats").unwrap();
+        w.start_element("cat").unwrap();
+        w.attribute("name", "bones").unwrap();
+        w.end_element("cat").unwrap();
+        w.start_element("cat").unwrap();
+        w.comment(" bishop ").unwrap();
+        w.end_element("cat").unwrap();
+        w.start_element("p").unwrap();
+        w.text("Hello ").unwrap();
+        w.start_element("b").unwrap();
+        w.text("world").unwrap();
+        w.end_element("b").unwrap();
+        w.cdata("!").unwrap();
+        w.start_element("br").unwrap();
+        w.end_element("br").unwrap();
+        w.end_element("p").unwrap();
+        w.end_element("cats").unwrap();
+        w.comment(" end ").unwrap();
+        assert_eq!(finish(w), xml);
+    }
+
+    #[test]
+    fn canonical() {
+        let opts = WriteOpts {
+            format: Format::Canonical,
+            ..Default::default()
+        };
+        let mut w = XmlWriter::new_opts(Vec::new(), &opts);
+        w.declaration(&Declaration::default()).unwrap();
+        w.comment("a").unwrap();
+        w.doc_type("b").unwrap();
+        w.start_element("b").unwrap();
+        w.attribute("z", "\"").unwrap();
+        w.attribute("xmlns", "urn:b").unwrap();
+        w.start_element("c").unwrap();
+        w.end_element("c").unwrap();
+        w.cdata("<").unwrap();
+        w.end_element("b").unwrap();
+        w.comment("d").unwrap();
+        assert_eq!(
+            finish(w),
+            "<!--a-->\n<b xmlns=\"urn:b\" z=\"&quot;\"><c></c>&lt;</b>\n<!--d-->"
+        );
+    }
+
+    #[test]
+    fn misuse_is_rejected() {
+        let mut w = XmlWriter::new(Vec::new());
+        assert!(w.text("text").is_err());
+        assert!(w.attribute("a", "1").is_err());
+        assert!(w.end_element("a").is_err());
+        assert!(w.start_element("1a").is_err());
+        w.start_element("a").unwrap();
+        assert!(w.attribute("b c", "1").is_err());
+        w.attribute("b", "1").unwrap();
+        assert!(w.attribute("b", "2").is_err());
+        assert!(w.comment("--").is_err());
+        w.start_element("b").unwrap();
+        assert!(w.declaration(&Declaration::default()).is_err());
+        assert!(w.doc_type("a").is_err());
+        assert!(w.end_element("a").is_err());
+        w.end_element("b").unwrap();
+        w.start_element("c").unwrap();
+        w.text("c").unwrap();
+        assert!(w.end_element("a").is_err());
+        w.end_element("c").unwrap();
+        w.end_element("a").unwrap();
+        assert!(w.start_element("c").is_err());
+
+        let mut w = XmlWriter::new(Vec::new());
+        w.start_element("a").unwrap();
+        assert!(w.finish().is_err());
+        assert!(XmlWriter::new(Vec::new()).finish().is_err());
+    }
+
+    #[test]
+    fn rejected_nodes_write_nothing() {
+        let bad_pi = PIData {
+            target: "a b".to_string(),
+            instructions: OrdMap::new(),
+        };
+        let mut w = XmlWriter::new(Vec::new());
+        assert!(w.comment("a--b").is_err());
+        assert!(w.processing_instruction(&bad_pi).is_err());
+        assert!(w.doc_type("cats>").is_err());
+        // the declaration can still come first
+        w.declaration(&Declaration {
+            version: Version::One,
+            encoding: Encoding::None,
+        })
+        .unwrap();
+        w.start_element("cats").unwrap();
+        w.attribute("a", "1").unwrap();
+        assert!(w.comment("a--b").is_err());
+        assert!(w.processing_instruction(&bad_pi).is_err());
+        assert!(w.cdata("]]>").is_err());
+        // the start tag is still open for attributes
+        w.attribute("b", "2").unwrap();
+        w.start_element("cat").unwrap();
+        assert!(w.cdata("]]>").is_err());
+        w.end_element("cat").unwrap();
+        w.end_element("cats").unwrap();
+        assert!(w.comment("end-").is_err());
+        assert_eq!(
+            finish(w),
+            "<?xml version=\"1.0\"?>\n<cats a=\"1\" b=\"2\">\n  <cat/>\n</cats>"
+        );
+    }
+}
@@ -0,0 +1,627 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::iter::once;
+
+use crate::arena::{Document, Node, NodeId};
+use crate::error::Result;
+use crate::xpath::lexer::is_whitespace;
+use crate::xpath::parser::{Arithmetic, Axis, Comparison, Expr, NodeTest, Step};
+use crate::xpath::{NodeRef, Value, XPathOpts, XML_NAMESPACE};
+use crate::Symbol;
+
+/// The context node, with its position in and the size of the node-set it is being evaluated
+/// over.
+pub(super) struct Context {
+    pub(super) node: NodeRef,
+    pub(super) position: usize,
+    pub(super) size: usize,
+}
+
+pub(super) struct Evaluator<'a> {
+    pub(super) doc: &'a Document,
+    opts: &'a XPathOpts,
+}
+
+impl<'a> Evaluator<'a> {
+    pub(super) fn new(doc: &'a Document, opts: &'a XPathOpts) -> Self {
+        Evaluator { doc, opts }
+    }
+
+    pub(super) fn evaluate_expr(&self, expr: &Expr, node: NodeRef) -> Result<Value> {
+        let context = Context {
+            node,
+            position: 1,
+            size: 1,
+        };
+        self.evaluate(expr, &context)
+    }
+
+    pub(super) fn evaluate(&self, expr: &Expr, context: &Context) -> Result<Value> {
+        Ok(match expr {
+            Expr::Or(left, right) => Value::Boolean(
+                self.evaluate(left, context)?.boolean() || self.evaluate(right, context)?.boolean(),
+            ),
+            Expr::And(left, right) => Value::Boolean(
+                self.evaluate(left, context)?.boolean() && self.evaluate(right, context)?.boolean(),
+            ),
+            Expr::Compare(op, left, right) => {
+                let left = self.evaluate(left, context)?;
+                let right = self.evaluate(right, context)?;
+                Value::Boolean(self.compare(*op, &left, &right))
+            }
+            Expr::Arithmetic(op, left, right) => {
+                let left = self.evaluate(left, context)?.number(self.doc);
+                let right = self.evaluate(right, context)?.number(self.doc);
+                Value::Number(match op {
+                    Arithmetic::Add => left + right,
+                    Arithmetic::Subtract => left - right,
+                    Arithmetic::Multiply => left * right,
+                    Arithmetic::Divide => left / right,
+                    // truncating, so the result has the sign of the dividend as XPath requires
+                    Arithmetic::Modulo => left % right,
+                })
+            }
+            Expr::Negate(operand) => {
+                Value::Number(-self.evaluate(operand, context)?.number(self.doc))
+            }
+            Expr::Union(left, right) => {
+                let mut nodes = self.node_set(self.evaluate(left, context)?)?;
+                nodes.extend(self.node_set(self.evaluate(right, context)?)?);
+                Value::Nodes(self.sorted(nodes))
+            }
+            Expr::Literal(s) => Value::String(s.clone()),
+            Expr::Number(n) => Value::Number(*n),
+            Expr::Variable(name) => match self.opts.variables.get(name) {
+                Some(Value::Nodes(nodes)) => Value::Nodes(self.sorted(nodes.clone())),
+                Some(value) => value.clone(),
+                None => return raise!("The variable '${}' is not defined.", name),
+            },
+            Expr::Function(name, args) => self.call(name, args, context)?,
+            Expr::Filter(primary, predicates) => {
+                let mut nodes = self.node_set(self.evaluate(primary, context)?)?;
+                for predicate in predicates {
+                    nodes = self.filter(nodes, predicate)?;
+                }
+                Value::Nodes(nodes)
+            }
+            Expr::Root => {
+                let id = context.node.id();
+                Value::Nodes(vec![NodeRef::Node(
+                    self.doc.ancestors(id).last().unwrap_or(id),
+                )])
+            }
+            Expr::Path(start, steps) => {
+                let mut nodes = match start {
+                    Some(start) => self.node_set(self.evaluate(start, context)?)?,
+                    None => vec![context.node.clone()],
+                };
+                for step in steps {
+                    nodes = self.step(&nodes, step)?;
+                }
+                Value::Nodes(nodes)
+            }
+        })
+    }
+
+    pub(super) fn node_set(&self, value: Value) -> Result<Vec<NodeRef>> {
+        match value {
+            Value::Nodes(nodes) => Ok(nodes),
+            other => raise!("Expected a node-set but found {:?}.", other),
+        }
+    }
+
+    /// Puts nodes in document order and removes duplicates.
+    pub(super) fn sorted(&self, mut nodes: Vec<NodeRef>) -> Vec<NodeRef> {
+        nodes.sort_by(|a, b| self.document_order(a, b));
+        nodes.dedup();
+        nodes
+    }
+
+    fn document_order(&self, a: &NodeRef, b: &NodeRef) -> Ordering {
+        // an element's namespaces come after it and before its attributes, which come before its
+        // children
+        let key = |node: &NodeRef| {
+            let (rank, name) = match node {
+                NodeRef::Node(_) => (0, None),
+                NodeRef::Namespace(_, prefix) => (1, Some(prefix.clone())),
+                NodeRef::Attribute(_, name) => (2, Some(name.clone())),
+            };
+            let position = self.doc.document_position(node.id()).unwrap_or(usize::MAX);
+            (position, rank, name)
+        };
+        key(a).cmp(&key(b))
+    }
+
+    /// Keeps the nodes for which the predicate holds. The nodes are in the order of the axis that
+    /// selected them, which is what their positions count.
+    fn filter(&self, nodes: Vec<NodeRef>, predicate: &Expr) -> Result<Vec<NodeRef>> {
+        let size = nodes.len();
+        let mut kept = Vec::new();
+        for (i, node) in nodes.into_iter().enumerate() {
+            let context = Context {
+                node,
+                position: i + 1,
+                size,
+            };
+            let keep = match self.evaluate(predicate, &context)? {
+                Value::Number(n) => n == context.position as f64,
+                value => value.boolean(),
+            };
+            if keep {
+                kept.push(context.node);
+            }
+        }
+        Ok(kept)
+    }
+
+    fn step(&self, nodes: &[NodeRef], step: &Step) -> Result<Vec<NodeRef>> {
+        let mut result = Vec::new();
+        for node in nodes {
+            let mut selected = Vec::new();
+            for candidate in self.axis(node, step.axis) {
+                if self.matches(&candidate, step.axis, &step.test)? {
+                    selected.push(candidate);
+                }
+            }
+            for predicate in &step.predicates {
+                selected = self.filter(selected, predicate)?;
+            }
+            result.extend(selected);
+        }
+        // the nodes on an axis from one node are in document order, or in reverse document order
+        // on a reverse axis, so only a step from several nodes needs the order of the document
+        if let [_] = nodes {
+            if step.axis.is_reverse() {
+                result.reverse();
+            }
+            return Ok(result);
+        }
+        Ok(self.sorted(result))
+    }
+
+    /// Returns the nodes on the axis, nearest first.
+    fn axis(&self, node: &NodeRef, axis: Axis) -> Vec<NodeRef> {
+        let doc = self.doc;
+        let id = match node {
+            NodeRef::Node(id) => *id,
+            NodeRef::Attribute(id, _) | NodeRef::Namespace(id, _) => {
+                let id = *id;
+                return match axis {
+                    Axis::SelfNode => vec![node.clone()],
+                    Axis::Parent => vec![NodeRef::Node(id)],
+                    Axis::Ancestor | Axis::AncestorOrSelf => {
+                        let mut nodes = Vec::new();
+                        if axis == Axis::AncestorOrSelf {
+                            nodes.push(node.clone());
+                        }
+                        nodes.extend(once(id).chain(doc.ancestors(id)).map(NodeRef::Node));
+                        nodes
+                    }
+                    // the element's children come after its attributes
+                    Axis::Following => {
+                        let mut nodes = self.visible(doc.descendants(id));
+                        nodes.extend(self.following(id));
+                        nodes
+                    }
+                    Axis::Preceding => self.preceding(id),
+                    _ => Vec::new(),
+                };
+            }
+        };
+        match axis {
+            Axis::Child => self.visible(doc.children(id)),
+            Axis::Descendant => self.visible(doc.descendants(id)),
+            Axis::DescendantOrSelf => self.visible(once(id).chain(doc.descendants(id))),
+            Axis::Parent => doc.parent(id).map(NodeRef::Node).into_iter().collect(),
+            Axis::Ancestor => doc.ancestors(id).map(NodeRef::Node).collect(),
+            Axis::AncestorOrSelf => once(id)
+                .chain(doc.ancestors(id))
+                .map(NodeRef::Node)
+                .collect(),
+            Axis::FollowingSibling => self.visible(doc.following_siblings(id)),
+            Axis::PrecedingSibling => self.visible(doc.preceding_siblings(id)),
+            Axis::Following => self.following(id),
+            Axis::Preceding => self.preceding(id),
+            Axis::Attribute => self.attributes(id),
+            Axis::Namespace => match doc.element(id) {
+                Some(_) => in_scope_namespaces(doc, id)
+                    .into_keys()
+                    .map(|prefix| NodeRef::Namespace(id, prefix.into()))
+                    .collect(),
+                None => Vec::new(),
+            },
+            Axis::SelfNode => vec![node.clone()],
+        }
+    }
+
+    /// Leaves out the nodes that are not in the XPath data model: DOCTYPEs, and text that
+    /// continues the text node of a preceding sibling.
+    fn visible<I: IntoIterator<Item = NodeId>>(&self, ids: I) -> Vec<NodeRef> {
+        ids.into_iter()
+            .filter(|&id| match self.doc.node(id) {
+                Node::DocType(_) => false,
+                Node::String(_) | Node::CData(_) => !self
+                    .doc
+                    .previous_sibling(id)
+                    .is_some_and(|previous| is_text(self.doc.node(previous))),
+                _ => true,
+            })
+            .map(NodeRef::Node)
+            .collect()
+    }
+
+    fn following(&self, id: NodeId) -> Vec<NodeRef> {
+        let mut ids = Vec::new();
+        let mut current = Some(id);
+        while let Some(node) = current {
+            for sibling in self.doc.following_siblings(node) {
+                ids.push(sibling);
+                ids.extend(self.doc.descendants(sibling));
+            }
+            current = self.doc.parent(node);
+        }
+        self.visible(ids)
+    }
+
+    fn preceding(&self, id: NodeId) -> Vec<NodeRef> {
+        let mut ids = Vec::new();
+        let mut current = Some(id);
+        while let Some(node) = current {
+            for sibling in self.doc.preceding_siblings(node) {
+                let start = ids.len();
+                ids.push(sibling);
+                ids.extend(self.doc.descendants(sibling));
+                ids[start..].reverse();
+            }
+            current = self.doc.parent(node);
+        }
+        self.visible(ids)
+    }
+
+    fn attributes(&self, id: NodeId) -> Vec<NodeRef> {
+        let element = match self.doc.element(id) {
+            Some(element) => element,
+            None => return Vec::new(),
+        };
+        let mut names = element
+            .attributes
+            .map()
+            .keys()
+            .filter(|name| !is_namespace_declaration(name))
+            .cloned()
+            .collect::<Vec<Symbol>>();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| NodeRef::Attribute(id, name))
+            .collect()
+    }
+
+    fn matches(&self, node: &NodeRef, axis: Axis, test: &NodeTest) -> Result<bool> {
+        let arena_node = match node {
+            NodeRef::Node(id) => Some(self.doc.node(*id)),
+            _ => None,
+        };
+        Ok(match test {
+            NodeTest::Node => true,
+            NodeTest::Text => arena_node.is_some_and(is_text),
+            NodeTest::Comment => matches!(arena_node, Some(Node::Comment(_))),
+            NodeTest::ProcessingInstruction(target) => match arena_node {
+                Some(Node::ProcessingInstruction(pi)) => {
+                    target.as_ref().is_none_or(|t| *t == pi.target)
+                }
+                _ => false,
+            },
+            NodeTest::Name { prefix, local } => {
+                // a name test only selects nodes of the axis's principal node type
+                let principal = match (axis, node) {
+                    (Axis::Attribute, NodeRef::Attribute(..)) => true,
+                    (Axis::Namespace, NodeRef::Namespace(..)) => true,
+                    (Axis::Attribute, _) | (Axis::Namespace, _) => false,
+                    (_, _) => matches!(arena_node, Some(Node::Element(_))),
+                };
+                if !principal {
+                    return Ok(false);
+                }
+                if prefix.is_none() && local.is_none() {
+                    return Ok(true);
+                }
+                let (uri, name) = expanded_name(self.doc, node);
+                let wanted = match prefix {
+                    Some(prefix) => Some(self.prefix_uri(prefix)?),
+                    None => None,
+                };
+                uri.as_deref() == wanted && local.as_ref().is_none_or(|local| *local == name)
+            }
+        })
+    }
+
+    fn prefix_uri(&self, prefix: &str) -> Result<&str> {
+        if prefix == "xml" {
+            return Ok(XML_NAMESPACE);
+        }
+        match self.opts.namespaces.get(prefix) {
+            Some(uri) => Ok(uri),
+            None => raise!("The namespace prefix '{}' is not bound.", prefix),
+        }
+    }
+
+    fn compare(&self, op: Comparison, left: &Value, right: &Value) -> bool {
+        let strings = |nodes: &[NodeRef]| -> Vec<Value> {
+            nodes
+                .iter()
+                .map(|node| Value::String(string_value(self.doc, node)))
+                .collect()
+        };
+        match (left, right) {
+            (Value::Nodes(left), Value::Nodes(right)) => {
+                let right = strings(right);
+                strings(left)
+                    .iter()
+                    .any(|a| right.iter().any(|b| self.compare_atoms(op, a, b)))
+            }
+            (Value::Nodes(nodes), Value::Boolean(_)) | (Value::Boolean(_), Value::Nodes(nodes)) => {
+                let nodes = Value::Boolean(!nodes.is_empty());
+                match left {
+                    Value::Nodes(_) => self.compare_atoms(op, &nodes, right),
+                    _ => self.compare_atoms(op, left, &nodes),
+                }
+            }
+            (Value::Nodes(nodes), other) => strings(nodes)
+                .iter()
+                .any(|node| self.compare_atoms(op, node, other)),
+            (other, Value::Nodes(nodes)) => strings(nodes)
+                .iter()
+                .any(|node| self.compare_atoms(op, other, node)),
+            _ => self.compare_atoms(op, left, right),
+        }
+    }
+
+    fn compare_atoms(&self, op: Comparison, left: &Value, right: &Value) -> bool {
+        let doc = self.doc;
+        let either = |f: fn(&Value) -> bool| f(left) || f(right);
+        match op {
+            Comparison::Equal | Comparison::NotEqual => {
+                let equal = if either(|v| matches!(v, Value::Boolean(_))) {
+                    left.boolean() == right.boolean()
+                } else if either(|v| matches!(v, Value::Number(_))) {
+                    left.number(doc) == right.number(doc)
+                } else {
+                    left.string(doc) == right.string(doc)
+                };
+                equal == (op == Comparison::Equal)
+            }
+            _ => {
+                let (left, right) = (left.number(doc), right.number(doc));
+                match op {
+                    Comparison::Less => left < right,
+                    Comparison::LessEqual => left <= right,
+                    Comparison::Greater => left > right,
+                    _ => left >= right,
+                }
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Converts the value as the XPath `boolean()` function does.
+    pub fn boolean(&self) -> bool {
+        match self {
+            Value::Nodes(nodes) => !nodes.is_empty(),
+            Value::String(s) => !s.is_empty(),
+            Value::Number(n) => *n != 0.0 && !n.is_nan(),
+            Value::Boolean(b) => *b,
+        }
+    }
+
+    /// Converts the value as the XPath `number()` function does.
+    pub fn number(&self, doc: &Document) -> f64 {
+        match self {
+            Value::Nodes(_) => parse_number(&self.string(doc)),
+            Value::String(s) => parse_number(s),
+            Value::Number(n) => *n,
+            Value::Boolean(b) => {
+                if *b {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Converts the value as the XPath `string()` function does. The string value of a node-set
+    /// is that of its first node.
+    pub fn string(&self, doc: &Document) -> String {
+        match self {
+            Value::Nodes(nodes) => nodes
+                .first()
+                .map(|node| string_value(doc, node))
+                .unwrap_or_default(),
+            Value::String(s) => s.clone(),
+            Value::Number(n) => format_number(*n),
+            Value::Boolean(b) => b.to_string(),
+        }
+    }
+}
+
+/// Returns the XPath string value of a node: the text below it for the document node and
+/// elements, and otherwise the node's own value.
+pub fn string_value(doc: &Document, node: &NodeRef) -> String {
+    match node {
+        NodeRef::Node(id) => match doc.node(*id) {
+            Node::Document | Node::Element(_) => doc
+                .descendants(*id)
+                .filter_map(|d| text(doc.node(d)))
+                .collect(),
+            // the rest of the run of adjacent text belongs to this text node
+            Node::String(_) | Node::CData(_) => once(*id)
+                .chain(doc.following_siblings(*id))
+                .map_while(|s| text(doc.node(s)))
+                .collect(),
+            Node::Comment(s) | Node::DocType(s) => s.clone(),
+            Node::ProcessingInstruction(pi) => {
+                let mut keys = pi.instructions.map().keys().collect::<Vec<_>>();
+                keys.sort();
+                keys.iter()
+                    .map(|key| format!("{}=\"{}\"", key, pi.instructions.map()[*key]))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+        },
+        NodeRef::Attribute(id, name) => doc
+            .element(*id)
+            .and_then(|element| element.attributes.map().get(name))
+            .cloned()
+            .unwrap_or_default(),
+        NodeRef::Namespace(id, prefix) => namespace_uri(doc, *id, prefix).unwrap_or_default(),
+    }
+}
+
+/// Returns the namespace URI and local name of a node. Nodes without a name have an empty one.
+pub(super) fn expanded_name(doc: &Document, node: &NodeRef) -> (Option<String>, String) {
+    match node {
+        NodeRef::Node(id) => match doc.node(*id) {
+            Node::Element(element) => (
+                namespace_uri(doc, *id, element.namespace.as_deref().unwrap_or("")),
+                element.name.to_string(),
+            ),
+            Node::ProcessingInstruction(pi) => (None, pi.target.clone()),
+            _ => (None, String::new()),
+        },
+        NodeRef::Attribute(id, name) => match name.split_once(':') {
+            Some((prefix, local)) => (namespace_uri(doc, *id, prefix), local.to_string()),
+            None => (None, name.to_string()),
+        },
+        NodeRef::Namespace(_, prefix) => (None, prefix.to_string()),
+    }
+}
+
+/// Returns the node's name as it is written in the document, with its prefix.
+pub(super) fn qualified_name(doc: &Document, node: &NodeRef) -> String {
+    match node {
+        NodeRef::Node(id) => match doc.node(*id) {
+            Node::Element(element) => match &element.namespace {
+                Some(prefix) => format!("{}:{}", prefix, element.name),
+                None => element.name.to_string(),
+            },
+            Node::ProcessingInstruction(pi) => pi.target.clone(),
+            _ => String::new(),
+        },
+        NodeRef::Attribute(_, name) | NodeRef::Namespace(_, name) => name.to_string(),
+    }
+}
+
+/// Looks up the namespace URI bound to `prefix` where the node is, using the empty prefix for the
+/// default namespace.
+fn namespace_uri(doc: &Document, id: NodeId, prefix: &str) -> Option<String> {
+    if prefix == "xml" {
+        return Some(XML_NAMESPACE.to_string());
+    }
+    let key = match prefix {
+        "" => "xmlns".to_string(),
+        _ => format!("xmlns:{}", prefix),
+    };
+    once(id)
+        .chain(doc.ancestors(id))
+        .filter_map(|ancestor| doc.element(ancestor))
+        .find_map(|element| element.attributes.map().get(key.as_str()))
+        .filter(|uri| !uri.is_empty())
+        .cloned()
+}
+
+/// Returns the URI of every namespace in scope on the element, by prefix.
+fn in_scope_namespaces(doc: &Document, id: NodeId) -> BTreeMap<String, String> {
+    let mut namespaces = BTreeMap::new();
+    namespaces.insert("xml".to_string(), XML_NAMESPACE.to_string());
+    // the nearest declaration of a prefix wins
+    for ancestor in once(id).chain(doc.ancestors(id)) {
+        if let Some(element) = doc.element(ancestor) {
+            for (name, uri) in element.attributes.map() {
+                let prefix = match name.as_str() {
+                    "xmlns" => "",
+                    _ => match name.strip_prefix("xmlns:") {
+                        Some(prefix) => prefix,
+                        None => continue,
+                    },
+                };
+                namespaces
+                    .entry(prefix.to_string())
+                    .or_insert_with(|| uri.clone());
+            }
+        }
+    }
+    // xmlns="" takes away the default namespace
+    namespaces.retain(|_, uri| !uri.is_empty());
+    namespaces
+}
+
+fn is_namespace_declaration(name: &str) -> bool {
+    name == "xmlns" || name.starts_with("xmlns:")
+}
+
+fn is_text(node: &Node) -> bool {
+    matches!(node, Node::String(_) | Node::CData(_))
+}
+
+fn text(node: &Node) -> Option<&str> {
+    match node {
+        Node::String(s) | Node::CData(s) => Some(s),
+        _ => None,
+    }
+}
+
+/// Parses a string as the XPath `number()` function does: an optional minus sign and a decimal
+/// number, with whitespace around it, and anything else is NaN.
+pub(super) fn parse_number(s: &str) -> f64 {
+    let s = s.trim_matches(is_whitespace);
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    let is_valid = digits.chars().any(|c| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && digits.matches('.').count() <= 1;
+    if is_valid {
+        s.parse().unwrap_or(f64::NAN)
+    } else {
+        f64::NAN
+    }
+}
+
+pub(super) fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        "NaN".to_string()
+    } else if n.is_infinite() {
+        if n > 0.0 {
+            "Infinity".to_string()
+        } else {
+            "-Infinity".to_string()
+        }
+    } else if n == 0.0 {
+        // without a sign for negative zero
+        "0".to_string()
+    } else {
+        // never in exponential notation
+        n.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_to_and_from_strings() {
+        assert_eq!(parse_number(" -12.5\n"), -12.5);
+        assert_eq!(parse_number(".5"), 0.5);
+        assert_eq!(parse_number("5."), 5.0);
+        for s in &["", ".", "-", "1e3", "+1", "inf", "NaN", "1.2.3", "1 2"] {
+            assert!(parse_number(s).is_nan(), "{}", s);
+        }
+        assert_eq!(format_number(1.0), "1");
+        assert_eq!(format_number(-0.0), "0");
+        assert_eq!(format_number(0.25), "0.25");
+        assert_eq!(format_number(1e21), "1000000000000000000000");
+        assert_eq!(format_number(f64::NAN), "NaN");
+        assert_eq!(format_number(f64::NEG_INFINITY), "-Infinity");
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+use std::iter::once;
+
+use crate::error::Result;
+use crate::xpath::eval::{expanded_name, parse_number, qualified_name, Context, Evaluator};
+use crate::xpath::lexer::is_whitespace;
+use crate::xpath::parser::Expr;
+use crate::xpath::{string_value, NodeRef, Value};
+
+impl Evaluator<'_> {
+    /// Calls a function of the XPath 1.0 core function library.
+    pub(super) fn call(&self, name: &str, args: &[Expr], context: &Context) -> Result<Value> {
+        let (min, max) = match arity(name) {
+            Some(arity) => arity,
+            None => return raise!("Unknown XPath function '{}()'.", name),
+        };
+        if args.len() < min || max.is_some_and(|max| args.len() > max) {
+            return raise!(
+                "Wrong number of arguments, {}, for '{}()'.",
+                args.len(),
+                name
+            );
+        }
+        let doc = self.doc;
+        let arg = |i: usize| self.evaluate(&args[i], context);
+        let string = |i: usize| -> Result<String> { Ok(arg(i)?.string(doc)) };
+        let number = |i: usize| -> Result<f64> { Ok(arg(i)?.number(doc)) };
+        // the argument, or the context node if it is left out
+        let string_or_context = || -> Result<String> {
+            match args.is_empty() {
+                true => Ok(string_value(doc, &context.node)),
+                false => string(0),
+            }
+        };
+        let node_or_context = || -> Result<Option<NodeRef>> {
+            match args.is_empty() {
+                true => Ok(Some(context.node.clone())),
+                false => Ok(self.node_set(arg(0)?)?.into_iter().next()),
+            }
+        };
+        Ok(match name {
+            // node-set functions
+            "last" => Value::Number(context.size as f64),
+            "position" => Value::Number(context.position as f64),
+            "count" => Value::Number(self.node_set(arg(0)?)?.len() as f64),
+            "id" => Value::Nodes(self.id(arg(0)?, context)),
+            "local-name" => Value::String(
+                node_or_context()?
+                    .map(|node| expanded_name(doc, &node).1)
+                    .unwrap_or_default(),
+            ),
+            "namespace-uri" => Value::String(
+                node_or_context()?
+                    .and_then(|node| expanded_name(doc, &node).0)
+                    .unwrap_or_default(),
+            ),
+            "name" => Value::String(
+                node_or_context()?
+                    .map(|node| qualified_name(doc, &node))
+                    .unwrap_or_default(),
+            ),
+
+            // string functions
+            "string" => Value::String(string_or_context()?),
+            "concat" => {
+                let mut s = String::new();
+                for i in 0..args.len() {
+                    s.push_str(&string(i)?);
+                }
+                Value::String(s)
+            }
+            "starts-with" => Value::Boolean(string(0)?.starts_with(&string(1)?)),
+            "contains" => Value::Boolean(string(0)?.contains(&string(1)?)),
+            "substring-before" => {
+                let (s, pattern) = (string(0)?, string(1)?);
+                Value::String(
+                    s.split_once(pattern.as_str())
+                        .map(|(before, _)| before.to_string())
+                        .unwrap_or_default(),
+                )
+            }
+            "substring-after" => {
+                let (s, pattern) = (string(0)?, string(1)?);
+                Value::String(
+                    s.split_once(pattern.as_str())
+                        .map(|(_, after)| after.to_string())
+                        .unwrap_or_default(),
+                )
+            }
+            "substring" => {
+                let s = string(0)?;
+                let start = round(number(1)?);
+                let end = match args.len() {
+                    3 => start + round(number(2)?),
+                    _ => f64::INFINITY,
+                };
+                // positions count characters from 1, and NaN compares false with everything
+                Value::String(
+                    s.chars()
+                        .zip(1..)
+                        .filter(|&(_, i)| i as f64 >= start && (i as f64) < end)
+                        .map(|(c, _)| c)
+                        .collect(),
+                )
+            }
+            "string-length" => Value::Number(string_or_context()?.chars().count() as f64),
+            "normalize-space" => Value::String(
+                string_or_context()?
+                    .split(is_whitespace)
+                    .filter(|word| !word.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            "translate" => {
+                let (s, from, to) = (string(0)?, string(1)?, string(2)?);
+                let from = from.chars().collect::<Vec<_>>();
+                let to = to.chars().collect::<Vec<_>>();
+                // the first occurrence of a character in `from` decides, and a character with no
+                // counterpart in `to` is removed
+                Value::String(
+                    s.chars()
+                        .filter_map(|c| match from.iter().position(|&f| f == c) {
+                            Some(i) => to.get(i).copied(),
+                            None => Some(c),
+                        })
+                        .collect(),
+                )
+            }
+
+            // boolean functions
+            "boolean" => Value::Boolean(arg(0)?.boolean()),
+            "not" => Value::Boolean(!arg(0)?.boolean()),
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            "lang" => Value::Boolean(self.lang(&string(0)?, context)),
+
+            // number functions
+            "number" => Value::Number(match args.is_empty() {
+                true => parse_number(&string_value(doc, &context.node)),
+                false => number(0)?,
+            }),
+            "sum" => Value::Number(
+                self.node_set(arg(0)?)?
+                    .iter()
+                    .map(|node| parse_number(&string_value(doc, node)))
+                    .sum(),
+            ),
+            "floor" => Value::Number(number(0)?.floor()),
+            "ceiling" => Value::Number(number(0)?.ceil()),
+            _ => Value::Number(round(number(0)?)),
+        })
+    }
+
+    /// Selects the elements whose ID is one of the whitespace separated tokens of the argument, or
+    /// of the string values of its nodes.
+    fn id(&self, arg: Value, context: &Context) -> Vec<NodeRef> {
+        let doc = self.doc;
+        let strings = match &arg {
+            Value::Nodes(nodes) => nodes.iter().map(|n| string_value(doc, n)).collect(),
+            other => vec![other.string(doc)],
+        };
+        let tokens = strings
+            .iter()
+            .flat_map(|s| s.split(is_whitespace))
+            .filter(|token| !token.is_empty())
+            .collect::<HashSet<_>>();
+        let id = context.node.id();
+        let top = doc.ancestors(id).last().unwrap_or(id);
+        let mut found = HashSet::new();
+        let mut nodes = Vec::new();
+        for element_id in doc.descendants(top) {
+            let element = match doc.element(element_id) {
+                Some(element) => element,
+                None => continue,
+            };
+            let value = element
+                .attributes
+                .map()
+                .get("xml:id")
+                .or_else(|| element.attributes.map().get("id"));
+            // only the first element with an ID counts
+            if let Some(value) = value {
+                if tokens.contains(value.as_str()) && found.insert(value.as_str()) {
+                    nodes.push(NodeRef::Node(element_id));
+                }
+            }
+        }
+        nodes
+    }
+
+    /// Returns `true` if the `xml:lang` in scope is `lang`, or a sublanguage of it, ignoring case.
+    fn lang(&self, lang: &str, context: &Context) -> bool {
+        let doc = self.doc;
+        let id = context.node.id();
+        let in_scope = once(id)
+            .chain(doc.ancestors(id))
+            .filter_map(|ancestor| doc.element(ancestor))
+            .find_map(|element| element.attributes.map().get("xml:lang"));
+        match in_scope {
+            Some(value) => {
+                let (value, lang) = (value.to_lowercase(), lang.to_lowercase());
+                value == lang
+                    || value
+                        .strip_prefix(lang.as_str())
+                        .is_some_and(|rest| rest.starts_with('-'))
+            }
+            None => false,
+        }
+    }
+}
+
+/// The least and most number of arguments each function takes, where `None` is no limit.
+fn arity(name: &str) -> Option<(usize, Option<usize>)> {
+    Some(match name {
+        "last" | "position" | "true" | "false" => (0, Some(0)),
+        "local-name" | "namespace-uri" | "name" | "string" | "string-length"
+        | "normalize-space" | "number" => (0, Some(1)),
+        "count" | "id" | "boolean" | "not" | "lang" | "sum" | "floor" | "ceiling" | "round" => {
+            (1, Some(1))
+        }
+        "starts-with" | "contains" | "substring-before" | "substring-after" => (2, Some(2)),
+        "substring" => (2, Some(3)),
+        "translate" => (3, Some(3)),
+        "concat" => (2, None),
+        _ => return None,
+    })
+}
+
+/// Rounds to the nearest integer, and towards positive infinity at a half.
+fn round(n: f64) -> f64 {
+    if n.is_nan() || n.is_infinite() {
+        n
+    } else if (-0.5..0.0).contains(&n) {
+        -0.0
+    } else {
+        (n + 0.5).floor()
+    }
+}
@@ -0,0 +1,352 @@
+use crate::chars::{is_name_char, is_name_start_char};
+use crate::error::Result;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Token {
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    Dot,
+    DotDot,
+    At,
+    Comma,
+    ColonColon,
+    Slash,
+    DoubleSlash,
+    Pipe,
+    Plus,
+    Minus,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Multiply,
+    And,
+    Or,
+    Mod,
+    Div,
+    Literal(String),
+    Number(f64),
+    // the qualified name after a '$'
+    Variable(String),
+    FunctionName(String),
+    // comment, text, processing-instruction or node, when followed by '('
+    NodeType(String),
+    // when followed by '::'
+    AxisName(String),
+    // '*' has neither, 'prefix:*' has no local name
+    NameTest {
+        prefix: Option<String>,
+        local: Option<String>,
+    },
+}
+
+/// Splits an expression into tokens, using the rules in section 3.7 of the XPath 1.0
+/// recommendation to tell a `*` or a name that is an operator from one that is a name test.
+pub(super) fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    Lexer {
+        chars: expression.chars().collect(),
+        pos: 0,
+        tokens: Vec::new(),
+    }
+    .run()
+}
+
+pub(super) fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    tokens: Vec<Token>,
+}
+
+impl Lexer {
+    fn run(mut self) -> Result<Vec<Token>> {
+        loop {
+            self.skip_whitespace();
+            let c = match self.peek(0) {
+                Some(c) => c,
+                None => return Ok(self.tokens),
+            };
+            let token = match c {
+                '(' => self.single(Token::LeftParen),
+                ')' => self.single(Token::RightParen),
+                '[' => self.single(Token::LeftBracket),
+                ']' => self.single(Token::RightBracket),
+                '@' => self.single(Token::At),
+                ',' => self.single(Token::Comma),
+                '|' => self.single(Token::Pipe),
+                '+' => self.single(Token::Plus),
+                '-' => self.single(Token::Minus),
+                '=' => self.single(Token::Equal),
+                '.' if self.peek(1) == Some('.') => self.double(Token::DotDot),
+                '.' if self.peek(1).is_some_and(|c| c.is_ascii_digit()) => self.number()?,
+                '.' => self.single(Token::Dot),
+                '/' if self.peek(1) == Some('/') => self.double(Token::DoubleSlash),
+                '/' => self.single(Token::Slash),
+                '!' if self.peek(1) == Some('=') => self.double(Token::NotEqual),
+                '<' if self.peek(1) == Some('=') => self.double(Token::LessEqual),
+                '<' => self.single(Token::Less),
+                '>' if self.peek(1) == Some('=') => self.double(Token::GreaterEqual),
+                '>' => self.single(Token::Greater),
+                ':' if self.peek(1) == Some(':') => self.double(Token::ColonColon),
+                '"' | '\'' => self.literal(c)?,
+                '0'..='9' => self.number()?,
+                '$' => {
+                    self.pos += 1;
+                    match self.qualified_name() {
+                        Some(name) => Token::Variable(name),
+                        None => return raise!("Expected a variable name after '$'."),
+                    }
+                }
+                '*' if self.operator_expected() => self.single(Token::Multiply),
+                '*' => self.single(Token::NameTest {
+                    prefix: None,
+                    local: None,
+                }),
+                c if is_name_start_char(c) && c != ':' => self.name()?,
+                c => return raise!("Unexpected '{}' in XPath expression.", c),
+            };
+            self.tokens.push(token);
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek(0).is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn single(&mut self, token: Token) -> Token {
+        self.pos += 1;
+        token
+    }
+
+    fn double(&mut self, token: Token) -> Token {
+        self.pos += 2;
+        token
+    }
+
+    /// A `*` is the multiply operator, and a name is an operator name, when there is a preceding
+    /// token and it is not `@`, `::`, `(`, `[`, `,` or an operator.
+    fn operator_expected(&self) -> bool {
+        use Token::*;
+        match self.tokens.last() {
+            None => false,
+            Some(token) => !matches!(
+                token,
+                At | ColonColon
+                    | LeftParen
+                    | LeftBracket
+                    | Comma
+                    | Slash
+                    | DoubleSlash
+                    | Pipe
+                    | Plus
+                    | Minus
+                    | Equal
+                    | NotEqual
+                    | Less
+                    | LessEqual
+                    | Greater
+                    | GreaterEqual
+                    | Multiply
+                    | And
+                    | Or
+                    | Mod
+                    | Div
+            ),
+        }
+    }
+
+    fn literal(&mut self, quote: char) -> Result<Token> {
+        let start = self.pos + 1;
+        match self.chars[start..].iter().position(|&c| c == quote) {
+            Some(len) => {
+                self.pos = start + len + 1;
+                Ok(Token::Literal(
+                    self.chars[start..start + len].iter().collect(),
+                ))
+            }
+            None => raise!("Unterminated string literal in XPath expression."),
+        }
+    }
+
+    fn number(&mut self) -> Result<Token> {
+        let start = self.pos;
+        let mut seen_dot = false;
+        while let Some(c) = self.peek(0) {
+            match c {
+                '0'..='9' => {}
+                '.' if !seen_dot => seen_dot = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        match text.parse() {
+            Ok(n) => Ok(Token::Number(n)),
+            Err(e) => wrap!(e, "Invalid number '{}' in XPath expression.", text),
+        }
+    }
+
+    fn ncname(&mut self) -> Option<String> {
+        let start = self.pos;
+        match self.peek(0) {
+            Some(c) if is_name_start_char(c) && c != ':' => self.pos += 1,
+            _ => return None,
+        }
+        while self.peek(0).is_some_and(|c| is_name_char(c) && c != ':') {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn qualified_name(&mut self) -> Option<String> {
+        let prefix = self.ncname()?;
+        if self.peek(0) == Some(':') && self.peek(1) != Some(':') {
+            self.pos += 1;
+            let local = self.ncname()?;
+            return Some(format!("{}:{}", prefix, local));
+        }
+        Some(prefix)
+    }
+
+    fn name(&mut self) -> Result<Token> {
+        let name = self.ncname().unwrap_or_default();
+        if self.operator_expected() {
+            return match name.as_str() {
+                "and" => Ok(Token::And),
+                "or" => Ok(Token::Or),
+                "mod" => Ok(Token::Mod),
+                "div" => Ok(Token::Div),
+                _ => raise!("Expected an operator but found '{}'.", name),
+            };
+        }
+        if self.peek(0) == Some(':') && self.peek(1) != Some(':') {
+            self.pos += 1;
+            if self.peek(0) == Some('*') {
+                self.pos += 1;
+                return Ok(Token::NameTest {
+                    prefix: Some(name),
+                    local: None,
+                });
+            }
+            let local = match self.ncname() {
+                Some(local) => local,
+                None => return raise!("Expected a local name after '{}:'.", name),
+            };
+            self.skip_whitespace();
+            if self.peek(0) == Some('(') {
+                return Ok(Token::FunctionName(format!("{}:{}", name, local)));
+            }
+            return Ok(Token::NameTest {
+                prefix: Some(name),
+                local: Some(local),
+            });
+        }
+        self.skip_whitespace();
+        match (self.peek(0), self.peek(1)) {
+            (Some('('), _) => match name.as_str() {
+                "comment" | "text" | "processing-instruction" | "node" => Ok(Token::NodeType(name)),
+                _ => Ok(Token::FunctionName(name)),
+            },
+            (Some(':'), Some(':')) => Ok(Token::AxisName(name)),
+            _ => Ok(Token::NameTest {
+                prefix: None,
+                local: Some(name),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(prefix: Option<&str>, local: Option<&str>) -> Token {
+        Token::NameTest {
+            prefix: prefix.map(String::from),
+            local: local.map(String::from),
+        }
+    }
+
+    #[test]
+    fn operators_and_names_are_told_apart() {
+        let tokens = tokenize("div div * * * mod mod").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                name(None, Some("div")),
+                Token::Div,
+                name(None, None),
+                Token::Multiply,
+                name(None, None),
+                Token::Mod,
+                name(None, Some("mod")),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_functions_and_axes() {
+        let tokens = tokenize("child :: m:* / x:y[count (a)] | text() | $v:w").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::AxisName("child".to_string()),
+                Token::ColonColon,
+                name(Some("m"), None),
+                Token::Slash,
+                name(Some("x"), Some("y")),
+                Token::LeftBracket,
+                Token::FunctionName("count".to_string()),
+                Token::LeftParen,
+                name(None, Some("a")),
+                Token::RightParen,
+                Token::RightBracket,
+                Token::Pipe,
+                Token::NodeType("text".to_string()),
+                Token::LeftParen,
+                Token::RightParen,
+                Token::Pipe,
+                Token::Variable("v:w".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_literals_and_dots() {
+        let tokens = tokenize(".5 . .. 12. 3.25 'a\"b' \"c\"").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Number(0.5),
+                Token::Dot,
+                Token::DotDot,
+                Token::Number(12.0),
+                Token::Number(3.25),
+                Token::Literal("a\"b".to_string()),
+                Token::Literal("c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_tokens() {
+        assert!(tokenize("'open").is_err());
+        assert!(tokenize("a ! b").is_err());
+        assert!(tokenize("1 cat").is_err());
+        assert!(tokenize("$").is_err());
+        assert!(tokenize("a:").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+//! An XPath 1.0 query engine over the arena `Document`, which has the parent and sibling links
+//! that the axes need.
+//!
+//! Name tests are namespace aware. A prefix in an expression is looked up in
+//! `XPathOpts::namespaces`, not in the document, and an unprefixed name only matches an element
+//! with no namespace, even where the document declares a default namespace. The `xml` prefix is
+//! always bound.
+//!
+//! Adjacent text and CDATA siblings make up one XPath text node, which is referred to by the first
+//! of them. A DOCTYPE is not part of the XPath data model and is never selected. Since the DTD is
+//! not read, `id()` treats attributes named `id` and `xml:id` as IDs.
+//!
+//! ```
+//! use xdoc::arena::Document;
+//! use xdoc::xpath::{Value, XPath, XPathOpts};
+//! use xdoc::xml;
+//!
+//! let root = xml! {
+//!     cats {
+//!         cat(name = "bones", age = "3"),
+//!         cat(name = "bishop", age = "7"),
+//!     }
+//! };
+//! let doc = Document::from(&xdoc::Document::from_root(root.unwrap()));
+//! let opts = XPathOpts::default();
+//!
+//! let old = XPath::parse("//cat[@age > 5]/@name").unwrap();
+//! let names = old.evaluate(&doc, &opts).unwrap();
+//! assert_eq!(names.string(&doc), "bishop");
+//!
+//! let total = XPath::parse("sum(/cats/cat/@age)").unwrap();
+//! assert_eq!(total.evaluate(&doc, &opts).unwrap(), Value::Number(10.0));
+//! ```
+
+use std::collections::HashMap;
+
+use crate::arena::{Document, NodeId};
+use crate::error::Result;
+use crate::Symbol;
+
+pub use eval::string_value;
+
+mod eval;
+mod functions;
+mod lexer;
+mod parser;
+
+pub(crate) const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// A parsed XPath 1.0 expression, which can be evaluated any number of times.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XPath {
+    expr: parser::Expr,
+}
+
+/// The context that an expression is evaluated in, besides the context node.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XPathOpts {
+    /// Binds the namespace prefixes used in the expression to namespace URIs.
+    pub namespaces: HashMap<String, String>,
+    /// The values of the variables used in the expression, by qualified name without the `$`.
+    pub variables: HashMap<String, Value>,
+}
+
+/// The result of an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A node-set, in document order and without duplicates.
+    Nodes(Vec<NodeRef>),
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+/// A node in the XPath data model. Attributes and namespaces are not nodes in the arena, so they
+/// are referred to through the element they belong to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NodeRef {
+    Node(NodeId),
+    /// An attribute of the element, by qualified name.
+    Attribute(NodeId, Symbol),
+    /// A namespace in scope on the element, by prefix. The default namespace has an empty prefix.
+    Namespace(NodeId, Symbol),
+}
+
+impl XPath {
+    pub fn parse(expression: &str) -> Result<Self> {
+        Ok(XPath {
+            expr: parser::parse(expression)?,
+        })
+    }
+
+    /// Evaluates the expression with the document node as the context node.
+    pub fn evaluate(&self, doc: &Document, opts: &XPathOpts) -> Result<Value> {
+        self.evaluate_at(doc, doc.document_node(), opts)
+    }
+
+    /// Evaluates the expression with `context` as the context node.
+    pub fn evaluate_at(&self, doc: &Document, context: NodeId, opts: &XPathOpts) -> Result<Value> {
+        eval::Evaluator::new(doc, opts).evaluate_expr(&self.expr, NodeRef::Node(context))
+    }
+}
+
+impl NodeRef {
+    /// The arena node, or for an attribute or a namespace, the element that it belongs to.
+    pub fn id(&self) -> NodeId {
+        match self {
+            NodeRef::Node(id) | NodeRef::Attribute(id, _) | NodeRef::Namespace(id, _) => *id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{xml, Node, PIData};
+
+    fn cats() -> Document {
+        let root = xml! {
+            cats("xmlns:x" = "urn:x", "xml:lang" = "en") {
+                cat(name = "bones") { "big", @cdata(" & "), "small" },
+                ["x:cat"]("x:name" = "bishop") { @comment("punk") },
+                cat(name = "jack", "xml:lang" = "de-AT") {
+                    @node(Node::ProcessingInstruction(PIData {
+                        target: "purr".to_string(),
+                        instructions: Default::default(),
+                    })),
+                },
+            }
+        };
+        let mut doc = crate::Document::from_root(root.unwrap());
+        doc.prolog.push(Node::DocType("cats".to_string()));
+        Document::from(&doc)
+    }
+
+    fn opts() -> XPathOpts {
+        let mut opts = XPathOpts::default();
+        opts.namespaces.insert("y".to_string(), "urn:x".to_string());
+        opts.variables
+            .insert("name".to_string(), Value::String("jack".to_string()));
+        opts
+    }
+
+    fn string(expression: &str) -> String {
+        let doc = cats();
+        let value = XPath::parse(expression)
+            .unwrap()
+            .evaluate(&doc, &opts())
+            .unwrap();
+        value.string(&doc)
+    }
+
+    fn count(expression: &str) -> usize {
+        let doc = cats();
+        match XPath::parse(expression)
+            .unwrap()
+            .evaluate(&doc, &opts())
+            .unwrap()
+        {
+            Value::Nodes(nodes) => nodes.len(),
+            other => panic!("{:?} is not a node-set", other),
+        }
+    }
+
+    #[test]
+    fn document_order_follows_changes() {
+        let mut doc = cats();
+        let first =
+            XPath::parse("string((//cat[@name = 'jack'] | //cat[@name = 'bones'])[1]/@name)")
+                .unwrap();
+        let evaluate = |doc: &Document| first.evaluate(doc, &opts()).unwrap().string(doc);
+        assert_eq!(evaluate(&doc), "bones");
+        // a reverse axis from one node still gives the nodes in document order
+        let preceding = XPath::parse("/cats/cat[2]/preceding-sibling::*").unwrap();
+        match preceding.evaluate(&doc, &opts()).unwrap() {
+            Value::Nodes(nodes) => assert_eq!(nodes.len(), 2),
+            other => panic!("{:?} is not a node-set", other),
+        }
+        assert_eq!(string("name(/cats/cat[2]/preceding-sibling::*)"), "cat");
+        assert_eq!(
+            string("name(/cats/cat[2]/preceding-sibling::*[1])"),
+            "x:cat"
+        );
+        let root = doc.root().unwrap();
+        let (bones, jack) = (
+            doc.first_child(root).unwrap(),
+            doc.last_child(root).unwrap(),
+        );
+        doc.insert_before(bones, jack).unwrap();
+        assert_eq!(evaluate(&doc), "jack");
+        doc.remove(jack).unwrap();
+        assert_eq!(evaluate(&doc), "bones");
+    }
+
+    #[test]
+    fn text_runs_are_one_node() {
+        assert_eq!(count("//text()"), 1);
+        assert_eq!(string("//cat[1]/text()"), "big & small");
+        assert_eq!(string("count(//cat[1]/node())"), "1");
+        assert_eq!(string("/cats"), "big & small");
+    }
+
+    #[test]
+    fn names_use_the_callers_prefixes() {
+        assert_eq!(count("/cats/*"), 3);
+        assert_eq!(count("/cats/cat"), 2);
+        assert_eq!(count("/cats/y:cat"), 1);
+        assert_eq!(count("/cats/y:*"), 1);
+        assert_eq!(string("name(//y:cat/@y:name)"), "x:name");
+        assert_eq!(string("//@y:name"), "bishop");
+        assert_eq!(string("namespace-uri(//y:*)"), "urn:x");
+        assert!(XPath::parse("/x:cat")
+            .unwrap()
+            .evaluate(&cats(), &opts())
+            .is_err());
+    }
+
+    #[test]
+    fn attributes_and_namespaces() {
+        // xmlns attributes are only seen on the namespace axis
+        assert_eq!(count("/cats/@*"), 1);
+        assert_eq!(count("/cats/namespace::*"), 2);
+        assert_eq!(count("//cat[2]/namespace::*"), 2);
+        assert_eq!(string("/cats/namespace::x"), "urn:x");
+        assert_eq!(string("name(/cats/namespace::*[1])"), "x");
+    }
+
+    #[test]
+    fn axes_from_attributes() {
+        assert_eq!(string("//@name[. = 'bones']/.."), "big & small");
+        assert_eq!(string("name(//@y:name/following::*)"), "cat");
+        assert_eq!(string("//@y:name/ancestor::*[last()]/@xml:lang"), "en");
+        assert_eq!(count("//@y:name/preceding::node()"), 2);
+        assert_eq!(count("//@name/child::node()"), 0);
+    }
+
+    #[test]
+    fn doctypes_are_invisible() {
+        assert_eq!(count("/node()"), 1);
+        assert_eq!(count("/cats/preceding::node()"), 0);
+    }
+
+    #[test]
+    fn variables_lang_and_pis() {
+        assert_eq!(string("count(//cat[@name = $name])"), "1");
+        assert_eq!(string("count(//*[lang('en')])"), "3");
+        assert_eq!(string("count(//*[lang('DE')])"), "1");
+        assert_eq!(string("name(//processing-instruction('purr'))"), "purr");
+        assert!(XPath::parse("$nope")
+            .unwrap()
+            .evaluate(&cats(), &opts())
+            .is_err());
+    }
+
+    #[test]
+    fn evaluate_at_a_node() {
+        let doc = cats();
+        let jack = doc.descendants(doc.root().unwrap()).nth(6).unwrap();
+        let value = XPath::parse("string(@name)")
+            .unwrap()
+            .evaluate_at(&doc, jack, &opts())
+            .unwrap();
+        assert_eq!(value, Value::String("jack".to_string()));
+        let value = XPath::parse("count(preceding-sibling::*)")
+            .unwrap()
+            .evaluate_at(&doc, jack, &opts())
+            .unwrap();
+        assert_eq!(value, Value::Number(2.0));
+    }
+}
@@ -0,0 +1,530 @@
+use crate::error::Result;
+use crate::xpath::lexer::{tokenize, Token};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Compare(Comparison, Box<Expr>, Box<Expr>),
+    Arithmetic(Arithmetic, Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    Union(Box<Expr>, Box<Expr>),
+    Literal(String),
+    Number(f64),
+    Variable(String),
+    Function(String, Vec<Expr>),
+    // a primary expression followed by predicates
+    Filter(Box<Expr>, Vec<Expr>),
+    // the document node that the context node belongs to
+    Root,
+    // steps taken from each node of the start, or from the context node if there is no start
+    Path(Option<Box<Expr>>, Vec<Step>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Step {
+    pub(super) axis: Axis,
+    pub(super) test: NodeTest,
+    pub(super) predicates: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Axis {
+    Ancestor,
+    AncestorOrSelf,
+    Attribute,
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Following,
+    FollowingSibling,
+    Namespace,
+    Parent,
+    Preceding,
+    PrecedingSibling,
+    SelfNode,
+}
+
+impl Axis {
+    /// Returns `true` for the axes whose nodes come before the context node in document order.
+    pub(super) fn is_reverse(self) -> bool {
+        matches!(
+            self,
+            Axis::Ancestor | Axis::AncestorOrSelf | Axis::Preceding | Axis::PrecedingSibling
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum NodeTest {
+    // a local name of None is '*', and a prefix of None with it matches any name
+    Name {
+        prefix: Option<String>,
+        local: Option<String>,
+    },
+    Node,
+    Text,
+    Comment,
+    ProcessingInstruction(Option<String>),
+}
+
+impl Step {
+    fn new(axis: Axis, test: NodeTest) -> Self {
+        Step {
+            axis,
+            test,
+            predicates: Vec::new(),
+        }
+    }
+
+    // what '//' abbreviates
+    fn descendant_or_self() -> Self {
+        Step::new(Axis::DescendantOrSelf, NodeTest::Node)
+    }
+}
+
+impl Axis {
+    fn from_name(name: &str) -> Result<Self> {
+        Ok(match name {
+            "ancestor" => Axis::Ancestor,
+            "ancestor-or-self" => Axis::AncestorOrSelf,
+            "attribute" => Axis::Attribute,
+            "child" => Axis::Child,
+            "descendant" => Axis::Descendant,
+            "descendant-or-self" => Axis::DescendantOrSelf,
+            "following" => Axis::Following,
+            "following-sibling" => Axis::FollowingSibling,
+            "namespace" => Axis::Namespace,
+            "parent" => Axis::Parent,
+            "preceding" => Axis::Preceding,
+            "preceding-sibling" => Axis::PrecedingSibling,
+            "self" => Axis::SelfNode,
+            _ => return raise!("Unknown axis '{}'.", name),
+        })
+    }
+}
+
+pub(super) fn parse(expression: &str) -> Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        pos: 0,
+    };
+    let expr = parser.or_expr()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => raise!(
+            "Unexpected {:?} in XPath expression '{}'.",
+            token,
+            expression
+        ),
+    }
+}
+
+/// A recursive descent parser with one function for each level of operator precedence.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, token: Token) -> Result<()> {
+        match self.next() {
+            Some(found) if found == token => Ok(()),
+            found => raise!("Expected {:?} but found {:?}.", token, found),
+        }
+    }
+
+    fn or_expr(&mut self) -> Result<Expr> {
+        let mut left = self.and_expr()?;
+        while self.eat(&Token::Or) {
+            left = Expr::Or(Box::new(left), Box::new(self.and_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        let mut left = self.equality_expr()?;
+        while self.eat(&Token::And) {
+            left = Expr::And(Box::new(left), Box::new(self.equality_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn equality_expr(&mut self) -> Result<Expr> {
+        let mut left = self.relational_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Equal) => Comparison::Equal,
+                Some(Token::NotEqual) => Comparison::NotEqual,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Compare(op, Box::new(left), Box::new(self.relational_expr()?));
+        }
+    }
+
+    fn relational_expr(&mut self) -> Result<Expr> {
+        let mut left = self.additive_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Less) => Comparison::Less,
+                Some(Token::LessEqual) => Comparison::LessEqual,
+                Some(Token::Greater) => Comparison::Greater,
+                Some(Token::GreaterEqual) => Comparison::GreaterEqual,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Compare(op, Box::new(left), Box::new(self.additive_expr()?));
+        }
+    }
+
+    fn additive_expr(&mut self) -> Result<Expr> {
+        let mut left = self.multiplicative_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => Arithmetic::Add,
+                Some(Token::Minus) => Arithmetic::Subtract,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Arithmetic(op, Box::new(left), Box::new(self.multiplicative_expr()?));
+        }
+    }
+
+    fn multiplicative_expr(&mut self) -> Result<Expr> {
+        let mut left = self.unary_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Multiply) => Arithmetic::Multiply,
+                Some(Token::Div) => Arithmetic::Divide,
+                Some(Token::Mod) => Arithmetic::Modulo,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Arithmetic(op, Box::new(left), Box::new(self.unary_expr()?));
+        }
+    }
+
+    fn unary_expr(&mut self) -> Result<Expr> {
+        if self.eat(&Token::Minus) {
+            return Ok(Expr::Negate(Box::new(self.unary_expr()?)));
+        }
+        self.union_expr()
+    }
+
+    fn union_expr(&mut self) -> Result<Expr> {
+        let mut left = self.path_expr()?;
+        while self.eat(&Token::Pipe) {
+            left = Expr::Union(Box::new(left), Box::new(self.path_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn path_expr(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(Token::Variable(_))
+            | Some(Token::LeftParen)
+            | Some(Token::Literal(_))
+            | Some(Token::Number(_))
+            | Some(Token::FunctionName(_)) => {}
+            _ => return self.location_path(),
+        }
+        let primary = self.primary_expr()?;
+        let predicates = self.predicates()?;
+        let start = if predicates.is_empty() {
+            primary
+        } else {
+            Expr::Filter(Box::new(primary), predicates)
+        };
+        let mut steps = Vec::new();
+        if self.eat(&Token::DoubleSlash) {
+            steps.push(Step::descendant_or_self());
+        } else if !self.eat(&Token::Slash) {
+            return Ok(start);
+        }
+        steps.extend(self.relative_path()?);
+        Ok(Expr::Path(Some(Box::new(start)), steps))
+    }
+
+    fn location_path(&mut self) -> Result<Expr> {
+        if self.eat(&Token::Slash) {
+            if !self.starts_step() {
+                return Ok(Expr::Root);
+            }
+            return Ok(Expr::Path(
+                Some(Box::new(Expr::Root)),
+                self.relative_path()?,
+            ));
+        }
+        if self.eat(&Token::DoubleSlash) {
+            let mut steps = vec![Step::descendant_or_self()];
+            steps.extend(self.relative_path()?);
+            return Ok(Expr::Path(Some(Box::new(Expr::Root)), steps));
+        }
+        Ok(Expr::Path(None, self.relative_path()?))
+    }
+
+    fn starts_step(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Token::NameTest { .. })
+                | Some(Token::NodeType(_))
+                | Some(Token::AxisName(_))
+                | Some(Token::At)
+                | Some(Token::Dot)
+                | Some(Token::DotDot)
+        )
+    }
+
+    fn relative_path(&mut self) -> Result<Vec<Step>> {
+        let mut steps = vec![self.step()?];
+        loop {
+            if self.eat(&Token::DoubleSlash) {
+                steps.push(Step::descendant_or_self());
+            } else if !self.eat(&Token::Slash) {
+                return Ok(steps);
+            }
+            steps.push(self.step()?);
+        }
+    }
+
+    fn step(&mut self) -> Result<Step> {
+        if self.eat(&Token::Dot) {
+            return Ok(Step::new(Axis::SelfNode, NodeTest::Node));
+        }
+        if self.eat(&Token::DotDot) {
+            return Ok(Step::new(Axis::Parent, NodeTest::Node));
+        }
+        let axis = if self.eat(&Token::At) {
+            Axis::Attribute
+        } else if let Some(Token::AxisName(name)) = self.peek() {
+            let axis = Axis::from_name(name)?;
+            self.pos += 1;
+            self.expect(Token::ColonColon)?;
+            axis
+        } else {
+            Axis::Child
+        };
+        let test = self.node_test()?;
+        let predicates = self.predicates()?;
+        Ok(Step {
+            axis,
+            test,
+            predicates,
+        })
+    }
+
+    fn node_test(&mut self) -> Result<NodeTest> {
+        match self.next() {
+            Some(Token::NameTest { prefix, local }) => Ok(NodeTest::Name { prefix, local }),
+            Some(Token::NodeType(node_type)) => {
+                self.expect(Token::LeftParen)?;
+                let test = match node_type.as_str() {
+                    "comment" => NodeTest::Comment,
+                    "text" => NodeTest::Text,
+                    "node" => NodeTest::Node,
+                    _ => match self.peek() {
+                        Some(Token::Literal(target)) => {
+                            let target = target.clone();
+                            self.pos += 1;
+                            NodeTest::ProcessingInstruction(Some(target))
+                        }
+                        _ => NodeTest::ProcessingInstruction(None),
+                    },
+                };
+                self.expect(Token::RightParen)?;
+                Ok(test)
+            }
+            found => raise!("Expected a node test but found {:?}.", found),
+        }
+    }
+
+    fn predicates(&mut self) -> Result<Vec<Expr>> {
+        let mut predicates = Vec::new();
+        while self.eat(&Token::LeftBracket) {
+            predicates.push(self.or_expr()?);
+            self.expect(Token::RightBracket)?;
+        }
+        Ok(predicates)
+    }
+
+    fn primary_expr(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Variable(name)) => Ok(Expr::Variable(name)),
+            Some(Token::Literal(s)) => Ok(Expr::Literal(s)),
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::LeftParen) => {
+                let expr = self.or_expr()?;
+                self.expect(Token::RightParen)?;
+                Ok(expr)
+            }
+            Some(Token::FunctionName(name)) => {
+                self.expect(Token::LeftParen)?;
+                let mut args = Vec::new();
+                if !self.eat(&Token::RightParen) {
+                    loop {
+                        args.push(self.or_expr()?);
+                        if !self.eat(&Token::Comma) {
+                            break;
+                        }
+                    }
+                    self.expect(Token::RightParen)?;
+                }
+                Ok(Expr::Function(name, args))
+            }
+            found => raise!("Expected an expression but found {:?}.", found),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(local: &str) -> NodeTest {
+        NodeTest::Name {
+            prefix: None,
+            local: Some(local.to_string()),
+        }
+    }
+
+    #[test]
+    fn abbreviations_expand() {
+        let expr = parse("//a/../@b").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Path(
+                Some(Box::new(Expr::Root)),
+                vec![
+                    Step::descendant_or_self(),
+                    Step::new(Axis::Child, name("a")),
+                    Step::new(Axis::Parent, NodeTest::Node),
+                    Step::new(Axis::Attribute, name("b")),
+                ]
+            )
+        );
+        assert_eq!(parse("/").unwrap(), Expr::Root);
+        assert_eq!(
+            parse("/ | .").unwrap(),
+            Expr::Union(
+                Box::new(Expr::Root),
+                Box::new(Expr::Path(
+                    None,
+                    vec![Step::new(Axis::SelfNode, NodeTest::Node)]
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        let expr = parse("1 + 2 * 3 = 7 or not(-1 > 0) and true()").unwrap();
+        let arithmetic = Expr::Arithmetic(
+            Arithmetic::Add,
+            Box::new(Expr::Number(1.0)),
+            Box::new(Expr::Arithmetic(
+                Arithmetic::Multiply,
+                Box::new(Expr::Number(2.0)),
+                Box::new(Expr::Number(3.0)),
+            )),
+        );
+        let not = Expr::Function(
+            "not".to_string(),
+            vec![Expr::Compare(
+                Comparison::Greater,
+                Box::new(Expr::Negate(Box::new(Expr::Number(1.0)))),
+                Box::new(Expr::Number(0.0)),
+            )],
+        );
+        assert_eq!(
+            expr,
+            Expr::Or(
+                Box::new(Expr::Compare(
+                    Comparison::Equal,
+                    Box::new(arithmetic),
+                    Box::new(Expr::Number(7.0))
+                )),
+                Box::new(Expr::And(
+                    Box::new(not),
+                    Box::new(Expr::Function("true".to_string(), Vec::new()))
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn filters_and_node_tests() {
+        let expr = parse("$v[2]//processing-instruction('x')").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Path(
+                Some(Box::new(Expr::Filter(
+                    Box::new(Expr::Variable("v".to_string())),
+                    vec![Expr::Number(2.0)]
+                ))),
+                vec![
+                    Step::descendant_or_self(),
+                    Step::new(
+                        Axis::Child,
+                        NodeTest::ProcessingInstruction(Some("x".to_string()))
+                    ),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn bad_expressions() {
+        for expression in &[
+            "",
+            "a/",
+            "a[1",
+            "f(1,)",
+            "sideways::a",
+            "child::",
+            "(1",
+            "a b",
+            "1 +",
+            "@",
+        ] {
+            assert!(parse(expression).is_err(), "{}", expression);
+        }
+    }
+}
@@ -19,13 +19,13 @@ pub fn generate_tests<P: AsRef<Path>>(output_filepath: P) {
                 line,
                 column,
             } => write_bad_syntax_test(&mut f, character_position, line, column),
-            Syntax::Good {} => write_good_syntax_test(&mut f, &xml_file),
+            Syntax::Good {} => write_good_syntax_test(&mut f, xml_file),
         }
         finish_test(&mut f);
     }
 
     Command::new("cargo")
-        .args(&["fmt", "--", output_filepath.as_ref().to_str().unwrap()])
+        .args(["fmt", "--", output_filepath.as_ref().to_str().unwrap()])
         .output()
         .expect("failed to execute process");
 }
@@ -97,7 +97,15 @@ fn write_good_syntax_test(f: &mut File, xml_file: &XmlFile) {
         writeln!(f, "let expected = &info.metadata.expected.unwrap();").unwrap();
         writeln!(f, "let equal = expected == &actual;").unwrap();
         writeln!(f, "if !equal {{").unwrap();
-        // We prefer to assert that the strings are not equal for the visual aid when debugging.
+        // We prefer to report the structural differences for the visual aid when debugging.
+        writeln!(f, "let diff = xdoc::diff::Diff::new(expected, &actual);").unwrap();
+        writeln!(f, "if !diff.is_empty() {{").unwrap();
+        writeln!(
+            f,
+            "panic!(\"the parsed document differs from the expected one:\\n{{}}\", diff);"
+        )
+        .unwrap();
+        writeln!(f, "}}").unwrap();
         writeln!(f, "let expected_str = expected.to_string();").unwrap();
         writeln!(f, "let actual_str = actual.to_string();").unwrap();
         writeln!(f, "if expected_str != actual_str {{").unwrap();
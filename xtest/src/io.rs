@@ -19,7 +19,7 @@ pub fn load_all() -> Vec<XmlFile> {
     for xml_path in xtest.iter() {
         let name = name_from_path(xml_path);
         let data_dir = xml_path.parent().unwrap();
-        let metadata_path = metadata_filepath_from_name(&name, &data_dir);
+        let metadata_path = metadata_filepath_from_name(&name, data_dir);
         result.push(XmlFile {
             name,
             xml_path: xml_path.into(),
@@ -40,7 +40,7 @@ fn my_crate_dir() -> PathBuf {
         .unwrap()
 }
 
-fn load_impl(test_name: &str, dir: &PathBuf) -> XmlFile {
+fn load_impl(test_name: &str, dir: &Path) -> XmlFile {
     let xml_file = dir.to_path_buf().join(format!("{}{}", test_name, ".xml"));
     let metadata_file = dir
         .to_path_buf()
@@ -91,11 +91,11 @@ fn data_dir() -> PathBuf {
     my_crate_dir().join("data").canonicalize().unwrap()
 }
 
-fn ext(p: &PathBuf) -> String {
+fn ext(p: &Path) -> String {
     p.extension().unwrap().to_string_lossy().to_string()
 }
 
-fn name_from_path(p: &PathBuf) -> String {
+fn name_from_path(p: &Path) -> String {
     p.file_name().unwrap().to_string_lossy().replace(".xml", "")
 }
 